@@ -0,0 +1,3 @@
+fn main() {
+    std::process::exit(tauri_app_lib::run_cli())
+}
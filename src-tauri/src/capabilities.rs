@@ -0,0 +1,252 @@
+use crate::runner::{SharedProcessRunner, Tool};
+use serde::{Deserialize, Serialize};
+
+/// Version and build configuration reported by `ffmpeg -version` or `ffprobe -version`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub struct ToolVersion {
+    /// Version as printed, e.g. `6.1.1-3ubuntu5` or `N-113456-g0ecbb6c`
+    pub version: String,
+    /// Only known for release builds; git snapshots have no release number
+    pub major: Option<u32>,
+    pub minor: Option<u32>,
+    /// Flags from the `configuration:` line
+    pub configuration: Vec<String>,
+    /// External libraries enabled at build time, e.g. `libx265` or `libvmaf`
+    pub enabled_libs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub enum MediaKind {
+    Video,
+    Audio,
+    Subtitle,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub struct CodecEntry {
+    pub name: String,
+    pub kind: MediaKind,
+    pub description: String,
+}
+
+/// Everything the installed ffmpeg build can encode, decode, write and filter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub struct Capabilities {
+    pub encoders: Vec<CodecEntry>,
+    pub decoders: Vec<CodecEntry>,
+    pub muxers: Vec<String>,
+    pub filters: Vec<String>,
+}
+
+/// Parse the first lines of `-version` output
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn parse_version(output: &str) -> Option<ToolVersion> {
+    let first_line = output.lines().next()?;
+    let version = first_line
+        .split_whitespace()
+        .skip_while(|word| *word != "version")
+        .nth(1)?
+        .to_string();
+
+    // Release tags may be prefixed with `n`, e.g. `n7.0.1`
+    let mut numbers = version
+        .strip_prefix('n')
+        .unwrap_or(&version)
+        .split(['.', '-'])
+        .map_while(|part| part.parse::<u32>().ok());
+    let major = numbers.next();
+    let minor = major.and(numbers.next());
+
+    let configuration: Vec<String> = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("configuration:"))
+        .map(|flags| flags.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let enabled_libs = configuration
+        .iter()
+        .filter_map(|flag| flag.strip_prefix("--enable-"))
+        .filter(|lib| lib.starts_with("lib"))
+        .map(str::to_string)
+        .collect();
+
+    Some(ToolVersion {
+        version,
+        major,
+        minor,
+        configuration,
+        enabled_libs,
+    })
+}
+
+/// Parse `-encoders` or `-decoders` output
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn parse_codecs(output: &str) -> Vec<CodecEntry> {
+    entries_after_legend(output)
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let flags = parts.next()?;
+            let kind = match flags.chars().next()? {
+                'V' => MediaKind::Video,
+                'A' => MediaKind::Audio,
+                'S' => MediaKind::Subtitle,
+                _ => return None,
+            };
+            let name = parts.next()?.to_string();
+            let description = parts.collect::<Vec<_>>().join(" ");
+            Some(CodecEntry {
+                name,
+                kind,
+                description,
+            })
+        })
+        .collect()
+}
+
+/// Parse `-muxers` output; formats with several names (`mov,mp4,m4a`) are split
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn parse_muxers(output: &str) -> Vec<String> {
+    entries_after_legend(output)
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let flags = parts.next()?;
+            if !flags.contains('E') {
+                return None;
+            }
+            parts.next()
+        })
+        .flat_map(|names| names.split(','))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse `-filters` output
+pub fn parse_filters(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            // Entries look like ` T.. scale  V->V  Scale the input video size`
+            match parts.as_slice() {
+                [_, name, io, ..] if io.contains("->") => Some(name.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Lines below the `---` separator that ends the flag legend
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+fn entries_after_legend(output: &str) -> impl Iterator<Item = &str> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim().starts_with("--"))
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+}
+
+fn run_listing(runner: &SharedProcessRunner, tool: Tool, flag: &str) -> Option<String> {
+    let args = [flag.to_string(), "-hide_banner".to_string()];
+    runner
+        .output_blocking(tool, &args)
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Version of ffmpeg or ffprobe, or `None` if it cannot be run
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn tool_version(runner: &SharedProcessRunner, tool: Tool) -> Option<ToolVersion> {
+    run_listing(runner, tool, "-version").and_then(|output| parse_version(&output))
+}
+
+/// Query the encoders, decoders, muxers and filters of the installed ffmpeg
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn detect_capabilities(runner: &SharedProcessRunner) -> Capabilities {
+    let listing = |flag| run_listing(runner, Tool::Ffmpeg, flag).unwrap_or_default();
+
+    Capabilities {
+        encoders: parse_codecs(&listing("-encoders")),
+        decoders: parse_codecs(&listing("-decoders")),
+        muxers: parse_muxers(&listing("-muxers")),
+        filters: detect_filters(runner),
+    }
+}
+
+/// Names of the filters the installed ffmpeg has
+pub fn detect_filters(runner: &SharedProcessRunner) -> Vec<String> {
+    parse_filters(&run_listing(runner, Tool::Ffmpeg, "-filters").unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        let output = "\
+ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers
+built with gcc 13 (Ubuntu 13.2.0-23ubuntu3)
+configuration: --prefix=/usr --enable-gpl --enable-libx265 --enable-libsvtav1 --enable-libvmaf
+libavutil      58. 29.100 / 58. 29.100
+";
+        let version = parse_version(output).unwrap();
+        assert_eq!(version.version, "6.1.1-3ubuntu5");
+        assert_eq!((version.major, version.minor), (Some(6), Some(1)));
+        assert_eq!(version.configuration.len(), 5);
+        assert_eq!(
+            version.enabled_libs,
+            vec!["libx265", "libsvtav1", "libvmaf"]
+        );
+
+        let release = parse_version("ffprobe version n7.0.2 Copyright (c) 2007-2024").unwrap();
+        assert_eq!((release.major, release.minor), (Some(7), Some(0)));
+
+        let snapshot = parse_version("ffmpeg version N-113456-g0ecbb6c Copyright").unwrap();
+        assert_eq!(snapshot.major, None);
+        assert!(parse_version("").is_none());
+    }
+
+    #[test]
+    fn test_parse_listings() {
+        let encoders = "\
+Encoders:
+ V..... = Video
+ A..... = Audio
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC (codec h264)
+ V....D h264_nvenc           NVIDIA NVENC H.264 encoder (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)
+ S..... srt                  SubRip subtitle
+";
+        let parsed = parse_codecs(encoders);
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[1].name, "h264_nvenc");
+        assert_eq!(parsed[2].kind, MediaKind::Audio);
+        assert!(parsed[0].description.starts_with("libx264 H.264"));
+
+        let muxers = "\
+ File formats:
+ D. = Demuxing supported
+ .E = Muxing supported
+ --
+  E 3g2             3GP2 (3GPP2 file format)
+  E mov,mp4,m4a     QuickTime / MOV
+ D  aa              Audible AA format files
+";
+        assert_eq!(parse_muxers(muxers), vec!["3g2", "mov", "mp4", "m4a"]);
+
+        let filters = "\
+Filters:
+  T.. = Timeline support
+  | = Source or sink filter
+ ... ssim              VV->V      Calculate the SSIM between two video streams.
+ TSC scale             V->V       Scale the input video size and/or convert the image format.
+";
+        assert_eq!(parse_filters(filters), vec!["ssim", "scale"]);
+    }
+}
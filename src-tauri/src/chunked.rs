@@ -0,0 +1,564 @@
+use crate::encoder::{
+    apply_target_quality, build_concat_command, build_segment_command, detect_hardware_encoders,
+    encode_video, run_ffmpeg, EncodingProgress, EncodingSettings, ProcessControl,
+};
+use crate::events::SharedEventSink;
+use crate::probe::{probe_keyframes, StreamType, VideoInfo};
+use crate::runner::SharedProcessRunner;
+use crate::subtitles::SubtitleMode;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{watch, Semaphore};
+
+/// Split long inputs into segments that are encoded in parallel
+///
+/// Finished segments survive a crash or restart, so the encode resumes from them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkSettings {
+    /// Target segment length in seconds; segments start on the next keyframe
+    #[serde(default = "default_segment_duration")]
+    pub segment_duration: f64,
+    /// Inputs shorter than this are encoded in one piece
+    #[serde(default = "default_min_input_duration")]
+    pub min_input_duration: f64,
+}
+
+fn default_segment_duration() -> f64 {
+    120.0
+}
+
+fn default_min_input_duration() -> f64 {
+    600.0
+}
+
+impl Default for ChunkSettings {
+    fn default() -> Self {
+        Self {
+            segment_duration: default_segment_duration(),
+            min_input_duration: default_min_input_duration(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Segment {
+    pub index: usize,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Segment {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// Why a job that asks for chunking is encoded in one piece, if it is
+///
+/// Segments carry the video only and the join adds the source's first audio
+/// track, so chunking is limited to single-pass encodes that keep nothing else.
+pub fn chunking_blocker(
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+) -> Option<&'static str> {
+    let chunked = settings.chunked.as_ref()?;
+    if video_info.duration < chunked.min_input_duration {
+        return None;
+    }
+
+    let keeps_subtitles = match settings.subtitles.mode {
+        SubtitleMode::Remove => false,
+        SubtitleMode::Auto => {
+            video_info.streams_of(StreamType::Subtitle).next().is_some()
+                || !settings.subtitles.external_files.is_empty()
+        }
+        SubtitleMode::Keep | SubtitleMode::BurnIn(_) => true,
+    };
+
+    if settings.remux {
+        Some("remuxing copies the streams unchanged")
+    } else if settings.target_size_bytes.is_some() {
+        Some("a target file size is met by encoding the whole file")
+    } else if settings.two_pass && settings.bitrate.is_some() {
+        Some("segments are encoded in a single pass")
+    } else if settings.stream_mapping.is_some() {
+        Some("segments do not support stream mapping")
+    } else if keeps_subtitles {
+        Some("segments cannot carry subtitles")
+    } else if video_info.streams_of(StreamType::Audio).count() > 1 {
+        Some("only the first audio track is joined to the segments")
+    } else {
+        None
+    }
+}
+
+/// Check if a job should be encoded in segments
+pub fn should_chunk(settings: &EncodingSettings, video_info: &VideoInfo) -> bool {
+    settings
+        .chunked
+        .as_ref()
+        .is_some_and(|chunked| video_info.duration >= chunked.min_input_duration)
+        && chunking_blocker(settings, video_info).is_none()
+}
+
+/// Cut a video into segments of roughly `segment_duration`, starting on keyframes
+///
+/// Keyframe times are packet timestamps, which count from the file's `start_time`;
+/// segment bounds count from the start of the file, as `-ss` expects. A cut is
+/// skipped if it would leave a final segment shorter than half the target.
+pub fn plan_segments(
+    keyframes: &[f64],
+    start_time: f64,
+    duration: f64,
+    segment_duration: f64,
+) -> Vec<Segment> {
+    let mut starts = vec![0.0];
+    for keyframe in keyframes.iter().map(|keyframe| keyframe - start_time) {
+        let last = *starts.last().unwrap();
+        if keyframe - last >= segment_duration && duration - keyframe >= segment_duration / 2.0 {
+            starts.push(keyframe);
+        }
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, &start)| Segment {
+            index,
+            start,
+            end: starts.get(index + 1).copied().unwrap_or(duration),
+        })
+        .collect()
+}
+
+/// Directory holding the segments of an output while it is being encoded
+pub fn segment_dir(output: &Path) -> PathBuf {
+    let name = output
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    output
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(format!(".{}.segments", name))
+}
+
+/// Finished segment; only complete encodes are renamed to this path
+fn segment_path(dir: &Path, segment: &Segment) -> PathBuf {
+    dir.join(format!("segment_{:05}.mkv", segment.index))
+}
+
+fn partial_segment_path(dir: &Path, segment: &Segment) -> PathBuf {
+    dir.join(format!("segment_{:05}.part.mkv", segment.index))
+}
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Segment plan written next to the segments so an interrupted encode can resume
+#[derive(Debug, Serialize, Deserialize)]
+struct SegmentManifest {
+    input: PathBuf,
+    /// Settings the job was started with
+    settings: EncodingSettings,
+    /// Settings after the CRF search, used for every segment
+    resolved_settings: EncodingSettings,
+    segments: Vec<Segment>,
+}
+
+fn read_manifest(work_dir: &Path) -> Option<SegmentManifest> {
+    let json = std::fs::read(work_dir.join(MANIFEST_FILE)).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+fn write_manifest(work_dir: &Path, manifest: &SegmentManifest) -> Result<()> {
+    let json = serde_json::to_vec_pretty(manifest)?;
+    std::fs::write(work_dir.join(MANIFEST_FILE), json).context("Failed to write segment manifest")
+}
+
+/// Check if a manifest was written for the same input and settings
+fn manifest_matches(manifest: &SegmentManifest, input: &Path, settings: &EncodingSettings) -> bool {
+    manifest.input == input
+        && serde_json::to_value(&manifest.settings).ok() == serde_json::to_value(settings).ok()
+}
+
+/// Finished and total segments of an interrupted chunked encode of `output`
+pub fn completed_segments(output: &Path) -> Option<(usize, usize)> {
+    let work_dir = segment_dir(output);
+    let manifest = read_manifest(&work_dir)?;
+    let done = manifest
+        .segments
+        .iter()
+        .filter(|segment| segment_path(&work_dir, segment).exists())
+        .count();
+    Some((done, manifest.segments.len()))
+}
+
+/// Shared state of the segment workers of one job
+struct ChunkContext {
+    events: SharedEventSink,
+    runner: SharedProcessRunner,
+    input: PathBuf,
+    settings: EncodingSettings,
+    video_info: VideoInfo,
+    hw_encoders: Vec<String>,
+    control: ProcessControl,
+    work_dir: PathBuf,
+    pending: std::sync::Mutex<VecDeque<Segment>>,
+    /// Seconds encoded so far, per segment
+    encoded: Arc<std::sync::Mutex<Vec<f64>>>,
+    /// Set once no segment is left to hand out or a segment failed
+    drained: watch::Sender<bool>,
+}
+
+impl ChunkContext {
+    fn next_segment(&self) -> Option<Segment> {
+        if *self.drained.borrow() {
+            return None;
+        }
+        let segment = self.pending.lock().unwrap().pop_front();
+        if segment.is_none() {
+            self.drained.send_replace(true);
+        }
+        segment
+    }
+
+    /// Encode segments until none are left
+    async fn run_worker<F>(&self, progress_callback: F) -> Result<()>
+    where
+        F: Fn(EncodingProgress) + Send + Clone + 'static,
+    {
+        while let Some(segment) = self.next_segment() {
+            if let Err(e) = self
+                .encode_segment(&segment, progress_callback.clone())
+                .await
+            {
+                // Stop handing out work; running segments finish on their own
+                self.drained.send_replace(true);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn encode_segment<F>(&self, segment: &Segment, progress_callback: F) -> Result<()>
+    where
+        F: Fn(EncodingProgress) + Send + Clone + 'static,
+    {
+        let output = segment_path(&self.work_dir, segment);
+        let partial = partial_segment_path(&self.work_dir, segment);
+        let args = build_segment_command(
+            &self.input,
+            &partial,
+            &self.settings,
+            &self.video_info,
+            &self.hw_encoders,
+            segment.start,
+            segment.duration(),
+        );
+
+        let index = segment.index;
+        let segment_duration = segment.duration();
+        let total_duration = self.video_info.duration;
+        let encoded = self.encoded.clone();
+        run_ffmpeg(
+            &self.events,
+            &self.runner,
+            args,
+            &partial,
+            segment_duration,
+            &self.control,
+            move |mut progress| {
+                let total = {
+                    let mut encoded = encoded.lock().unwrap();
+                    encoded[index] = progress.current_time.min(segment_duration);
+                    encoded.iter().sum::<f64>()
+                };
+                progress.current_time = total;
+                progress.percentage = ((total / total_duration) * 100.0).min(100.0) as f32;
+                progress_callback(progress);
+            },
+        )
+        .await?;
+
+        // A segment only counts as done once it is complete, so a crash never leaves a
+        // truncated segment behind for the resume
+        std::fs::rename(&partial, &output).context("Failed to finish segment")
+    }
+}
+
+/// Encode a long input in keyframe-aligned segments and join them losslessly
+///
+/// The calling job's own permit runs the first worker. Further workers take
+/// permits from `workers`, so segments share the queue's concurrency limit.
+#[allow(clippy::too_many_arguments)]
+pub async fn encode_chunked<F>(
+    events: SharedEventSink,
+    runner: SharedProcessRunner,
+    input: PathBuf,
+    output: PathBuf,
+    settings: EncodingSettings,
+    video_info: &VideoInfo,
+    control: ProcessControl,
+    workers: Arc<Semaphore>,
+    progress_callback: F,
+) -> Result<()>
+where
+    F: Fn(EncodingProgress) + Send + Clone + 'static,
+{
+    if control.is_cancelled() {
+        anyhow::bail!("Encoding cancelled");
+    }
+
+    let work_dir = segment_dir(&output);
+
+    // Pick up the plan of an interrupted run of the same job, if there is one
+    let manifest =
+        read_manifest(&work_dir).filter(|manifest| manifest_matches(manifest, &input, &settings));
+    let (settings, segments) = match manifest {
+        Some(manifest) => {
+            eprintln!("Resuming chunked encode of {:?}", input);
+            (manifest.resolved_settings, manifest.segments)
+        }
+        None => {
+            let resolved = apply_target_quality(
+                &events,
+                &runner,
+                &input,
+                settings.clone(),
+                video_info,
+                &control,
+            )
+            .await?;
+            let segment_duration = resolved
+                .chunked
+                .as_ref()
+                .map(|c| c.segment_duration)
+                .unwrap_or_else(default_segment_duration);
+
+            let keyframes = probe_keyframes(&runner, &input).await?;
+            let segments = plan_segments(
+                &keyframes,
+                video_info.start_time,
+                video_info.duration,
+                segment_duration,
+            );
+            if segments.len() < 2 {
+                return encode_video(
+                    events,
+                    runner,
+                    input,
+                    output,
+                    resolved,
+                    video_info,
+                    control,
+                    progress_callback,
+                )
+                .await;
+            }
+
+            // Segments from a run with other settings cannot be reused
+            let _ = std::fs::remove_dir_all(&work_dir);
+            std::fs::create_dir_all(&work_dir).context("Failed to create segment directory")?;
+            write_manifest(
+                &work_dir,
+                &SegmentManifest {
+                    input: input.clone(),
+                    settings,
+                    resolved_settings: resolved.clone(),
+                    segments: segments.clone(),
+                },
+            )?;
+            (resolved, segments)
+        }
+    };
+
+    let result = encode_segments(
+        events,
+        runner,
+        &input,
+        &output,
+        settings,
+        video_info,
+        control,
+        workers,
+        &segments,
+        &work_dir,
+        progress_callback,
+    )
+    .await;
+
+    // Segments are only kept when the app goes away mid-encode
+    let _ = std::fs::remove_dir_all(&work_dir);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn encode_segments<F>(
+    events: SharedEventSink,
+    runner: SharedProcessRunner,
+    input: &Path,
+    output: &Path,
+    settings: EncodingSettings,
+    video_info: &VideoInfo,
+    control: ProcessControl,
+    workers: Arc<Semaphore>,
+    segments: &[Segment],
+    work_dir: &Path,
+    progress_callback: F,
+) -> Result<()>
+where
+    F: Fn(EncodingProgress) + Send + Clone + 'static,
+{
+    // Segments finished by an earlier run are not encoded again
+    let pending: VecDeque<Segment> = segments
+        .iter()
+        .filter(|segment| !segment_path(work_dir, segment).exists())
+        .copied()
+        .collect();
+    let encoded: Vec<f64> = segments
+        .iter()
+        .map(|segment| {
+            if pending.contains(segment) {
+                0.0
+            } else {
+                segment.duration()
+            }
+        })
+        .collect();
+    let worker_count = pending.len();
+
+    let (drained, _) = watch::channel(false);
+    let context = Arc::new(ChunkContext {
+        events: events.clone(),
+        runner: runner.clone(),
+        input: input.to_path_buf(),
+        settings: settings.clone(),
+        video_info: video_info.clone(),
+        hw_encoders: detect_hardware_encoders(&runner),
+        control: control.clone(),
+        work_dir: work_dir.to_path_buf(),
+        pending: std::sync::Mutex::new(pending),
+        encoded: Arc::new(std::sync::Mutex::new(encoded)),
+        drained,
+    });
+
+    // Extra workers wait for a free permit, or give up once all segments are taken
+    let mut handles = Vec::new();
+    for _ in 1..worker_count {
+        let context = context.clone();
+        let workers = workers.clone();
+        let callback = progress_callback.clone();
+        handles.push(tokio::spawn(async move {
+            let mut drained = context.drained.subscribe();
+            let _permit = tokio::select! {
+                permit = workers.acquire_owned() => permit?,
+                _ = drained.wait_for(|drained| *drained) => return Ok(()),
+            };
+            context.run_worker(callback).await
+        }));
+    }
+
+    let mut result = context.run_worker(progress_callback.clone()).await;
+    for handle in handles {
+        let worker_result = handle.await.context("Segment worker panicked")?;
+        if result.is_ok() {
+            result = worker_result;
+        }
+    }
+    result?;
+
+    // Join the segments in order
+    let list = work_dir.join("segments.txt");
+    let entries: String = segments
+        .iter()
+        .map(|segment| {
+            let path = segment_path(work_dir, segment)
+                .to_string_lossy()
+                .replace('\'', "'\\''");
+            format!("file '{}'\n", path)
+        })
+        .collect();
+    std::fs::write(&list, entries).context("Failed to write segment list")?;
+
+    let args = build_concat_command(&list, input, output, &settings);
+    run_ffmpeg(
+        &events,
+        &runner,
+        args,
+        output,
+        video_info.duration,
+        &control,
+        |_| {},
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_segments() {
+        let keyframes: Vec<f64> = (0..100).map(|i| i as f64 * 10.0).collect();
+        let segments = plan_segments(&keyframes, 0.0, 1000.0, 300.0);
+        let bounds: Vec<(f64, f64)> = segments.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(bounds, vec![(0.0, 300.0), (300.0, 600.0), (600.0, 1000.0)]);
+
+        // Transport streams rarely start at timestamp 0
+        let shifted: Vec<f64> = keyframes.iter().map(|k| k + 1.5).collect();
+        let segments = plan_segments(&shifted, 1.5, 1000.0, 300.0);
+        assert_eq!(segments[1].start, 300.0);
+
+        // No keyframes to cut at
+        let segments = plan_segments(&[0.0], 0.0, 1000.0, 300.0);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].end, 1000.0);
+    }
+
+    #[test]
+    fn test_completed_segments_after_interruption() {
+        let dir = std::env::temp_dir().join(format!("rvc-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("movie.mp4");
+        let work_dir = segment_dir(&output);
+        std::fs::create_dir_all(&work_dir).unwrap();
+
+        let settings = EncodingSettings::default();
+        let segments = plan_segments(&[0.0, 300.0, 600.0], 0.0, 1000.0, 300.0);
+        write_manifest(
+            &work_dir,
+            &SegmentManifest {
+                input: PathBuf::from("movie.mkv"),
+                settings: settings.clone(),
+                resolved_settings: settings.clone(),
+                segments: segments.clone(),
+            },
+        )
+        .unwrap();
+        std::fs::write(segment_path(&work_dir, &segments[0]), b"done").unwrap();
+        std::fs::write(partial_segment_path(&work_dir, &segments[1]), b"half").unwrap();
+
+        assert_eq!(completed_segments(&output), Some((1, 3)));
+
+        let manifest = read_manifest(&work_dir).unwrap();
+        assert!(manifest_matches(
+            &manifest,
+            Path::new("movie.mkv"),
+            &settings
+        ));
+        let changed = EncodingSettings {
+            crf: Some(30),
+            ..settings
+        };
+        assert!(!manifest_matches(
+            &manifest,
+            Path::new("movie.mkv"),
+            &changed
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,775 @@
+use crate::capabilities::detect_filters;
+use crate::container::validate_codecs;
+use crate::encoder::{EncodingProgress, EncodingSettings, SourceLimitPolicy};
+use crate::events::{ChannelEventSink, Event};
+use crate::filters::{validate_filters, DeinterlaceMethod, DenoiseMethod, VideoFilter};
+use crate::hdr::HdrMode;
+use crate::pixel_format::PixelFormat;
+use crate::probe::{probe_video, VideoInfo};
+use crate::queue::{calculate_max_concurrent, Job, JobQueue, JobStatus};
+use crate::runner::{SharedProcessRunner, SystemProcessRunner, ToolPaths};
+use crate::scaling::ScaleMode;
+use crate::session::SessionManager;
+use crate::utils::{format_duration, format_file_size, generate_unique_filename, parse_resolution};
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const USAGE: &str = "\
+Usage: rvc [--json] [--verbose] [--ffmpeg <path>] [--ffprobe <path>] <command>
+
+Commands:
+  probe <input>                 Print stream and format information
+  convert <input> [options]     Convert a single file
+  queue list [--session <id>]   List the jobs of a saved session (latest by default)
+  queue run [--session <id>]    Run the pending jobs of a saved session
+  help                          Show this message
+
+Convert options:
+  -o, --output <path>           Output file (default: next to the input)
+  --settings <file>             Encoding settings as JSON, as saved by the app
+  --format <ext>                Output container, e.g. mp4, mkv, webm
+  --codec <encoder>             Video encoder or codec, e.g. libx264, libx265, vp9
+  --audio-codec <encoder>       Audio encoder or codec, e.g. aac, libopus, opus
+  --crf <n>                     Constant rate factor
+  --bitrate <kbps>              Video bitrate in kbit/s (overrides --crf)
+  --preset <name>               Encoder preset
+  --tune <name>                 Encoder tuning, e.g. film, animation
+  --profile <name>              Codec profile, e.g. high, main10
+  --level <level>               Codec level, e.g. 4.1
+  --gop <frames>                Maximum keyframe interval
+  --bframes <n>                 Maximum consecutive B-frames
+  --maxrate <kbps>              Peak video bitrate in kbit/s
+  --bufsize <kbps>              Rate control buffer size in kbit/s
+  --resolution <WxH>            Output resolution, e.g. 1280x720
+  --scale <mode>                fit, fill, pad, max-height or stretch (default fit)
+  --source-limits <policy>      clamp, keep or reject targets above the source (default clamp)
+  --hdr <mode>                  auto, preserve, tonemap or ignore for HDR sources (default auto)
+  --pix-fmt <format>            source, compatible or an ffmpeg pixel format (default source)
+  --bit-depth <bits>            Bit depth when matching the source, e.g. 10
+  --filter <spec>               Add a video filter; repeat to chain them in order:
+                                crop=W:H[:X:Y], rotate=90, hflip, vflip,
+                                deinterlace[=yadif|bwdif][:double],
+                                denoise=hqdn3d|nlmeans[:strength], sharpen=AMOUNT[:SIZE],
+                                eq=brightness=B:contrast=C:saturation=S:gamma=G, fps=N
+  --two-pass                    Two-pass encode when a bitrate is set
+  --no-hardware                 Do not use hardware encoders
+
+Exit codes: 0 success, 1 a job failed, 2 usage error, 130 cancelled";
+
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_CANCELLED: i32 = 130;
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Probe {
+        input: PathBuf,
+    },
+    Convert {
+        input: PathBuf,
+        output: Option<PathBuf>,
+        settings_file: Option<PathBuf>,
+        overrides: Vec<(String, String)>,
+    },
+    QueueList {
+        session: Option<i64>,
+    },
+    QueueRun {
+        session: Option<i64>,
+    },
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+struct Invocation {
+    command: Command,
+    json: bool,
+    verbose: bool,
+    tool_paths: ToolPaths,
+}
+
+/// Run the CLI with the arguments after the program name and return the exit code
+pub fn run(args: Vec<String>, db_path: PathBuf) -> i32 {
+    let invocation = match parse_args(&args) {
+        Ok(invocation) => invocation,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("error: failed to start runtime: {}", e);
+            return EXIT_FAILED;
+        }
+    };
+
+    match runtime.block_on(execute(invocation, db_path)) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            EXIT_FAILED
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Invocation, String> {
+    let mut json = false;
+    let mut verbose = false;
+    let mut help = false;
+    let mut positional = Vec::new();
+    let mut options: Vec<(String, String)> = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-v" | "--verbose" => verbose = true,
+            "-h" | "--help" => help = true,
+            "--two-pass" | "--no-hardware" => options.push((arg.clone(), String::new())),
+            flag if flag.starts_with('-') => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", flag))?;
+                options.push((flag.to_string(), value.clone()));
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let take_option = |options: &mut Vec<(String, String)>, names: &[&str]| {
+        options
+            .iter()
+            .position(|(flag, _)| names.contains(&flag.as_str()))
+            .map(|i| options.remove(i).1)
+    };
+    let session = |options: &mut Vec<(String, String)>| -> Result<Option<i64>, String> {
+        take_option(options, &["--session"])
+            .map(|id| {
+                id.parse()
+                    .map_err(|_| format!("invalid session id: {}", id))
+            })
+            .transpose()
+    };
+
+    let tool_paths = ToolPaths {
+        ffmpeg: take_option(&mut options, &["--ffmpeg"]).map(PathBuf::from),
+        ffprobe: take_option(&mut options, &["--ffprobe"]).map(PathBuf::from),
+    };
+
+    // Asking for help alongside any command prints the usage
+    if help {
+        return Ok(Invocation {
+            command: Command::Help,
+            json,
+            verbose,
+            tool_paths,
+        });
+    }
+
+    let positional: Vec<&str> = positional.iter().map(String::as_str).collect();
+    let command = match positional.as_slice() {
+        [] | ["help"] => Command::Help,
+        ["probe", input] => Command::Probe {
+            input: PathBuf::from(input),
+        },
+        ["convert", input] => Command::Convert {
+            input: PathBuf::from(input),
+            output: take_option(&mut options, &["-o", "--output"]).map(PathBuf::from),
+            settings_file: take_option(&mut options, &["--settings"]).map(PathBuf::from),
+            overrides: std::mem::take(&mut options),
+        },
+        ["queue", "list"] => Command::QueueList {
+            session: session(&mut options)?,
+        },
+        ["queue", "run"] => Command::QueueRun {
+            session: session(&mut options)?,
+        },
+        _ => return Err(format!("unknown command: {}", positional.join(" "))),
+    };
+
+    if let Some((flag, _)) = options.first() {
+        return Err(format!("unexpected option: {}", flag));
+    }
+
+    Ok(Invocation {
+        command,
+        json,
+        verbose,
+        tool_paths,
+    })
+}
+
+/// Parse a `--filter` value such as `crop=1280:720` or `denoise=nlmeans:3`
+fn parse_filter(spec: &str) -> Result<VideoFilter> {
+    let (name, params) = spec.split_once('=').unwrap_or((spec, ""));
+    let params: Vec<&str> = params.split(':').filter(|p| !p.is_empty()).collect();
+    let number = |index: usize| -> Result<Option<f64>> {
+        params
+            .get(index)
+            .map(|p| p.parse::<f64>())
+            .transpose()
+            .with_context(|| format!("Invalid --filter value: {}", spec))
+    };
+    let integer = |index: usize| -> Result<Option<u32>> {
+        params
+            .get(index)
+            .map(|p| p.parse::<u32>())
+            .transpose()
+            .with_context(|| format!("Invalid --filter value: {}", spec))
+    };
+
+    let filter = match name {
+        "crop" => VideoFilter::Crop {
+            width: integer(0)?.context("crop needs a width and height")?,
+            height: integer(1)?.context("crop needs a width and height")?,
+            x: integer(2)?,
+            y: integer(3)?,
+        },
+        "rotate" => VideoFilter::Rotate {
+            degrees: params
+                .first()
+                .context("rotate needs an angle")?
+                .parse()
+                .with_context(|| format!("Invalid --filter value: {}", spec))?,
+        },
+        "hflip" => VideoFilter::HorizontalFlip,
+        "vflip" => VideoFilter::VerticalFlip,
+        "deinterlace" => VideoFilter::Deinterlace {
+            method: match params.first().copied() {
+                None | Some("yadif") => DeinterlaceMethod::Yadif,
+                Some("bwdif") => DeinterlaceMethod::Bwdif,
+                Some(other) => anyhow::bail!("Unknown deinterlace method: {}", other),
+            },
+            double_rate: params.get(1) == Some(&"double"),
+        },
+        "denoise" => {
+            let method = match params.first().copied() {
+                None | Some("hqdn3d") => DenoiseMethod::Hqdn3d,
+                Some("nlmeans") => DenoiseMethod::Nlmeans,
+                Some(other) => anyhow::bail!("Unknown denoise method: {}", other),
+            };
+            let default_strength = match method {
+                DenoiseMethod::Hqdn3d => 4.0,
+                DenoiseMethod::Nlmeans => 3.0,
+            };
+            VideoFilter::Denoise {
+                method,
+                strength: number(1)?.unwrap_or(default_strength),
+            }
+        }
+        "sharpen" => VideoFilter::Sharpen {
+            amount: number(0)?.unwrap_or(1.0),
+            size: integer(1)?.unwrap_or(5),
+        },
+        "eq" => {
+            let (mut brightness, mut contrast, mut saturation, mut gamma) = (0.0, 1.0, 1.0, 1.0);
+            for param in &params {
+                let (key, value) = param
+                    .split_once('=')
+                    .with_context(|| format!("Invalid --filter value: {}", spec))?;
+                let value: f64 = value
+                    .parse()
+                    .with_context(|| format!("Invalid --filter value: {}", spec))?;
+                match key {
+                    "brightness" => brightness = value,
+                    "contrast" => contrast = value,
+                    "saturation" => saturation = value,
+                    "gamma" => gamma = value,
+                    _ => anyhow::bail!("Unknown eq option: {}", key),
+                }
+            }
+            VideoFilter::Adjust {
+                brightness,
+                contrast,
+                saturation,
+                gamma,
+            }
+        }
+        "fps" => VideoFilter::FrameRate {
+            fps: number(0)?.context("fps needs a frame rate")?,
+        },
+        _ => anyhow::bail!("Unknown filter: {}", name),
+    };
+    Ok(filter)
+}
+
+/// Apply `--flag value` overrides on top of the base settings
+fn apply_overrides(
+    mut settings: EncodingSettings,
+    overrides: &[(String, String)],
+) -> Result<EncodingSettings> {
+    for (flag, value) in overrides {
+        match flag.as_str() {
+            "--format" => settings.output_format = value.clone(),
+            "--codec" => settings.video_codec = value.clone(),
+            "--audio-codec" => settings.audio_codec = value.clone(),
+            "--preset" => settings.preset = value.clone(),
+            "--crf" => settings.crf = Some(value.parse().context("Invalid --crf value")?),
+            "--bitrate" => {
+                let kbps: u64 = value.parse().context("Invalid --bitrate value")?;
+                settings.bitrate = Some(kbps * 1000);
+            }
+            "--resolution" => {
+                settings.resolution =
+                    Some(parse_resolution(value).context("Invalid --resolution value")?)
+            }
+            "--tune" => settings.tuning.tune = Some(value.clone()),
+            "--profile" => settings.tuning.profile = Some(value.clone()),
+            "--level" => settings.tuning.level = Some(value.clone()),
+            "--gop" => {
+                settings.tuning.gop_size = Some(value.parse().context("Invalid --gop value")?)
+            }
+            "--bframes" => {
+                settings.tuning.b_frames = Some(value.parse().context("Invalid --bframes value")?)
+            }
+            "--maxrate" => {
+                let kbps: u64 = value.parse().context("Invalid --maxrate value")?;
+                settings.tuning.max_bitrate = Some(kbps * 1000);
+            }
+            "--bufsize" => {
+                let kbps: u64 = value.parse().context("Invalid --bufsize value")?;
+                settings.tuning.buffer_size = Some(kbps * 1000);
+            }
+            "--scale" => {
+                settings.scale_mode = match value.as_str() {
+                    "fit" => ScaleMode::Fit,
+                    "fill" => ScaleMode::Fill,
+                    "pad" => ScaleMode::Pad,
+                    "max-height" => ScaleMode::MaxHeight,
+                    "stretch" => ScaleMode::Stretch,
+                    _ => anyhow::bail!("Invalid --scale value: {}", value),
+                }
+            }
+            "--source-limits" => {
+                settings.source_limits = match value.as_str() {
+                    "clamp" => SourceLimitPolicy::Clamp,
+                    "keep" => SourceLimitPolicy::KeepOriginal,
+                    "reject" => SourceLimitPolicy::Reject,
+                    _ => anyhow::bail!("Invalid --source-limits value: {}", value),
+                }
+            }
+            "--hdr" => {
+                settings.hdr_mode = match value.as_str() {
+                    "auto" => HdrMode::Auto,
+                    "preserve" => HdrMode::Preserve,
+                    "tonemap" => HdrMode::ToneMap,
+                    "ignore" => HdrMode::Ignore,
+                    _ => anyhow::bail!("Invalid --hdr value: {}", value),
+                }
+            }
+            "--pix-fmt" => {
+                settings.pixel_format = match value.as_str() {
+                    "source" => PixelFormat::MatchSource,
+                    "compatible" => PixelFormat::Compatible,
+                    _ => PixelFormat::Custom(value.clone()),
+                }
+            }
+            "--bit-depth" => {
+                let bits: u8 = value.parse().context("Invalid --bit-depth value")?;
+                if !matches!(bits, 8 | 10 | 12) {
+                    anyhow::bail!("Invalid --bit-depth value: {}", value);
+                }
+                settings.bit_depth = Some(bits);
+            }
+            "--filter" => settings.filters.push(parse_filter(value)?),
+            "--two-pass" => settings.two_pass = true,
+            "--no-hardware" => settings.use_hardware = false,
+            _ => anyhow::bail!("Unknown option: {}", flag),
+        }
+    }
+    Ok(settings)
+}
+
+async fn execute(invocation: Invocation, db_path: PathBuf) -> Result<i32> {
+    let Invocation {
+        command,
+        json,
+        verbose,
+        tool_paths,
+    } = invocation;
+    let runner: SharedProcessRunner = Arc::new(SystemProcessRunner::new(tool_paths));
+
+    match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(EXIT_OK)
+        }
+        Command::Probe { input } => {
+            let info = probe_video(&runner, input).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                print_video_info(&info);
+            }
+            Ok(EXIT_OK)
+        }
+        Command::Convert {
+            input,
+            output,
+            settings_file,
+            overrides,
+        } => {
+            let base = match settings_file {
+                Some(path) => {
+                    let json = std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read {:?}", path))?;
+                    serde_json::from_str(&json).context("Failed to parse settings file")?
+                }
+                None => EncodingSettings::default(),
+            };
+            let settings = match apply_overrides(base, &overrides) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    eprintln!("error: {:#}", e);
+                    return Ok(EXIT_USAGE);
+                }
+            };
+            if let Err(e) = validate_codecs(&settings) {
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&e)?);
+                }
+                eprintln!("error: {}", e);
+                return Ok(EXIT_USAGE);
+            }
+            if let Err(e) = validate_filters(&settings.filters) {
+                eprintln!("error: {}", e);
+                return Ok(EXIT_USAGE);
+            }
+
+            let video_info = probe_video(&runner, input.clone()).await?;
+            let output = output.unwrap_or_else(|| {
+                generate_unique_filename(&input.with_extension(&settings.output_format))
+            });
+
+            let queue = JobQueue::with_runner(1, runner);
+            let filters = detect_filters(&queue.runner_for(&settings));
+            let mut job = Job::new(input, output, video_info, settings);
+            job.check_tone_mapping(&filters);
+            for adjustment in &job.adjustments {
+                eprintln!("note: {}", adjustment);
+            }
+            if let JobStatus::Failed { error } = &job.status {
+                eprintln!("error: {}", error);
+                return Ok(EXIT_FAILED);
+            }
+
+            queue.add_job(job).await;
+            run_queue(&queue, json, verbose).await
+        }
+        Command::QueueList { session } => {
+            let manager = SessionManager::new(db_path)?;
+            let (_, jobs) = load_session_jobs(&manager, session)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&jobs)?);
+            } else {
+                for job in &jobs {
+                    println!(
+                        "{}  {:<12} {} -> {}",
+                        &job.id[..8],
+                        status_label(&job.status),
+                        job.input_path.display(),
+                        job.output_path.display()
+                    );
+                }
+            }
+            Ok(EXIT_OK)
+        }
+        Command::QueueRun { session } => {
+            let manager = SessionManager::new(db_path)?;
+            let (session_id, mut jobs) = load_session_jobs(&manager, session)?;
+            for job in &mut jobs {
+                job.reconcile_interrupted();
+            }
+
+            let queue = JobQueue::with_runner(calculate_max_concurrent(), runner);
+            for job in jobs {
+                queue.add_job(job).await;
+            }
+            let code = run_queue(&queue, json, verbose).await;
+
+            manager.save_jobs(session_id, &queue.get_jobs().await)?;
+            code
+        }
+    }
+}
+
+fn load_session_jobs(manager: &SessionManager, session: Option<i64>) -> Result<(i64, Vec<Job>)> {
+    let session_id = match session {
+        Some(id) => id,
+        None => {
+            manager
+                .get_latest_session()?
+                .context("No saved session found")?
+                .id
+        }
+    };
+    Ok((session_id, manager.load_jobs(session_id)?))
+}
+
+/// Process every pending job, reporting to the terminal; Ctrl-C cancels the run
+async fn run_queue(queue: &JobQueue, json: bool, verbose: bool) -> Result<i32> {
+    let (sink, mut receiver) = ChannelEventSink::new();
+    let printer = tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            match event {
+                Event::Progress { job_id, progress } => print_progress(&job_id, &progress, json),
+                Event::StatusChange { job_id, status } => print_status(&job_id, &status, json),
+                Event::Log(line) if verbose => eprintln!("{}", line),
+                Event::Log(_) => {}
+                Event::QueueFinished => break,
+            }
+        }
+    });
+
+    let interrupt_queue = queue.clone();
+    let interrupt = tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            for job in interrupt_queue.get_jobs().await {
+                interrupt_queue.cancel_job(&job.id).await;
+            }
+        }
+    });
+
+    queue.process_all(Arc::new(sink)).await;
+    interrupt.abort();
+    let _ = printer.await;
+
+    let jobs = queue.get_jobs().await;
+    if json {
+        println!("{}", serde_json::to_string(&jobs)?);
+    }
+
+    let code = if jobs
+        .iter()
+        .any(|j| matches!(j.status, JobStatus::Failed { .. }))
+    {
+        EXIT_FAILED
+    } else if jobs.iter().any(|j| j.status == JobStatus::Cancelled) {
+        EXIT_CANCELLED
+    } else {
+        EXIT_OK
+    };
+    Ok(code)
+}
+
+fn print_progress(job_id: &str, progress: &EncodingProgress, json: bool) {
+    if json {
+        let event = serde_json::json!({ "event": "progress", "job": job_id, "progress": progress });
+        println!("{}", event);
+    } else {
+        eprint!(
+            "\r{} {:5.1}%  {}  {:.0} fps  {}   ",
+            &job_id[..8],
+            progress.percentage,
+            format_duration(progress.current_time),
+            progress.fps,
+            progress.speed
+        );
+    }
+}
+
+fn print_status(job_id: &str, status: &JobStatus, json: bool) {
+    if json {
+        let event = serde_json::json!({ "event": "status", "job": job_id, "status": status });
+        println!("{}", event);
+        return;
+    }
+
+    match status {
+        JobStatus::Completed { output_path } => {
+            eprintln!("\r{} completed: {}", &job_id[..8], output_path.display())
+        }
+        JobStatus::Failed { error } => eprintln!("\r{} failed: {}", &job_id[..8], error),
+        JobStatus::Cancelled => eprintln!("\r{} cancelled", &job_id[..8]),
+        _ => {}
+    }
+}
+
+fn status_label(status: &JobStatus) -> String {
+    match status {
+        JobStatus::Pending => "pending".to_string(),
+        JobStatus::Processing { progress } => format!("{:.0}%", progress),
+        JobStatus::Completed { .. } => "completed".to_string(),
+        JobStatus::Failed { .. } => "failed".to_string(),
+        JobStatus::Paused { .. } => "paused".to_string(),
+        JobStatus::Cancelled => "cancelled".to_string(),
+        JobStatus::Skipped { .. } => "skipped".to_string(),
+    }
+}
+
+fn print_video_info(info: &VideoInfo) {
+    println!("File:       {}", info.path.display());
+    println!("Duration:   {}", format_duration(info.duration));
+    println!(
+        "Video:      {} {}x{} @ {:.2} fps",
+        info.codec, info.width, info.height, info.fps
+    );
+    println!("Bitrate:    {} kbit/s", info.bitrate / 1000);
+    println!("Size:       {}", format_file_size(info.size));
+    for stream in &info.streams {
+        println!(
+            "  #{} {:?} {}{}",
+            stream.index,
+            stream.stream_type,
+            stream.codec.as_deref().unwrap_or("unknown"),
+            stream
+                .language
+                .as_ref()
+                .map(|l| format!(" [{}]", l))
+                .unwrap_or_default()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn with_override(flag: &str, value: &str) -> Result<EncodingSettings> {
+        apply_overrides(
+            EncodingSettings::default(),
+            &[(flag.to_string(), value.to_string())],
+        )
+    }
+
+    #[test]
+    fn test_parse_convert() {
+        let invocation = parse_args(&args(
+            "--json convert in.mkv -o out.mp4 --crf 20 --no-hardware --maxrate 5000",
+        ))
+        .unwrap();
+        assert!(invocation.json);
+        assert_eq!(
+            invocation.command,
+            Command::Convert {
+                input: PathBuf::from("in.mkv"),
+                output: Some(PathBuf::from("out.mp4")),
+                settings_file: None,
+                overrides: vec![
+                    ("--crf".to_string(), "20".to_string()),
+                    ("--no-hardware".to_string(), String::new()),
+                    ("--maxrate".to_string(), "5000".to_string()),
+                ],
+            }
+        );
+
+        let Command::Convert { overrides, .. } = invocation.command else {
+            unreachable!()
+        };
+        let settings = apply_overrides(EncodingSettings::default(), &overrides).unwrap();
+        assert_eq!(settings.crf, Some(20));
+        assert!(!settings.use_hardware);
+        assert_eq!(settings.tuning.max_bitrate, Some(5_000_000));
+    }
+
+    #[test]
+    fn test_parse_tool_paths() {
+        let invocation = parse_args(&args("--ffmpeg /opt/ffmpeg/bin/ffmpeg probe in.mkv")).unwrap();
+        assert_eq!(
+            invocation.tool_paths.ffmpeg,
+            Some(PathBuf::from("/opt/ffmpeg/bin/ffmpeg"))
+        );
+        assert_eq!(invocation.tool_paths.ffprobe, None);
+    }
+
+    #[test]
+    fn test_parse_help() {
+        for line in [
+            "-h",
+            "help",
+            "convert in.mkv --help",
+            "queue run -h --session 4",
+        ] {
+            assert_eq!(parse_args(&args(line)).unwrap().command, Command::Help);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_args(&args("queue run --session abc")).is_err());
+        assert!(parse_args(&args("probe")).is_err());
+        assert!(parse_args(&args("probe a.mkv --crf 20")).is_err());
+        assert_eq!(
+            parse_args(&args("queue run --session 4")).unwrap().command,
+            Command::QueueRun { session: Some(4) }
+        );
+    }
+
+    #[test]
+    fn test_scale_override() {
+        let settings = with_override("--scale", "pad").unwrap();
+        assert_eq!(settings.scale_mode, ScaleMode::Pad);
+        assert!(with_override("--scale", "zoom").is_err());
+    }
+
+    #[test]
+    fn test_hdr_override() {
+        let settings = with_override("--hdr", "tonemap").unwrap();
+        assert_eq!(settings.hdr_mode, HdrMode::ToneMap);
+        assert!(with_override("--hdr", "hdr10").is_err());
+    }
+
+    #[test]
+    fn test_bit_depth_override() {
+        let settings = with_override("--bit-depth", "10").unwrap();
+        assert_eq!(settings.bit_depth, Some(10));
+        assert!(with_override("--bit-depth", "9").is_err());
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            parse_filter("crop=1280:720").unwrap(),
+            VideoFilter::Crop {
+                width: 1280,
+                height: 720,
+                x: None,
+                y: None
+            }
+        );
+        assert_eq!(
+            parse_filter("denoise=nlmeans").unwrap(),
+            VideoFilter::Denoise {
+                method: DenoiseMethod::Nlmeans,
+                strength: 3.0
+            }
+        );
+        assert!(matches!(
+            parse_filter("eq=contrast=1.2").unwrap(),
+            VideoFilter::Adjust { contrast, .. } if contrast == 1.2
+        ));
+        assert!(parse_filter("crop=1280").is_err());
+        assert!(parse_filter("blur").is_err());
+        assert_eq!(
+            parse_filter("rotate=-90").unwrap(),
+            VideoFilter::Rotate { degrees: -90 }
+        );
+        assert!(parse_filter("rotate=90.5").is_err());
+
+        // Repeated filters run in the order given
+        let chained = apply_overrides(
+            EncodingSettings::default(),
+            &[
+                ("--filter".to_string(), "hflip".to_string()),
+                ("--filter".to_string(), "fps=30".to_string()),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            chained.filters,
+            vec![
+                VideoFilter::HorizontalFlip,
+                VideoFilter::FrameRate { fps: 30.0 }
+            ]
+        );
+    }
+}
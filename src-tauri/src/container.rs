@@ -0,0 +1,330 @@
+use crate::encoder::{encoder_codec_name, resolve_encoder, select_encoder, EncodingSettings};
+use crate::probe::{StreamType, VideoInfo};
+use crate::streams::{holds_attachments, plan_streams, StreamCodec};
+use crate::subtitles::{subtitle_codec_for, SubtitleMode};
+use serde::{Deserialize, Serialize};
+
+/// Codecs (ffprobe names) a container can hold without transcoding
+struct ContainerCodecs {
+    video: &'static [&'static str],
+    audio: &'static [&'static str],
+}
+
+/// Get the codec lists for a container, or `None` if it accepts (almost) anything
+fn container_codecs(container: &str) -> Option<ContainerCodecs> {
+    match container {
+        "mkv" => None,
+        "mp4" | "m4v" => Some(ContainerCodecs {
+            video: &["h264", "hevc", "av1", "vp9", "mpeg4"],
+            audio: &["aac", "mp3", "ac3", "eac3", "opus", "flac", "alac"],
+        }),
+        "mov" => Some(ContainerCodecs {
+            video: &["h264", "hevc", "prores", "mpeg4", "mjpeg"],
+            audio: &["aac", "alac", "mp3", "ac3", "pcm_s16le", "pcm_s24le"],
+        }),
+        "webm" => Some(ContainerCodecs {
+            video: &["vp9", "vp8", "av1"],
+            audio: &["vorbis", "opus"],
+        }),
+        "avi" => Some(ContainerCodecs {
+            video: &["mpeg4", "h264", "mjpeg", "msmpeg4v3"],
+            audio: &["mp3", "ac3", "pcm_s16le"],
+        }),
+        _ => Some(ContainerCodecs {
+            video: &[],
+            audio: &[],
+        }),
+    }
+}
+
+/// Check if a stream with this codec can be copied into the container as-is
+pub fn can_copy(container: &str, stream_type: StreamType, codec: &str) -> bool {
+    let Some(codecs) = container_codecs(container) else {
+        return true;
+    };
+
+    match stream_type {
+        StreamType::Video => codecs.video.contains(&codec),
+        StreamType::Audio => codecs.audio.contains(&codec),
+        _ => false,
+    }
+}
+
+/// Output formats offered for conversion, in the order alternatives are suggested
+const OUTPUT_FORMATS: &[&str] = &["mp4", "mkv", "webm", "mov", "avi"];
+
+/// Encoder used when a suggestion has to switch to another codec
+fn default_encoder(codec: &str) -> Option<&'static str> {
+    match codec {
+        "h264" => Some("libx264"),
+        "hevc" => Some("libx265"),
+        "vp8" => Some("libvpx"),
+        "vp9" => Some("libvpx-vp9"),
+        "av1" => Some("libaom-av1"),
+        "mpeg4" => Some("mpeg4"),
+        "aac" => Some("aac"),
+        "mp3" => Some("libmp3lame"),
+        "opus" => Some("libopus"),
+        "vorbis" => Some("libvorbis"),
+        "ac3" => Some("ac3"),
+        _ => None,
+    }
+}
+
+/// Codecs the output container cannot hold, with the nearest combination that works
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, thiserror::Error)]
+#[error("{}; try {} with {}", .problems.join("; "), .suggested_video_codec, .suggested_audio_codec)]
+pub struct CompatibilityError {
+    /// One entry per offending codec, e.g. `webm cannot hold h264 video`
+    pub problems: Vec<String>,
+    /// Codecs for the same container, keeping whichever of the two already fits
+    pub suggested_video_codec: String,
+    pub suggested_audio_codec: String,
+    /// Containers that accept the selected codecs unchanged
+    pub compatible_formats: Vec<String>,
+}
+
+/// Check that the output container can hold the selected video and audio codecs
+///
+/// Codecs that no known container lists (custom encoders) and `copy` are not checked.
+pub fn validate_codecs(settings: &EncodingSettings) -> Result<(), CompatibilityError> {
+    let container = settings.output_format.as_str();
+    let Some(codecs) = container_codecs(container) else {
+        return Ok(());
+    };
+    if codecs.video.is_empty() {
+        return Ok(());
+    }
+
+    let video = checked_codec(&settings.video_codec, StreamType::Video);
+    let audio = checked_codec(&settings.audio_codec, StreamType::Audio);
+    let video_fits = video.is_none_or(|codec| codecs.video.contains(&codec));
+    let audio_fits = audio.is_none_or(|codec| codecs.audio.contains(&codec));
+    if video_fits && audio_fits {
+        return Ok(());
+    }
+
+    let mut problems = Vec::new();
+    let mut suggested_video_codec = resolve_encoder(&settings.video_codec).to_string();
+    let mut suggested_audio_codec = resolve_encoder(&settings.audio_codec).to_string();
+    if let (false, Some(codec)) = (video_fits, video) {
+        problems.push(format!("{} cannot hold {} video", container, codec));
+        suggested_video_codec = first_encoder(codecs.video);
+    }
+    if let (false, Some(codec)) = (audio_fits, audio) {
+        problems.push(format!("{} cannot hold {} audio", container, codec));
+        suggested_audio_codec = first_encoder(codecs.audio);
+    }
+
+    let compatible_formats = OUTPUT_FORMATS
+        .iter()
+        .filter(|format| {
+            video.is_none_or(|codec| can_copy(format, StreamType::Video, codec))
+                && audio.is_none_or(|codec| can_copy(format, StreamType::Audio, codec))
+        })
+        .map(|format| format.to_string())
+        .collect();
+
+    Err(CompatibilityError {
+        problems,
+        suggested_video_codec,
+        suggested_audio_codec,
+        compatible_formats,
+    })
+}
+
+/// The ffprobe codec name an encoder setting produces, if it is one containers are checked for
+fn checked_codec(encoder: &str, stream_type: StreamType) -> Option<&str> {
+    let codec = encoder_codec_name(resolve_encoder(encoder));
+    let known = OUTPUT_FORMATS
+        .iter()
+        .filter_map(|format| container_codecs(format))
+        .any(|codecs| match stream_type {
+            StreamType::Video => codecs.video.contains(&codec),
+            _ => codecs.audio.contains(&codec),
+        });
+    known.then_some(codec)
+}
+
+fn first_encoder(codecs: &[&str]) -> String {
+    codecs
+        .iter()
+        .find_map(|codec| default_encoder(codec))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// What happens to a stream when remuxing
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum StreamAction {
+    Copy,
+    Transcode { encoder: String },
+    Drop { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemuxStream {
+    pub index: u32,
+    pub stream_type: StreamType,
+    pub codec: Option<String>,
+    pub action: StreamAction,
+    /// Disposition flags requested by the stream mapping
+    pub disposition: Option<String>,
+}
+
+/// Decide per stream whether a remux can copy it or has to transcode it
+pub fn plan_remux(
+    info: &VideoInfo,
+    settings: &EncodingSettings,
+    hw_encoders: &[String],
+) -> Vec<RemuxStream> {
+    let container = settings.output_format.as_str();
+    let mapping = settings.stream_mapping.clone().unwrap_or_default();
+
+    plan_streams(info, &mapping)
+        .into_iter()
+        .map(|mapped| {
+            let source = info.streams.iter().find(|s| s.index == mapped.input_index);
+            let codec = source.and_then(|s| s.codec.clone());
+
+            let action = match (&mapped.codec, mapped.stream_type) {
+                // Fonts and other attachments can only be copied, and only Matroska holds them
+                (_, StreamType::Attachment | StreamType::Data) if holds_attachments(container) => {
+                    StreamAction::Copy
+                }
+                (_, StreamType::Attachment | StreamType::Data) => StreamAction::Drop {
+                    reason: format!("not supported in {}", container),
+                },
+                (Some(StreamCodec::Copy), _) => StreamAction::Copy,
+                (Some(StreamCodec::Encode(encoder)), _) => StreamAction::Transcode {
+                    encoder: encoder.clone(),
+                },
+                (None, StreamType::Subtitle)
+                    if matches!(
+                        settings.subtitles.mode,
+                        SubtitleMode::Remove | SubtitleMode::BurnIn(_)
+                    ) =>
+                {
+                    StreamAction::Drop {
+                        reason: "subtitles are not kept".to_string(),
+                    }
+                }
+                (None, StreamType::Subtitle) => {
+                    let format = source.and_then(|s| s.subtitle_format.as_ref());
+                    match format.and_then(|f| subtitle_codec_for(container, f)) {
+                        Some("copy") => StreamAction::Copy,
+                        Some(encoder) => StreamAction::Transcode {
+                            encoder: encoder.to_string(),
+                        },
+                        None => StreamAction::Drop {
+                            reason: format!("not supported in {}", container),
+                        },
+                    }
+                }
+                (None, stream_type) => match codec.as_deref() {
+                    Some(c) if can_copy(container, stream_type, c) => StreamAction::Copy,
+                    _ => StreamAction::Transcode {
+                        encoder: match stream_type {
+                            StreamType::Video => select_encoder(settings, hw_encoders),
+                            _ => resolve_encoder(&settings.audio_codec).to_string(),
+                        },
+                    },
+                },
+            };
+
+            RemuxStream {
+                index: mapped.input_index,
+                stream_type: mapped.stream_type,
+                codec,
+                action,
+                disposition: mapped.disposition,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::StreamInfo;
+    use crate::streams::{StreamMapping, StreamSelection};
+
+    #[test]
+    fn test_plan_mkv_to_mp4_remux() {
+        let info = VideoInfo {
+            streams: vec![
+                StreamInfo::test_stream(0, StreamType::Video, "h264"),
+                StreamInfo::test_stream(1, StreamType::Audio, "aac"),
+                StreamInfo::test_stream(2, StreamType::Audio, "dts"),
+                StreamInfo::test_stream(3, StreamType::Subtitle, "subrip"),
+                StreamInfo::test_stream(4, StreamType::Subtitle, "hdmv_pgs_subtitle"),
+                StreamInfo::test_stream(5, StreamType::Attachment, "ttf"),
+                StreamInfo::test_stream(6, StreamType::Data, "bin_data"),
+            ],
+            ..VideoInfo::test_default()
+        };
+        let settings = EncodingSettings {
+            remux: true,
+            use_hardware: false,
+            ..Default::default()
+        };
+
+        let plan = plan_remux(&info, &settings, &[]);
+        let actions: Vec<&StreamAction> = plan.iter().map(|s| &s.action).collect();
+        assert_eq!(actions[0], &StreamAction::Copy);
+        assert_eq!(actions[1], &StreamAction::Copy);
+        assert_eq!(
+            actions[2],
+            &StreamAction::Transcode {
+                encoder: "aac".to_string()
+            }
+        );
+        assert_eq!(
+            actions[3],
+            &StreamAction::Transcode {
+                encoder: "mov_text".to_string()
+            }
+        );
+        assert!(matches!(actions[4], StreamAction::Drop { .. }));
+        assert!(matches!(actions[5], StreamAction::Drop { .. }));
+
+        // Data streams are only planned when picked by index
+        let mkv = EncodingSettings {
+            output_format: "mkv".to_string(),
+            stream_mapping: Some(StreamMapping {
+                selection: StreamSelection::Indices(vec![0, 5, 6]),
+                ..Default::default()
+            }),
+            ..settings
+        };
+        let plan = plan_remux(&info, &mkv, &[]);
+        assert!(plan.iter().all(|s| s.action == StreamAction::Copy));
+        assert_eq!(plan.len(), 3);
+    }
+
+    #[test]
+    fn test_validate_codecs() {
+        let settings = |format: &str, video: &str, audio: &str| EncodingSettings {
+            output_format: format.to_string(),
+            video_codec: video.to_string(),
+            audio_codec: audio.to_string(),
+            ..Default::default()
+        };
+
+        assert!(validate_codecs(&settings("webm", "vp9", "opus")).is_ok());
+        assert!(validate_codecs(&settings("mp4", "libx265", "aac")).is_ok());
+        assert!(validate_codecs(&settings("mkv", "vp9", "aac")).is_ok());
+        assert!(validate_codecs(&settings("mov", "prores_ks", "copy")).is_ok());
+
+        let error = validate_codecs(&settings("webm", "libx264", "aac")).unwrap_err();
+        assert_eq!(error.problems.len(), 2);
+        assert_eq!(error.suggested_video_codec, "libvpx-vp9");
+        assert_eq!(error.suggested_audio_codec, "libvorbis");
+        assert_eq!(error.compatible_formats, vec!["mp4", "mkv", "mov"]);
+
+        let error = validate_codecs(&settings("avi", "vp9", "opus")).unwrap_err();
+        assert_eq!(error.suggested_video_codec, "mpeg4");
+        assert_eq!(error.suggested_audio_codec, "libmp3lame");
+        assert_eq!(error.compatible_formats, vec!["mp4", "mkv", "webm"]);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::encoder::{
+    build_ffmpeg_command, geometry_filters, run_ffmpeg, EncodingSettings, ProcessControl,
+};
+use crate::events::SharedEventSink;
+use crate::probe::VideoInfo;
+use crate::quality::{detect_vmaf, measure_quality, QualityCheck};
+use crate::runner::SharedProcessRunner;
+use crate::subtitles::SubtitleSettings;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Metric used to judge sample encodes
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum TargetMetric {
+    /// SSIM, target between 0 and 1
+    #[default]
+    Ssim,
+    /// VMAF, target between 0 and 100; needs ffmpeg with libvmaf
+    Vmaf,
+}
+
+/// Pick the CRF automatically so the output reaches a quality score
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetQuality {
+    #[serde(default)]
+    pub metric: TargetMetric,
+    pub score: f64,
+    #[serde(default = "default_min_crf")]
+    pub min_crf: u8,
+    #[serde(default = "default_max_crf")]
+    pub max_crf: u8,
+    /// Number of samples taken from the input
+    #[serde(default = "default_sample_count")]
+    pub sample_count: u32,
+    /// Length of each sample in seconds
+    #[serde(default = "default_sample_duration")]
+    pub sample_duration: f64,
+}
+
+fn default_min_crf() -> u8 {
+    16
+}
+
+fn default_max_crf() -> u8 {
+    40
+}
+
+fn default_sample_count() -> u32 {
+    3
+}
+
+fn default_sample_duration() -> f64 {
+    5.0
+}
+
+/// Binary search for the highest CRF (smallest output) that still meets the target
+#[derive(Debug, Clone)]
+pub struct CrfSearch {
+    low: u8,
+    high: u8,
+    best: Option<u8>,
+    done: bool,
+}
+
+impl CrfSearch {
+    pub fn new(min_crf: u8, max_crf: u8) -> Self {
+        Self {
+            low: min_crf.min(max_crf),
+            high: max_crf.max(min_crf),
+            best: None,
+            done: false,
+        }
+    }
+
+    /// Next CRF to try, or `None` once the search has converged
+    pub fn next_crf(&self) -> Option<u8> {
+        if self.done || self.low > self.high {
+            None
+        } else {
+            Some(self.low + (self.high - self.low) / 2)
+        }
+    }
+
+    /// Record whether a CRF met the target
+    pub fn record(&mut self, crf: u8, passed: bool) {
+        if passed {
+            self.best = Some(crf);
+            if crf >= self.high {
+                self.done = true;
+            } else {
+                self.low = crf + 1;
+            }
+        } else if crf <= self.low {
+            // Nothing lower than the current bound is left to try
+            self.done = true;
+        } else {
+            self.high = crf - 1;
+        }
+    }
+
+    /// Highest passing CRF found, if any
+    pub fn best(&self) -> Option<u8> {
+        self.best
+    }
+}
+
+/// Start offsets and length of the samples taken from a video
+///
+/// Samples are spread evenly over the video. Short videos are used whole.
+pub fn sample_ranges(duration: f64, count: u32, sample_duration: f64) -> Result<Vec<(f64, f64)>> {
+    if !duration.is_finite() || duration <= 0.0 {
+        anyhow::bail!("Cannot search for a CRF without a known duration");
+    }
+
+    let count = count.max(1);
+    if duration <= sample_duration * count as f64 * 2.0 {
+        return Ok(vec![(0.0, duration)]);
+    }
+
+    Ok((1..=count)
+        .map(|i| {
+            let center = duration * i as f64 / (count + 1) as f64;
+            ((center - sample_duration / 2.0).max(0.0), sample_duration)
+        })
+        .collect())
+}
+
+/// Find the CRF that reaches the target quality by encoding samples of the input
+pub async fn find_crf(
+    events: &SharedEventSink,
+    runner: &SharedProcessRunner,
+    input: &Path,
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+    target: &TargetQuality,
+    control: &ProcessControl,
+) -> Result<u8> {
+    if target.metric == TargetMetric::Vmaf && !detect_vmaf(runner) {
+        anyhow::bail!("A VMAF quality target needs an ffmpeg build with libvmaf");
+    }
+
+    let work_dir = std::env::temp_dir().join(format!("rvc-crf-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&work_dir).context("Failed to create sample directory")?;
+
+    let result = search_samples(
+        events, runner, input, settings, video_info, target, control, &work_dir,
+    )
+    .await;
+
+    let _ = std::fs::remove_dir_all(&work_dir);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn search_samples(
+    events: &SharedEventSink,
+    runner: &SharedProcessRunner,
+    input: &Path,
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+    target: &TargetQuality,
+    control: &ProcessControl,
+    work_dir: &Path,
+) -> Result<u8> {
+    // Cut lossless reference samples so every candidate is compared against the same frames
+    let mut references = Vec::new();
+    for (i, (start, length)) in sample_ranges(
+        video_info.duration,
+        target.sample_count,
+        target.sample_duration,
+    )?
+    .into_iter()
+    .enumerate()
+    {
+        let reference = work_dir.join(format!("reference_{}.mkv", i));
+        let args = vec![
+            "-y".to_string(),
+            "-ss".to_string(),
+            start.to_string(),
+            "-t".to_string(),
+            length.to_string(),
+            "-i".to_string(),
+            input.to_string_lossy().to_string(),
+            "-map".to_string(),
+            "0:v:0".to_string(),
+            "-an".to_string(),
+            "-sn".to_string(),
+            "-c:v".to_string(),
+            "ffv1".to_string(),
+            reference.to_string_lossy().to_string(),
+        ];
+        run_ffmpeg(events, runner, args, &reference, length, control, |_| {}).await?;
+
+        let info = VideoInfo {
+            path: reference.clone(),
+            duration: length,
+            streams: Vec::new(),
+            ..video_info.clone()
+        };
+        references.push((reference, info));
+    }
+
+    // Samples are encoded with the job's codec and scaling, but only the video is kept;
+    // filters change the picture on purpose, so samples run without them to stay
+    // comparable with the reference
+    let mut sample_settings = settings.clone();
+    sample_settings.filters = Vec::new();
+    sample_settings.bitrate = None;
+    sample_settings.two_pass = false;
+    sample_settings.target_size_bytes = None;
+    sample_settings.target_quality = None;
+    sample_settings.stream_mapping = None;
+    sample_settings.subtitles = SubtitleSettings::default();
+    sample_settings.remux = false;
+    sample_settings.use_hardware = false;
+    sample_settings.quality_check = QualityCheck::default();
+
+    let check = QualityCheck {
+        use_vmaf: target.metric == TargetMetric::Vmaf,
+        ..Default::default()
+    };
+
+    let mut search = CrfSearch::new(target.min_crf, target.max_crf);
+    while let Some(crf) = search.next_crf() {
+        sample_settings.crf = Some(crf);
+
+        let mut total = 0.0;
+        for (i, (reference, info)) in references.iter().enumerate() {
+            let candidate: PathBuf =
+                work_dir.join(format!("sample_{}.{}", i, sample_settings.output_format));
+            let args = build_ffmpeg_command(reference, &candidate, &sample_settings, info, &[]);
+            run_ffmpeg(
+                events,
+                runner,
+                args,
+                &candidate,
+                info.duration,
+                control,
+                |_| {},
+            )
+            .await?;
+
+            let geometry = geometry_filters(&sample_settings, info);
+            let metrics = measure_quality(
+                runner, reference, &candidate, &geometry, &check, control, events,
+            )
+            .await?;
+            let score = match target.metric {
+                TargetMetric::Ssim => metrics.ssim,
+                TargetMetric::Vmaf => metrics.vmaf,
+            };
+            total += score.context("Quality check did not report a score")?;
+        }
+
+        let score = total / references.len() as f64;
+        events.log(format!(
+            "CRF {} scored {:.4} (target {})",
+            crf, score, target.score
+        ));
+        search.record(crf, score >= target.score);
+    }
+
+    Ok(search.best().unwrap_or_else(|| {
+        events.log(format!(
+            "No CRF between {} and {} reaches {}, using {}",
+            target.min_crf, target.max_crf, target.score, target.min_crf
+        ));
+        target.min_crf.min(target.max_crf)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crf_search_converges() {
+        // Pretend every CRF up to 27 meets the target
+        let mut search = CrfSearch::new(16, 40);
+        let mut tried = Vec::new();
+        while let Some(crf) = search.next_crf() {
+            tried.push(crf);
+            search.record(crf, crf <= 27);
+        }
+        assert_eq!(search.best(), Some(27));
+        assert!(tried.len() <= 5);
+
+        // Nothing passes
+        let mut search = CrfSearch::new(16, 40);
+        while let Some(crf) = search.next_crf() {
+            search.record(crf, false);
+        }
+        assert_eq!(search.best(), None);
+    }
+
+    #[test]
+    fn test_sample_ranges() {
+        let ranges = sample_ranges(120.0, 3, 5.0).unwrap();
+        assert_eq!(ranges, vec![(27.5, 5.0), (57.5, 5.0), (87.5, 5.0)]);
+
+        assert_eq!(sample_ranges(20.0, 3, 5.0).unwrap(), vec![(0.0, 20.0)]);
+        assert!(sample_ranges(0.0, 3, 5.0).is_err());
+    }
+}
@@ -1,10 +1,31 @@
+use crate::chunked::ChunkSettings;
+use crate::container::{plan_remux, StreamAction};
+use crate::crf_search::{find_crf, TargetQuality};
+use crate::events::SharedEventSink;
+use crate::filters::{filter_chain, filtered_info, VideoFilter};
+use crate::hdr::{color_output, hdr_handling, HdrHandling, HdrMode};
+use crate::pixel_format::{keeps_source_format, PixelFormat};
+use crate::probe::{StreamType, VideoInfo};
+use crate::quality::QualityCheck;
+use crate::rate_control::{video_codec_args, VideoTuning};
+use crate::runner::{SharedProcessRunner, Tool, ToolPaths};
+use crate::scaling::{output_size, scale_factor, scale_filters, within_target, ScaleMode};
+use crate::streams::{
+    plan_streams, renumber_streams, resolve_attachments, stream_mapping_args, MappedStream,
+    StreamCodec, StreamMapping,
+};
+use crate::subtitles::{
+    burn_in_filter, external_subtitle_args, resolve_subtitle_codecs, unsupported_subtitle_files,
+    SubtitleMode, SubtitleSettings,
+};
+use crate::utils::format_file_size;
 use anyhow::{Context, Result};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::process::Stdio;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
+use tokio::sync::watch;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncodingSettings {
@@ -12,12 +33,100 @@ pub struct EncodingSettings {
     pub video_codec: String,
     pub audio_codec: String,
     pub resolution: Option<(u32, u32)>,
+    /// How the picture is fitted into `resolution`
+    #[serde(default)]
+    pub scale_mode: ScaleMode,
+    /// What to do when `resolution` or `bitrate` exceed the source
+    #[serde(default)]
+    pub source_limits: SourceLimitPolicy,
     pub bitrate: Option<u64>,
     pub crf: Option<u8>,
     pub preset: String,
     pub use_hardware: bool,
     pub remove_metadata: bool,
     pub custom_metadata: Option<Vec<(String, String)>>,
+    /// Run a two-pass encode when a target bitrate is set
+    #[serde(default)]
+    pub two_pass: bool,
+    /// Audio bitrate in bits per second (encoder default if not set)
+    #[serde(default)]
+    pub audio_bitrate: Option<u64>,
+    /// Hard cap on the output file size; overrides `bitrate`
+    #[serde(default)]
+    pub target_size_bytes: Option<u64>,
+    /// Number of encodes attempted to get under `target_size_bytes`
+    #[serde(default = "default_target_size_attempts")]
+    pub target_size_attempts: u32,
+    /// Stream selection rules; ffmpeg's default single-stream pick if not set
+    #[serde(default)]
+    pub stream_mapping: Option<StreamMapping>,
+    #[serde(default)]
+    pub subtitles: SubtitleSettings,
+    /// Copy compatible streams into the new container instead of re-encoding
+    #[serde(default)]
+    pub remux: bool,
+    /// What to do with inputs that already match these settings
+    #[serde(default)]
+    pub compliance_policy: CompliancePolicy,
+    /// Compare the output against the input once the encode finishes
+    #[serde(default)]
+    pub quality_check: QualityCheck,
+    /// Search for the CRF that reaches a quality score; ignored when a bitrate or
+    /// target size is set
+    #[serde(default)]
+    pub target_quality: Option<TargetQuality>,
+    /// Encode long inputs in parallel segments
+    #[serde(default)]
+    pub chunked: Option<ChunkSettings>,
+    /// ffmpeg/ffprobe binaries for this job, overriding the app-wide paths
+    #[serde(default)]
+    pub tool_paths: ToolPaths,
+    /// Tune, profile, level, GOP and VBV options for the video encoder
+    #[serde(default)]
+    pub tuning: VideoTuning,
+    /// Keep or tone map HDR sources
+    #[serde(default)]
+    pub hdr_mode: HdrMode,
+    #[serde(default)]
+    pub pixel_format: PixelFormat,
+    /// Bit depth when matching the source, e.g. 10 to encode 8-bit sources in
+    /// 10-bit; the source's if not set
+    #[serde(default)]
+    pub bit_depth: Option<u8>,
+    /// Crop, rotate, deinterlace and other filters run before scaling, in order;
+    /// not applied when remuxing
+    #[serde(default)]
+    pub filters: Vec<VideoFilter>,
+}
+
+/// Handling of inputs that already satisfy the requested settings
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum CompliancePolicy {
+    /// Encode every input regardless
+    #[default]
+    Encode,
+    /// Do not produce an output
+    Skip,
+    /// Copy the streams into the target container
+    Remux,
+    /// Copy the input file to the output directory unchanged
+    Copy,
+}
+
+/// Handling of settings that ask for more resolution or bitrate than the source has
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum SourceLimitPolicy {
+    /// Shrink the target so the picture is not enlarged and cap the bitrate
+    #[default]
+    Clamp,
+    /// Keep the source resolution and cap the bitrate at the source's
+    KeepOriginal,
+    /// Refuse the job
+    Reject,
+}
+
+fn default_target_size_attempts() -> u32 {
+    3
 }
 
 impl Default for EncodingSettings {
@@ -27,12 +136,31 @@ impl Default for EncodingSettings {
             video_codec: "libx264".to_string(),
             audio_codec: "aac".to_string(),
             resolution: None,
+            scale_mode: ScaleMode::default(),
+            source_limits: SourceLimitPolicy::default(),
             bitrate: None,
             crf: Some(23),
             preset: "medium".to_string(),
             use_hardware: true,
             remove_metadata: false,
             custom_metadata: None,
+            two_pass: false,
+            audio_bitrate: None,
+            target_size_bytes: None,
+            target_size_attempts: default_target_size_attempts(),
+            stream_mapping: None,
+            subtitles: SubtitleSettings::default(),
+            remux: false,
+            compliance_policy: CompliancePolicy::default(),
+            quality_check: QualityCheck::default(),
+            target_quality: None,
+            chunked: None,
+            tool_paths: ToolPaths::default(),
+            tuning: VideoTuning::default(),
+            hdr_mode: HdrMode::default(),
+            pixel_format: PixelFormat::default(),
+            bit_depth: None,
+            filters: Vec::new(),
         }
     }
 }
@@ -46,11 +174,150 @@ pub struct EncodingProgress {
     pub bitrate: String,
 }
 
+/// Shared handle used by the queue to control a running ffmpeg process
+#[derive(Debug, Clone)]
+pub struct ProcessControl {
+    cancel_tx: Arc<watch::Sender<bool>>,
+    state: Arc<std::sync::Mutex<ProcessState>>,
+}
+
+#[derive(Debug, Default)]
+struct ProcessState {
+    /// Running ffmpeg processes; chunked encodes run several at once
+    pids: Vec<u32>,
+    suspended: bool,
+}
+
+impl ProcessControl {
+    pub fn new() -> Self {
+        let (cancel_tx, _) = watch::channel(false);
+        Self {
+            cancel_tx: Arc::new(cancel_tx),
+            state: Arc::new(std::sync::Mutex::new(ProcessState::default())),
+        }
+    }
+
+    /// Suspend the running ffmpeg processes in place (SIGSTOP)
+    ///
+    /// A process spawned while suspended (e.g. the software fallback) is stopped right away.
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fn suspend(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Err(err) = signal_all(&state.pids, ProcessSignal::Stop) {
+            // Don't leave some segments stopped while the job still shows as running
+            let _ = signal_all(&state.pids, ProcessSignal::Continue);
+            return Err(err);
+        }
+        state.suspended = true;
+        Ok(())
+    }
+
+    /// Resume suspended ffmpeg processes (SIGCONT)
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fn resume(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.suspended = false;
+        signal_all(&state.pids, ProcessSignal::Continue)
+    }
+
+    /// Check if the process is currently suspended
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fn is_suspended(&self) -> bool {
+        self.state.lock().unwrap().suspended
+    }
+
+    pub(crate) fn attach(&self, pid: Option<u32>) -> Result<()> {
+        let Some(pid) = pid else {
+            return Ok(());
+        };
+        let mut state = self.state.lock().unwrap();
+        if state.suspended {
+            send_signal(pid, ProcessSignal::Stop)?;
+        }
+        state.pids.push(pid);
+        Ok(())
+    }
+
+    pub(crate) fn detach(&self, pid: Option<u32>) {
+        self.state.lock().unwrap().pids.retain(|&p| Some(p) != pid);
+    }
+
+    /// Request cancellation; a running ffmpeg child is killed as soon as possible
+    pub fn cancel(&self) {
+        self.cancel_tx.send_replace(true);
+    }
+
+    /// Check if cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel_tx.borrow()
+    }
+
+    /// Resolve once cancellation has been requested
+    pub(crate) async fn cancelled(&self) {
+        let mut rx = self.cancel_tx.subscribe();
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+impl Default for ProcessControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ProcessSignal {
+    Stop,
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    Continue,
+}
+
+#[cfg(unix)]
+fn send_signal(pid: u32, signal: ProcessSignal) -> Result<()> {
+    let signal = match signal {
+        ProcessSignal::Stop => libc::SIGSTOP,
+        ProcessSignal::Continue => libc::SIGCONT,
+    };
+
+    // SAFETY: kill(2) has no memory safety requirements; the pid belongs to our own child
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .context("Failed to send signal to ffmpeg process");
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_signal(_pid: u32, _signal: ProcessSignal) -> Result<()> {
+    anyhow::bail!("Pausing a running encode is not supported on this platform")
+}
+
+/// Signal every process, reporting all the ones that failed rather than just the first
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+fn signal_all(pids: &[u32], signal: ProcessSignal) -> Result<()> {
+    let failures: Vec<String> = pids
+        .iter()
+        .filter_map(|&pid| {
+            send_signal(pid, signal)
+                .err()
+                .map(|e| format!("pid {}: {}", pid, e.root_cause()))
+        })
+        .collect();
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "Failed to signal {} of {} ffmpeg processes ({})",
+            failures.len(),
+            pids.len(),
+            failures.join(", ")
+        );
+    }
+    Ok(())
+}
+
 /// Detect available hardware encoders
-pub fn detect_hardware_encoders() -> Vec<String> {
-    let output = std::process::Command::new("ffmpeg")
-        .args(["-encoders", "-hide_banner"])
-        .output();
+pub fn detect_hardware_encoders(runner: &SharedProcessRunner) -> Vec<String> {
+    let args = ["-encoders".to_string(), "-hide_banner".to_string()];
+    let output = runner.output_blocking(Tool::Ffmpeg, &args);
 
     if let Ok(output) = output {
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -88,12 +355,13 @@ pub fn detect_hardware_encoders() -> Vec<String> {
 
 /// Select best encoder based on settings and available hardware
 pub fn select_encoder(settings: &EncodingSettings, hw_encoders: &[String]) -> String {
+    let video_codec = resolve_encoder(&settings.video_codec);
     if !settings.use_hardware || hw_encoders.is_empty() {
-        return settings.video_codec.clone();
+        return video_codec.to_string();
     }
 
     // Map software codec to hardware equivalent
-    let hw_codec = match settings.video_codec.as_str() {
+    let hw_codec = match video_codec {
         "libx264" | "h264" => {
             // Prefer NVENC > QSV > VAAPI
             if hw_encoders.contains(&"h264_nvenc".to_string()) {
@@ -103,7 +371,7 @@ pub fn select_encoder(settings: &EncodingSettings, hw_encoders: &[String]) -> St
             } else if hw_encoders.contains(&"h264_vaapi".to_string()) {
                 "h264_vaapi"
             } else {
-                &settings.video_codec
+                video_codec
             }
         }
         "libx265" | "hevc" => {
@@ -114,10 +382,10 @@ pub fn select_encoder(settings: &EncodingSettings, hw_encoders: &[String]) -> St
             } else if hw_encoders.contains(&"hevc_vaapi".to_string()) {
                 "hevc_vaapi"
             } else {
-                &settings.video_codec
+                video_codec
             }
         }
-        _ => &settings.video_codec,
+        _ => video_codec,
     };
 
     hw_codec.to_string()
@@ -133,17 +401,100 @@ pub fn calculate_safe_bitrate(target: u64, original: u64) -> u64 {
 }
 
 /// Validate resolution (prevent upscaling)
-pub fn validate_resolution(target: (u32, u32), original: (u32, u32)) -> Result<(u32, u32)> {
-    if target.0 > original.0 || target.1 > original.1 {
+///
+/// Returns the size of the output frame once the source is fitted into `target`.
+pub fn validate_resolution(
+    target: (u32, u32),
+    mode: ScaleMode,
+    info: &VideoInfo,
+) -> Result<(u32, u32)> {
+    let display = info.display_size();
+    let output = output_size(display, target, mode);
+    // Even-dimension rounding may enlarge by a pixel, which is not an upscale
+    if scale_factor(display, target, mode) > 1.0 && output != display {
         anyhow::bail!(
             "Cannot upscale video from {}x{} to {}x{}",
-            original.0,
-            original.1,
-            target.0,
-            target.1
+            display.0,
+            display.1,
+            output.0,
+            output.1
         );
     }
-    Ok(target)
+    Ok(output)
+}
+
+/// Video bitrate of the source, estimated from the container if the stream has none
+fn source_video_bitrate(info: &VideoInfo) -> u64 {
+    info.streams_of(StreamType::Video)
+        .next()
+        .and_then(|s| s.bitrate)
+        .unwrap_or_else(|| info.bitrate.saturating_sub(info.audio_bitrate.unwrap_or(0)))
+}
+
+/// Apply the upscale and bitrate guards to a job's settings
+///
+/// Returns the settings the encode should use and a note for every change, or
+/// an error if the policy is `Reject` and a limit is exceeded.
+pub fn apply_source_limits(
+    settings: &EncodingSettings,
+    info: &VideoInfo,
+) -> Result<(EncodingSettings, Vec<String>)> {
+    // Limits apply to the picture the filters leave, which must exist
+    let info = &filtered_info(&settings.filters, info)?;
+    let mut effective = settings.clone();
+    let mut adjustments = Vec::new();
+    let policy = settings.source_limits;
+
+    if let Some(target) = settings.resolution {
+        if let Err(e) = validate_resolution(target, settings.scale_mode, info) {
+            let display = info.display_size();
+            match policy {
+                SourceLimitPolicy::Reject => return Err(e),
+                SourceLimitPolicy::KeepOriginal => {
+                    effective.resolution = None;
+                    adjustments.push(format!(
+                        "Kept the source resolution {}x{}",
+                        display.0, display.1
+                    ));
+                }
+                SourceLimitPolicy::Clamp => {
+                    // Shrinking the box by the upscale factor keeps its shape
+                    let factor = scale_factor(display, target, settings.scale_mode);
+                    let clamped = (
+                        ((target.0 as f64 / factor) as u32).max(2) & !1,
+                        ((target.1 as f64 / factor) as u32).max(2) & !1,
+                    );
+                    effective.resolution = Some(clamped);
+                    adjustments.push(format!(
+                        "Resolution clamped from {}x{} to {}x{}",
+                        target.0, target.1, clamped.0, clamped.1
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some(target) = settings.bitrate {
+        let source = source_video_bitrate(info);
+        let safe = calculate_safe_bitrate(target, source);
+        if safe != target {
+            if policy == SourceLimitPolicy::Reject {
+                anyhow::bail!(
+                    "Target bitrate {} kbps is above the source's {} kbps",
+                    target / 1000,
+                    source / 1000
+                );
+            }
+            effective.bitrate = Some(safe);
+            adjustments.push(format!(
+                "Bitrate capped from {} kbps to the source's {} kbps",
+                target / 1000,
+                safe / 1000
+            ));
+        }
+    }
+
+    Ok((effective, adjustments))
 }
 
 /// Estimate output file size in bytes
@@ -151,59 +502,561 @@ pub fn estimate_output_size(bitrate: u64, duration: f64) -> u64 {
     ((bitrate as f64 * duration) / 8.0) as u64
 }
 
+/// Friendly codec names accepted in settings and the ffmpeg encoder used for each
+const ENCODER_ALIASES: &[(&str, &str)] = &[
+    ("h264", "libx264"),
+    ("hevc", "libx265"),
+    ("h265", "libx265"),
+    ("vp8", "libvpx"),
+    ("vp9", "libvpx-vp9"),
+    ("av1", "libaom-av1"),
+    ("mp3", "libmp3lame"),
+    ("opus", "libopus"),
+    ("vorbis", "libvorbis"),
+];
+
+/// Map a friendly codec name such as `vp9` to the ffmpeg encoder that produces it
+///
+/// Encoder names and `copy` are returned unchanged.
+pub fn resolve_encoder(name: &str) -> &str {
+    ENCODER_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, encoder)| encoder)
+}
+
+/// Map an encoder name to the codec name ffprobe reports for its output
+pub fn encoder_codec_name(encoder: &str) -> &str {
+    match encoder {
+        "libx264" | "h264" | "h264_nvenc" | "h264_qsv" | "h264_vaapi" => "h264",
+        "libx265" | "hevc" | "hevc_nvenc" | "hevc_qsv" | "hevc_vaapi" => "hevc",
+        "libvpx" | "vp8" => "vp8",
+        "libvpx-vp9" | "vp9" => "vp9",
+        "libaom-av1" | "libsvtav1" | "av1" | "av1_nvenc" | "av1_qsv" | "av1_vaapi" => "av1",
+        "libfdk_aac" => "aac",
+        "libopus" => "opus",
+        "libvorbis" => "vorbis",
+        "libmp3lame" => "mp3",
+        other => other,
+    }
+}
+
+/// Check if an input already satisfies the requested settings
+///
+/// The codecs must match and the source must not exceed the target resolution,
+/// bitrate or file size.
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn is_already_compliant(info: &VideoInfo, settings: &EncodingSettings) -> bool {
+    if info.codec != encoder_codec_name(&settings.video_codec) {
+        return false;
+    }
+
+    if let Some(audio_codec) = &info.audio_codec {
+        if audio_codec != encoder_codec_name(&settings.audio_codec) {
+            return false;
+        }
+    }
+
+    if let Some(target) = settings.resolution {
+        if !within_target(info.display_size(), target, settings.scale_mode) {
+            return false;
+        }
+    }
+
+    if let Some(target) = settings.bitrate {
+        if source_video_bitrate(info) > target {
+            return false;
+        }
+    }
+
+    if let Some(target_size) = settings.target_size_bytes {
+        if info.size > target_size {
+            return false;
+        }
+    }
+
+    if !settings.filters.is_empty() {
+        return false;
+    }
+
+    // HDR sources that should end up SDR always need an encode
+    if hdr_handling(settings, info) == HdrHandling::ToneMap {
+        return false;
+    }
+
+    if !keeps_source_format(settings, info, resolve_encoder(&settings.video_codec)) {
+        return false;
+    }
+
+    true
+}
+
+/// Check if an encoder supports two-pass rate control
+pub fn supports_two_pass(encoder: &str) -> bool {
+    matches!(encoder, "libx264" | "libx265" | "libvpx-vp9" | "libaom-av1")
+}
+
+/// Check if a two-pass encode will be run for these settings
+pub fn uses_two_pass(settings: &EncodingSettings, encoder: &str) -> bool {
+    !settings.remux && settings.two_pass && settings.bitrate.is_some() && supports_two_pass(encoder)
+}
+
+/// Fraction of a target file size reserved for container overhead
+const CONTAINER_OVERHEAD: f64 = 0.02;
+
+/// Audio bitrate reserved for target-size encodes when none is configured
+const DEFAULT_AUDIO_BITRATE: u64 = 128_000;
+
+/// Lowest video bitrate a target-size encode is allowed to use
+const MIN_VIDEO_BITRATE: u64 = 50_000;
+
+/// Audio bitrate to reserve when encoding to a target size
+pub fn target_audio_bitrate(settings: &EncodingSettings, video_info: &VideoInfo) -> u64 {
+    if video_info.audio_codec.is_none() {
+        return 0;
+    }
+
+    settings.audio_bitrate.unwrap_or_else(|| {
+        video_info
+            .audio_bitrate
+            .map(|b| b.min(DEFAULT_AUDIO_BITRATE))
+            .unwrap_or(DEFAULT_AUDIO_BITRATE)
+    })
+}
+
+/// Calculate the video bitrate needed to fit a file of `target_size` bytes
+pub fn calculate_target_bitrate(
+    target_size: u64,
+    duration: f64,
+    audio_bitrate: u64,
+) -> Result<u64> {
+    if duration <= 0.0 {
+        anyhow::bail!("Cannot encode to a target size without a known duration");
+    }
+
+    let usable = target_size as f64 * (1.0 - CONTAINER_OVERHEAD);
+    let audio_size = estimate_output_size(audio_bitrate, duration) as f64;
+    let video_bitrate = ((usable - audio_size) * 8.0 / duration).max(0.0) as u64;
+
+    if video_bitrate < MIN_VIDEO_BITRATE {
+        anyhow::bail!(
+            "Target size of {} is too small for a {:.0}s video",
+            format_file_size(target_size),
+            duration
+        );
+    }
+
+    Ok(video_bitrate)
+}
+
 /// Build FFmpeg command arguments
 pub fn build_ffmpeg_command(
     input: &PathBuf,
     output: &PathBuf,
     settings: &EncodingSettings,
+    video_info: &VideoInfo,
     hw_encoders: &[String],
 ) -> Vec<String> {
-    let mut args = vec![
-        "-i".to_string(),
-        input.to_str().unwrap().to_string(),
+    build_ffmpeg_args(input, output, settings, video_info, hw_encoders, None)
+}
+
+/// Build FFmpeg command arguments for one pass of a two-pass encode
+///
+/// Pass 1 only analyses the video and writes its statistics to `passlog`,
+/// pass 2 produces the real output.
+pub fn build_ffmpeg_pass_command(
+    input: &Path,
+    output: &Path,
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+    hw_encoders: &[String],
+    pass: u8,
+    passlog: &Path,
+) -> Vec<String> {
+    build_ffmpeg_args(
+        input,
+        output,
+        settings,
+        video_info,
+        hw_encoders,
+        Some((pass, passlog)),
+    )
+}
+
+/// Streams an encode maps explicitly, or `None` to leave the choice to ffmpeg
+///
+/// Keeping subtitles needs explicit maps, otherwise ffmpeg picks a single stream.
+fn mapped_streams(
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+) -> Option<Vec<MappedStream>> {
+    match &settings.stream_mapping {
+        Some(mapping) => Some(plan_streams(video_info, mapping)),
+        None if settings.subtitles.maps_all_subtitles() => {
+            Some(plan_streams(video_info, &StreamMapping::default()))
+        }
+        None => None,
+    }
+}
+
+/// Notes for the subtitles a job keeps that its container cannot hold
+///
+/// The encode leaves these out; the notes make that visible on the job.
+pub fn subtitle_adjustments(settings: &EncodingSettings, video_info: &VideoInfo) -> Vec<String> {
+    if !matches!(
+        settings.subtitles.mode,
+        SubtitleMode::Auto | SubtitleMode::Keep
+    ) {
+        return Vec::new();
+    }
+
+    // Remuxing plans its streams separately and reports drops in the remux plan
+    let mut notes = match mapped_streams(settings, video_info) {
+        Some(mut plan) if !settings.remux => {
+            resolve_subtitle_codecs(&mut plan, video_info, &settings.output_format)
+        }
+        _ => Vec::new(),
+    };
+    notes.extend(unsupported_subtitle_files(
+        &settings.subtitles.external_files,
+        &settings.output_format,
+    ));
+    notes
+}
+
+fn build_ffmpeg_args(
+    input: &Path,
+    output: &Path,
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+    hw_encoders: &[String],
+    pass: Option<(u8, &Path)>,
+) -> Vec<String> {
+    if settings.remux {
+        return build_remux_args(input, output, settings, video_info, hw_encoders);
+    }
+
+    let first_pass = matches!(pass, Some((1, _)));
+    let subtitle_mode = &settings.subtitles.mode;
+
+    let mut stream_plan = mapped_streams(settings, video_info);
+    if let Some(plan) = &mut stream_plan {
+        match subtitle_mode {
+            SubtitleMode::Auto | SubtitleMode::Keep => {
+                // Dropped streams are reported by `subtitle_adjustments`
+                resolve_subtitle_codecs(plan, video_info, &settings.output_format);
+            }
+            SubtitleMode::Remove | SubtitleMode::BurnIn(_) => {
+                plan.retain(|s| s.stream_type != StreamType::Subtitle)
+            }
+        }
+        resolve_attachments(plan, &settings.output_format);
+    }
+
+    // External subtitle files become additional inputs
+    let (subtitle_inputs, subtitle_outputs) = match subtitle_mode {
+        SubtitleMode::Auto | SubtitleMode::Keep if !first_pass => {
+            let mapped = stream_plan
+                .iter()
+                .flatten()
+                .filter(|s| s.stream_type == StreamType::Subtitle)
+                .count();
+            external_subtitle_args(
+                &settings.subtitles.external_files,
+                mapped,
+                &settings.output_format,
+            )
+        }
+        _ => (Vec::new(), Vec::new()),
+    };
+
+    let mut args = vec!["-i".to_string(), input.to_str().unwrap().to_string()];
+    args.extend(subtitle_inputs);
+    args.extend([
         "-y".to_string(),       // Overwrite output file
         "-threads".to_string(), // Use multiple threads for encoding
         "0".to_string(),        // 0 means optimal number of threads based on CPU cores
-    ];
+    ]);
 
     // Video codec
     let encoder = select_encoder(settings, hw_encoders);
     args.push("-c:v".to_string());
     args.push(encoder.clone());
 
-    // Ensure pixel format for compatibility
+    // 8-bit 4:2:0 for compatibility, unless HDR is kept
+    let color = color_output(settings, video_info, &encoder);
     args.push("-pix_fmt".to_string());
-    args.push("yuv420p".to_string());
+    args.push(color.pix_fmt);
+    args.extend(color.args);
 
-    // Resolution and scaling
-    if let Some((width, height)) = settings.resolution {
-        args.push("-vf".to_string());
-        args.push(format!("scale={}:{}", width, height));
-    } else {
-        // Force even dimensions for compatibility (trunc(iw/2)*2) if keeping original
-        args.push("-vf".to_string());
-        args.push("scale=trunc(iw/2)*2:trunc(ih/2)*2".to_string());
+    // Tone mapping works on the source resolution, after the deinterlacer (always the
+    // first filter) has seen the source's fields; then the rest of the user's filters
+    // run and scaling works from the picture they leave
+    let (deinterlace, other_filters) = match settings.filters.split_first() {
+        Some((first @ VideoFilter::Deinterlace { .. }, rest)) => {
+            (std::slice::from_ref(first), rest)
+        }
+        _ => (&[][..], settings.filters.as_slice()),
+    };
+    let mut video_filters = filter_chain(deinterlace);
+    video_filters.extend(color.filters);
+    video_filters.extend(filter_chain(other_filters));
+    video_filters.extend(geometry_filters(settings, video_info));
+
+    // Burn subtitles in after scaling so they render at output resolution
+    if let SubtitleMode::BurnIn(source) = subtitle_mode {
+        video_filters.push(burn_in_filter(input, source, video_info));
     }
 
-    // Bitrate or CRF
-    if let Some(bitrate) = settings.bitrate {
-        args.push("-b:v".to_string());
-        args.push(format!("{}k", bitrate / 1000));
-    } else if let Some(crf) = settings.crf {
-        // CRF not supported by all hardware encoders
-        if !encoder.contains("nvenc") && !encoder.contains("qsv") && !encoder.contains("vaapi") {
-            args.push("-crf".to_string());
-            args.push(crf.to_string());
+    args.push("-vf".to_string());
+    args.push(video_filters.join(","));
+
+    // Bitrate or CRF, preset, tuning and two-pass statistics
+    args.extend(video_codec_args(
+        settings,
+        &encoder,
+        color.x265_params,
+        pass,
+    ));
+
+    // The analysis pass discards its output, so skip audio and metadata
+    if first_pass {
+        if let Some(plan) = &stream_plan {
+            for stream in plan.iter().filter(|s| s.stream_type == StreamType::Video) {
+                args.push("-map".to_string());
+                args.push(format!("0:{}", stream.input_index));
+            }
         }
+        args.push("-an".to_string());
+        args.push("-sn".to_string());
+        args.push("-progress".to_string());
+        args.push("pipe:1".to_string());
+        args.push("-f".to_string());
+        args.push("null".to_string());
+        args.push(null_device().to_string());
+        return args;
     }
 
-    // Preset
-    args.push("-preset".to_string());
-    args.push(settings.preset.clone());
-
     // Audio codec
     args.push("-c:a".to_string());
-    args.push(settings.audio_codec.clone());
+    args.push(resolve_encoder(&settings.audio_codec).to_string());
+    if let Some(audio_bitrate) = settings.audio_bitrate {
+        args.push("-b:a".to_string());
+        args.push(format!("{}k", audio_bitrate / 1000));
+    }
+
+    // Stream mapping, per-stream codecs and dispositions
+    if let Some(plan) = &stream_plan {
+        args.extend(stream_mapping_args(plan));
+    }
+    args.extend(subtitle_outputs);
+    if matches!(
+        subtitle_mode,
+        SubtitleMode::Remove | SubtitleMode::BurnIn(_)
+    ) {
+        args.push("-sn".to_string());
+    }
+
+    args.extend(metadata_args(settings));
+
+    // Progress reporting
+    args.push("-progress".to_string());
+    args.push("pipe:1".to_string());
+
+    // Output file
+    args.push(output.to_str().unwrap().to_string());
+
+    args
+}
+
+/// Filters that bring the picture left by the user's filters to the output frame size
+///
+/// The quality check runs the source through the same filters so both sides match.
+pub fn geometry_filters(settings: &EncodingSettings, video_info: &VideoInfo) -> Vec<String> {
+    let filtered =
+        filtered_info(&settings.filters, video_info).unwrap_or_else(|_| video_info.clone());
+    match settings.resolution {
+        Some(target) => scale_filters(&filtered, target, settings.scale_mode),
+        // Force even dimensions for compatibility (trunc(iw/2)*2) if keeping original
+        None => vec!["scale=trunc(iw/2)*2:trunc(ih/2)*2".to_string()],
+    }
+}
+
+/// Build FFmpeg arguments that copy compatible streams and transcode only the rest
+fn build_remux_args(
+    input: &Path,
+    output: &Path,
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+    hw_encoders: &[String],
+) -> Vec<String> {
+    let remux_plan = plan_remux(video_info, settings, hw_encoders);
+
+    let mut plan: Vec<MappedStream> = remux_plan
+        .iter()
+        .filter_map(|stream| {
+            let codec = match &stream.action {
+                StreamAction::Copy => StreamCodec::Copy,
+                StreamAction::Transcode { encoder } => StreamCodec::Encode(encoder.clone()),
+                StreamAction::Drop { .. } => return None,
+            };
+            Some(MappedStream {
+                input_index: stream.index,
+                stream_type: stream.stream_type,
+                output_index: 0,
+                codec: Some(codec),
+                disposition: stream.disposition.clone(),
+            })
+        })
+        .collect();
+    renumber_streams(&mut plan);
+
+    let (subtitle_inputs, subtitle_outputs) = match settings.subtitles.mode {
+        SubtitleMode::Auto | SubtitleMode::Keep => {
+            let mapped = plan
+                .iter()
+                .filter(|s| s.stream_type == StreamType::Subtitle)
+                .count();
+            external_subtitle_args(
+                &settings.subtitles.external_files,
+                mapped,
+                &settings.output_format,
+            )
+        }
+        _ => (Vec::new(), Vec::new()),
+    };
+
+    let mut args = vec!["-i".to_string(), input.to_str().unwrap().to_string()];
+    args.extend(subtitle_inputs);
+    args.push("-y".to_string());
+
+    args.extend(stream_mapping_args(&plan));
+    args.extend(subtitle_outputs);
+
+    // Quality settings only matter for video streams that have to be transcoded
+    let video_encoder = remux_plan.iter().find_map(|s| match &s.action {
+        StreamAction::Transcode { encoder } if s.stream_type == StreamType::Video => {
+            Some(encoder.clone())
+        }
+        _ => None,
+    });
+    if let Some(encoder) = video_encoder {
+        let color = color_output(settings, video_info, &encoder);
+        args.push("-pix_fmt".to_string());
+        args.push(color.pix_fmt);
+        args.extend(color.args);
+        if !color.filters.is_empty() {
+            args.push("-vf".to_string());
+            args.push(color.filters.join(","));
+        }
+        args.extend(video_codec_args(
+            settings,
+            &encoder,
+            color.x265_params,
+            None,
+        ));
+    }
+
+    args.extend(metadata_args(settings));
+
+    // Progress reporting
+    args.push("-progress".to_string());
+    args.push("pipe:1".to_string());
+
+    // Output file
+    args.push(output.to_str().unwrap().to_string());
+
+    args
+}
+
+/// Build FFmpeg arguments for one video-only segment of a chunked encode
+///
+/// Audio is left out and muxed from the source when the segments are joined.
+pub fn build_segment_command(
+    input: &Path,
+    output: &Path,
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+    hw_encoders: &[String],
+    start: f64,
+    duration: f64,
+) -> Vec<String> {
+    let mut segment_settings = settings.clone();
+    segment_settings.stream_mapping = None;
+    segment_settings.subtitles = SubtitleSettings {
+        mode: SubtitleMode::Remove,
+        ..Default::default()
+    };
+
+    let mut args = vec![
+        "-ss".to_string(),
+        start.to_string(),
+        "-t".to_string(),
+        duration.to_string(),
+    ];
+    args.extend(build_ffmpeg_args(
+        input,
+        output,
+        &segment_settings,
+        video_info,
+        hw_encoders,
+        None,
+    ));
+
+    // Output-only options must come before the output file
+    let output_position = args.len() - 1;
+    args.insert(output_position, "-an".to_string());
+    args
+}
+
+/// Build FFmpeg arguments that join encoded segments and add the source audio
+pub fn build_concat_command(
+    segment_list: &Path,
+    source: &Path,
+    output: &Path,
+    settings: &EncodingSettings,
+) -> Vec<String> {
+    let mut args = vec![
+        "-y".to_string(),
+        "-f".to_string(),
+        "concat".to_string(),
+        "-safe".to_string(),
+        "0".to_string(),
+        "-i".to_string(),
+        segment_list.to_string_lossy().to_string(),
+        "-i".to_string(),
+        source.to_string_lossy().to_string(),
+        "-map".to_string(),
+        "0:v".to_string(),
+        "-map".to_string(),
+        "1:a:0?".to_string(),
+        "-c:v".to_string(),
+        "copy".to_string(),
+        "-c:a".to_string(),
+        resolve_encoder(&settings.audio_codec).to_string(),
+    ];
+    if let Some(audio_bitrate) = settings.audio_bitrate {
+        args.push("-b:a".to_string());
+        args.push(format!("{}k", audio_bitrate / 1000));
+    }
+    args.push("-sn".to_string());
+
+    // Keep the source metadata rather than the segments'
+    if !settings.remove_metadata {
+        args.push("-map_metadata".to_string());
+        args.push("1".to_string());
+    }
+    args.extend(metadata_args(settings));
+
+    args.push("-progress".to_string());
+    args.push("pipe:1".to_string());
+    args.push(output.to_string_lossy().to_string());
+    args
+}
+
+/// Metadata removal and custom metadata arguments
+fn metadata_args(settings: &EncodingSettings) -> Vec<String> {
+    let mut args = Vec::new();
 
     // Metadata handling
     if settings.remove_metadata {
@@ -219,14 +1072,40 @@ pub fn build_ffmpeg_command(
         }
     }
 
-    // Progress reporting
-    args.push("-progress".to_string());
-    args.push("pipe:1".to_string());
+    args
+}
 
-    // Output file
-    args.push(output.to_str().unwrap().to_string());
+pub(crate) fn null_device() -> &'static str {
+    if cfg!(windows) {
+        "NUL"
+    } else {
+        "/dev/null"
+    }
+}
 
-    args
+/// Create a unique passlog prefix in the temp directory
+fn passlog_prefix() -> PathBuf {
+    std::env::temp_dir().join(format!("rvc-passlog-{}", uuid::Uuid::new_v4()))
+}
+
+/// Remove every statistics file written for a passlog prefix
+fn cleanup_passlog(prefix: &Path) {
+    let (Some(dir), Some(name)) = (prefix.parent(), prefix.file_name()) else {
+        return;
+    };
+    let name = name.to_string_lossy();
+
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(name.as_ref())
+            {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
 }
 
 /// Parse FFmpeg progress output
@@ -278,30 +1157,288 @@ pub fn parse_progress(line: &str, total_duration: f64) -> Option<EncodingProgres
 }
 
 /// Encode video with progress callback
+///
+/// If `control` is cancelled while ffmpeg is running, the child process is killed,
+/// the partial output file is removed and an error is returned.
+#[allow(clippy::too_many_arguments)]
 pub async fn encode_video<F>(
-    app: tauri::AppHandle,
+    events: SharedEventSink,
+    runner: SharedProcessRunner,
     input: PathBuf,
     output: PathBuf,
     settings: EncodingSettings,
+    video_info: &VideoInfo,
+    control: ProcessControl,
+    progress_callback: F,
+) -> Result<()>
+where
+    F: Fn(EncodingProgress) + Send + Clone + 'static,
+{
+    if control.is_cancelled() {
+        anyhow::bail!("Encoding cancelled");
+    }
+
+    let settings =
+        apply_target_quality(&events, &runner, &input, settings, video_info, &control).await?;
+
+    if let Some(target_size) = settings.target_size_bytes.filter(|_| !settings.remux) {
+        return encode_to_target_size(
+            &events,
+            &runner,
+            &input,
+            &output,
+            &settings,
+            video_info,
+            target_size,
+            &control,
+            progress_callback,
+        )
+        .await;
+    }
+
+    encode_with_settings(
+        &events,
+        &runner,
+        &input,
+        &output,
+        &settings,
+        video_info,
+        &control,
+        progress_callback,
+    )
+    .await
+}
+
+/// Replace the fixed CRF with one measured on samples of the input, if a
+/// quality target is set
+pub(crate) async fn apply_target_quality(
+    events: &SharedEventSink,
+    runner: &SharedProcessRunner,
+    input: &Path,
+    mut settings: EncodingSettings,
+    video_info: &VideoInfo,
+    control: &ProcessControl,
+) -> Result<EncodingSettings> {
+    let search_target = settings
+        .target_quality
+        .clone()
+        .filter(|_| !settings.remux && settings.bitrate.is_none())
+        .filter(|_| settings.target_size_bytes.is_none());
+
+    if let Some(target) = search_target {
+        // Samples would be scored against the HDR source they were tone mapped from
+        if hdr_handling(&settings, video_info) == HdrHandling::ToneMap {
+            events.log(format!(
+                "Tone mapped output cannot be scored against its HDR source; {} is encoded without a quality target",
+                input.display()
+            ));
+            return Ok(settings);
+        }
+
+        let crf = find_crf(
+            events, runner, input, &settings, video_info, &target, control,
+        )
+        .await?;
+        events.log(format!("Using CRF {} for {}", crf, input.display()));
+        settings.crf = Some(crf);
+        settings.target_quality = None;
+        // Hardware encoders ignore CRF
+        settings.use_hardware = false;
+    }
+
+    Ok(settings)
+}
+
+/// Encode under a file size cap, re-encoding with a corrected bitrate on overshoot
+#[allow(clippy::too_many_arguments)]
+async fn encode_to_target_size<F>(
+    events: &SharedEventSink,
+    runner: &SharedProcessRunner,
+    input: &Path,
+    output: &Path,
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+    target_size: u64,
+    control: &ProcessControl,
+    progress_callback: F,
+) -> Result<()>
+where
+    F: Fn(EncodingProgress) + Send + Clone + 'static,
+{
+    let audio_bitrate = target_audio_bitrate(settings, video_info);
+    let mut video_bitrate =
+        calculate_target_bitrate(target_size, video_info.duration, audio_bitrate)?;
+    let attempts = settings.target_size_attempts.max(1);
+    // Progress keeps moving forward across retries: each attempt fills half of
+    // what is left of the bar and the last one runs it to 100%
+    let mut progress_start = 0.0;
+
+    for attempt in 1..=attempts {
+        let remaining = 100.0 - progress_start;
+        let span = if attempt == attempts {
+            remaining
+        } else {
+            remaining / 2.0
+        };
+        let callback = progress_callback.clone();
+        let attempt_progress = move |mut progress: EncodingProgress| {
+            progress.percentage = progress_start + progress.percentage * span / 100.0;
+            callback(progress);
+        };
+
+        let mut attempt_settings = settings.clone();
+        attempt_settings.target_size_bytes = None;
+        attempt_settings.bitrate = Some(video_bitrate);
+        attempt_settings.two_pass = true;
+        if audio_bitrate > 0 {
+            attempt_settings.audio_bitrate = Some(audio_bitrate);
+        }
+
+        encode_with_settings(
+            events,
+            runner,
+            input,
+            output,
+            &attempt_settings,
+            video_info,
+            control,
+            attempt_progress,
+        )
+        .await?;
+
+        let actual_size = std::fs::metadata(output)
+            .context("Failed to read output file size")?
+            .len();
+        if actual_size <= target_size {
+            return Ok(());
+        }
+
+        if attempt == attempts {
+            anyhow::bail!(
+                "Output is {} which exceeds the target size of {} after {} attempts",
+                format_file_size(actual_size),
+                format_file_size(target_size),
+                attempts
+            );
+        }
+
+        // Scale the bitrate by the overshoot, with a small extra margin
+        let corrected = video_bitrate as f64 * (target_size as f64 / actual_size as f64) * 0.97;
+        if (corrected as u64) < MIN_VIDEO_BITRATE {
+            anyhow::bail!(
+                "Cannot reach the target size of {}: required bitrate is too low",
+                format_file_size(target_size)
+            );
+        }
+
+        events.log(format!(
+            "Output is {} (target {}). Re-encoding at {}k...",
+            format_file_size(actual_size),
+            format_file_size(target_size),
+            corrected as u64 / 1000
+        ));
+        video_bitrate = corrected as u64;
+        progress_start += span;
+    }
+
+    Ok(())
+}
+
+/// Encode with the given settings, running two passes when requested
+#[allow(clippy::too_many_arguments)]
+async fn encode_with_settings<F>(
+    events: &SharedEventSink,
+    runner: &SharedProcessRunner,
+    input: &Path,
+    output: &Path,
+    settings: &EncodingSettings,
+    video_info: &VideoInfo,
+    control: &ProcessControl,
+    progress_callback: F,
+) -> Result<()>
+where
+    F: Fn(EncodingProgress) + Send + Clone + 'static,
+{
+    let total_duration = video_info.duration;
+    let hw_encoders = detect_hardware_encoders(runner);
+    let encoder = select_encoder(settings, &hw_encoders);
+
+    if !uses_two_pass(settings, &encoder) {
+        let args = build_ffmpeg_args(input, output, settings, video_info, &hw_encoders, None);
+        return run_ffmpeg(
+            events,
+            runner,
+            args,
+            output,
+            total_duration,
+            control,
+            progress_callback,
+        )
+        .await;
+    }
+
+    // Two-pass: each pass reports half of the overall progress
+    let passlog = passlog_prefix();
+    let mut result = Ok(());
+    for pass in [1u8, 2] {
+        let args = build_ffmpeg_pass_command(
+            input,
+            output,
+            settings,
+            video_info,
+            &hw_encoders,
+            pass,
+            &passlog,
+        );
+        let callback = progress_callback.clone();
+        let offset = if pass == 1 { 0.0 } else { 50.0 };
+
+        result = run_ffmpeg(
+            events,
+            runner,
+            args,
+            output,
+            total_duration,
+            control,
+            move |mut progress| {
+                progress.percentage = offset + progress.percentage / 2.0;
+                callback(progress);
+            },
+        )
+        .await;
+
+        if result.is_err() {
+            break;
+        }
+    }
+
+    cleanup_passlog(&passlog);
+    result
+}
+
+/// Run a single ffmpeg process to completion, reporting progress and log lines
+pub(crate) async fn run_ffmpeg<F>(
+    events: &SharedEventSink,
+    runner: &SharedProcessRunner,
+    args: Vec<String>,
+    output: &Path,
     total_duration: f64,
+    control: &ProcessControl,
     progress_callback: F,
 ) -> Result<()>
 where
     F: Fn(EncodingProgress) + Send + 'static,
 {
-    let hw_encoders = detect_hardware_encoders();
-    let args = build_ffmpeg_command(&input, &output, &settings, &hw_encoders);
+    let mut child = runner.spawn(Tool::Ffmpeg, &args)?;
 
-    // Use tokio::process::Command for async execution
-    let mut child = Command::new("ffmpeg")
-        .args(&args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .context("Failed to spawn ffmpeg process")?;
+    let pid = child.id();
+    if let Err(e) = control.attach(pid) {
+        let _ = child.kill().await;
+        return Err(e);
+    }
 
-    let stdout = child.stdout.take().context("Failed to capture stdout")?;
-    let stderr = child.stderr.take().context("Failed to capture stderr")?;
+    let stdout = child.take_stdout().context("Failed to capture stdout")?;
+    let stderr = child.take_stderr().context("Failed to capture stderr")?;
 
     // Handle stdout for progress updates
     let mut stdout_reader = BufReader::new(stdout);
@@ -322,9 +1459,8 @@ where
 
     // Handle stderr for error capturing AND real-time logging
     let mut stderr_reader = BufReader::new(stderr);
-    let app_clone = app.clone();
+    let events = events.clone();
     let stderr_handle = tokio::spawn(async move {
-        use tauri::Emitter;
         let mut lines = Vec::new();
         let mut line = String::new();
         while let Ok(n) = stderr_reader.read_line(&mut line).await {
@@ -333,8 +1469,8 @@ where
             }
             let log_line = line.trim().to_string();
 
-            // Emit log line to frontend
-            let _ = app_clone.emit("ffmpeg-log", log_line.clone());
+            // Forward log line (to the frontend or terminal)
+            events.log(log_line.clone());
 
             // Keep for error reporting
             if lines.len() >= 20 {
@@ -346,11 +1482,27 @@ where
         lines.join("\n")
     });
 
-    // Wait for process to complete
-    let status = child
-        .wait()
-        .await
-        .context("Failed to wait for ffmpeg process")?;
+    // Wait for process to complete, or kill it if the job gets cancelled
+    let status = tokio::select! {
+        status = child.wait() => Some(status.context("Failed to wait for ffmpeg process")?),
+        _ = control.cancelled() => None,
+    };
+    control.detach(pid);
+
+    let status = match status {
+        Some(status) => status,
+        None => {
+            let _ = child.kill().await;
+            let _ = stdout_handle.await;
+            let _ = stderr_handle.await;
+
+            // Remove the partially written output
+            if output.exists() {
+                let _ = tokio::fs::remove_file(output).await;
+            }
+            anyhow::bail!("Encoding cancelled");
+        }
+    };
 
     // Wait for IO tasks to finish
     let _ = stdout_handle.await;
@@ -366,3 +1518,339 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::DeinterlaceMethod;
+    use crate::probe::{ColorInfo, StreamInfo};
+    use crate::subtitles::ExternalSubtitle;
+
+    fn bitrate_settings() -> EncodingSettings {
+        EncodingSettings {
+            bitrate: Some(2_000_000),
+            crf: None,
+            use_hardware: false,
+            two_pass: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_two_pass_commands() {
+        let input = PathBuf::from("/tmp/in.mkv");
+        let output = PathBuf::from("/tmp/out.mp4");
+        let passlog = PathBuf::from("/tmp/rvc-passlog-test");
+        let settings = bitrate_settings();
+        let info = VideoInfo::test_default();
+
+        let first = build_ffmpeg_pass_command(&input, &output, &settings, &info, &[], 1, &passlog);
+        assert!(first.windows(2).any(|w| w == ["-pass", "1"]));
+        assert!(first.contains(&"-an".to_string()));
+        assert!(first.windows(2).any(|w| w == ["-f", "null"]));
+        assert!(!first.contains(&"/tmp/out.mp4".to_string()));
+
+        let second = build_ffmpeg_pass_command(&input, &output, &settings, &info, &[], 2, &passlog);
+        assert!(second.windows(2).any(|w| w == ["-pass", "2"]));
+        assert!(second
+            .windows(2)
+            .any(|w| w == ["-passlogfile", "/tmp/rvc-passlog-test"]));
+        assert_eq!(second.last().unwrap(), "/tmp/out.mp4");
+    }
+
+    #[test]
+    fn test_friendly_codec_names() {
+        let settings = EncodingSettings {
+            output_format: "webm".to_string(),
+            video_codec: "vp9".to_string(),
+            audio_codec: "opus".to_string(),
+            use_hardware: false,
+            ..Default::default()
+        };
+        let args = build_ffmpeg_command(
+            &PathBuf::from("/tmp/in.mkv"),
+            &PathBuf::from("/tmp/out.webm"),
+            &settings,
+            &VideoInfo::test_default(),
+            &[],
+        );
+        assert!(args.windows(2).any(|w| w == ["-c:v", "libvpx-vp9"]));
+        assert!(args.windows(2).any(|w| w == ["-c:a", "libopus"]));
+        assert_eq!(resolve_encoder("copy"), "copy");
+        assert_eq!(resolve_encoder("h264_nvenc"), "h264_nvenc");
+    }
+
+    #[test]
+    fn test_deinterlace_before_tone_mapping() {
+        let hdr = VideoInfo {
+            color: ColorInfo {
+                transfer: Some("smpte2084".to_string()),
+                ..Default::default()
+            },
+            ..VideoInfo::test_default()
+        };
+        let settings = EncodingSettings {
+            video_codec: "libx264".to_string(),
+            use_hardware: false,
+            filters: vec![
+                VideoFilter::Deinterlace {
+                    method: DeinterlaceMethod::Bwdif,
+                    double_rate: false,
+                },
+                VideoFilter::HorizontalFlip,
+            ],
+            ..Default::default()
+        };
+        let input = PathBuf::from("/tmp/in.mkv");
+        let output = PathBuf::from("/tmp/out.mp4");
+
+        let args = build_ffmpeg_command(&input, &output, &settings, &hdr, &[]);
+        let vf = &args[args.iter().position(|a| a == "-vf").unwrap() + 1];
+        assert!(vf.starts_with("bwdif=mode=send_frame,zscale="));
+        assert!(vf.contains("tonemap=") && vf.contains(",hflip,"));
+    }
+
+    #[test]
+    fn test_scale_filters() {
+        let portrait = VideoInfo {
+            width: 1920,
+            height: 1080,
+            rotation: 90,
+            ..VideoInfo::test_default()
+        };
+        let settings = EncodingSettings {
+            resolution: Some((1280, 720)),
+            use_hardware: false,
+            ..Default::default()
+        };
+        let input = PathBuf::from("/tmp/in.mkv");
+        let output = PathBuf::from("/tmp/out.mp4");
+
+        let args = build_ffmpeg_command(&input, &output, &settings, &portrait, &[]);
+        assert!(args
+            .windows(2)
+            .any(|w| w == ["-vf", "scale=720:1280,setsar=1"]));
+        assert_eq!(
+            validate_resolution((1280, 720), settings.scale_mode, &portrait).unwrap(),
+            (720, 1280)
+        );
+
+        let letterbox = EncodingSettings {
+            scale_mode: ScaleMode::Pad,
+            ..settings
+        };
+        let args = build_ffmpeg_command(&input, &output, &letterbox, &portrait, &[]);
+        assert!(args.windows(2).any(|w| w
+            == [
+                "-vf",
+                "scale=406:720,setsar=1,pad=1280:720:(ow-iw)/2:(oh-ih)/2"
+            ]));
+
+        // Scaling works from the cropped picture
+        let square = EncodingSettings {
+            scale_mode: ScaleMode::Fit,
+            filters: vec![VideoFilter::Crop {
+                width: 1080,
+                height: 1080,
+                x: None,
+                y: None,
+            }],
+            ..letterbox
+        };
+        let args = build_ffmpeg_command(&input, &output, &square, &portrait, &[]);
+        assert!(args
+            .windows(2)
+            .any(|w| w == ["-vf", "crop=1080:1080,scale=720:720,setsar=1"]));
+
+        let small = VideoInfo {
+            width: 640,
+            height: 360,
+            ..VideoInfo::test_default()
+        };
+        assert!(validate_resolution((1280, 720), ScaleMode::Fit, &small).is_err());
+    }
+
+    #[test]
+    fn test_apply_source_limits() {
+        // 854x480 source with 8 Mbit/s in total and 192k audio
+        let info = VideoInfo {
+            width: 854,
+            height: 480,
+            ..VideoInfo::test_default()
+        };
+        let settings = EncodingSettings {
+            resolution: Some((1920, 1080)),
+            bitrate: Some(10_000_000),
+            ..Default::default()
+        };
+
+        let (effective, adjustments) = apply_source_limits(&settings, &info).unwrap();
+        assert_eq!(effective.resolution, Some((854, 480)));
+        assert_eq!(effective.bitrate, Some(7_808_000));
+        assert_eq!(adjustments.len(), 2);
+
+        let keep = EncodingSettings {
+            source_limits: SourceLimitPolicy::KeepOriginal,
+            ..settings.clone()
+        };
+        let (effective, _) = apply_source_limits(&keep, &info).unwrap();
+        assert_eq!(effective.resolution, None);
+
+        let reject = EncodingSettings {
+            source_limits: SourceLimitPolicy::Reject,
+            ..settings
+        };
+        assert!(apply_source_limits(&reject, &info).is_err());
+
+        // Downscales and lower bitrates are left alone
+        let smaller = EncodingSettings {
+            resolution: Some((640, 360)),
+            bitrate: Some(1_000_000),
+            source_limits: SourceLimitPolicy::Reject,
+            ..Default::default()
+        };
+        let (effective, adjustments) = apply_source_limits(&smaller, &info).unwrap();
+        assert_eq!(effective.resolution, Some((640, 360)));
+        assert!(adjustments.is_empty());
+    }
+
+    #[test]
+    fn test_subtitle_adjustments() {
+        let info = VideoInfo {
+            streams: vec![
+                StreamInfo::test_stream(0, StreamType::Video, "h264"),
+                StreamInfo::test_stream(1, StreamType::Subtitle, "subrip"),
+                StreamInfo::test_stream(2, StreamType::Subtitle, "hdmv_pgs_subtitle"),
+            ],
+            ..VideoInfo::test_default()
+        };
+        let mut settings = EncodingSettings {
+            output_format: "mp4".to_string(),
+            ..Default::default()
+        };
+        settings.subtitles.mode = SubtitleMode::Keep;
+        settings.subtitles.external_files.push(ExternalSubtitle {
+            path: PathBuf::from("/videos/movie.sup"),
+            language: None,
+        });
+
+        assert_eq!(
+            subtitle_adjustments(&settings, &info),
+            vec![
+                "Subtitle stream 2 left out: not supported in mp4",
+                "Subtitle file /videos/movie.sup left out: not supported in mp4"
+            ]
+        );
+
+        // Matroska holds every embedded format, but not files of unknown types
+        settings.output_format = "mkv".to_string();
+        assert_eq!(
+            subtitle_adjustments(&settings, &info),
+            vec!["Subtitle file /videos/movie.sup left out: not supported in mkv"]
+        );
+    }
+
+    #[test]
+    fn test_calculate_target_bitrate() {
+        // 25 MB over 100 seconds with 128k audio
+        let bitrate = calculate_target_bitrate(25_000_000, 100.0, 128_000).unwrap();
+        let total = estimate_output_size(bitrate + 128_000, 100.0);
+        assert!(total <= 25_000_000);
+        assert!(total > 24_000_000);
+
+        assert!(calculate_target_bitrate(100_000, 600.0, 128_000).is_err());
+        assert!(calculate_target_bitrate(25_000_000, 0.0, 128_000).is_err());
+    }
+
+    #[test]
+    fn test_is_already_compliant() {
+        let info = VideoInfo::test_default();
+        let settings = EncodingSettings {
+            resolution: Some((1920, 1080)),
+            ..Default::default()
+        };
+        assert!(is_already_compliant(&info, &settings));
+
+        let hevc = EncodingSettings {
+            video_codec: "libx265".to_string(),
+            ..settings.clone()
+        };
+        assert!(!is_already_compliant(&info, &hevc));
+
+        let smaller = EncodingSettings {
+            resolution: Some((1280, 720)),
+            ..settings.clone()
+        };
+        assert!(!is_already_compliant(&info, &smaller));
+
+        let lower_bitrate = EncodingSettings {
+            bitrate: Some(4_000_000),
+            ..settings
+        };
+        assert!(!is_already_compliant(&info, &lower_bitrate));
+    }
+
+    #[test]
+    fn test_uses_two_pass() {
+        let settings = bitrate_settings();
+        assert!(uses_two_pass(&settings, "libx264"));
+        assert!(!uses_two_pass(&settings, "h264_nvenc"));
+
+        let crf_only = EncodingSettings {
+            bitrate: None,
+            ..settings
+        };
+        assert!(!uses_two_pass(&crf_only, "libx264"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_suspend_signals_every_process() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        let control = ProcessControl::new();
+        control.attach(Some(child.id())).unwrap();
+        // No process can have this pid, so signalling it always fails
+        control.attach(Some(i32::MAX as u32)).unwrap();
+
+        let err = control.suspend().unwrap_err();
+        assert!(err.to_string().contains("1 of 2"));
+        assert!(!control.is_suspended());
+        // The failed suspend must not leave the real process stopped
+        if cfg!(target_os = "linux") {
+            // Signals are delivered asynchronously, give a stray SIGSTOP time to land
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", child.id())).unwrap();
+            // The state follows the parenthesised command name
+            let state = stat.rsplit(')').next().unwrap().split_whitespace().next();
+            assert_ne!(state, Some("T"));
+        }
+
+        let err = control.resume().unwrap_err();
+        assert!(err.to_string().contains(&format!("pid {}", i32::MAX)));
+        assert!(!control.is_suspended());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_segment_command() {
+        let args = build_segment_command(
+            Path::new("in.mkv"),
+            Path::new("segment_00001.mkv"),
+            &bitrate_settings(),
+            &VideoInfo::test_default(),
+            &[],
+            120.5,
+            60.0,
+        );
+        let joined = args.join(" ");
+
+        assert!(joined.starts_with("-ss 120.5 -t 60 -i in.mkv"));
+        assert!(joined.ends_with("-an segment_00001.mkv"));
+        assert!(joined.contains("-sn"));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::encoder::EncodingProgress;
+use crate::queue::JobStatus;
+use serde::Serialize;
+use std::sync::Arc;
+#[cfg(feature = "gui")]
+use tauri::Emitter;
+use tokio::sync::mpsc;
+
+/// Something the encoder or queue reports while working
+#[derive(Debug, Clone, Serialize)]
+pub enum Event {
+    Progress {
+        job_id: String,
+        progress: EncodingProgress,
+    },
+    /// A line of ffmpeg output
+    Log(String),
+    StatusChange {
+        job_id: String,
+        status: JobStatus,
+    },
+    /// Every pending job of a `process_all` run has finished
+    QueueFinished,
+}
+
+/// Receiver for everything the encoder and queue publish
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: Event);
+
+    fn log(&self, line: String) {
+        self.emit(Event::Log(line));
+    }
+}
+
+pub type SharedEventSink = Arc<dyn EventSink>;
+
+impl<F> EventSink for F
+where
+    F: Fn(Event) + Send + Sync,
+{
+    fn emit(&self, event: Event) {
+        self(event)
+    }
+}
+
+/// Forwards events to the frontend under the names it listens for
+#[cfg(feature = "gui")]
+#[derive(Clone)]
+pub struct TauriEventSink {
+    app: tauri::AppHandle,
+}
+
+#[cfg(feature = "gui")]
+impl TauriEventSink {
+    pub fn new(app: tauri::AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl EventSink for TauriEventSink {
+    fn emit(&self, event: Event) {
+        let _ = match event {
+            Event::Progress { job_id, progress } => {
+                self.app.emit("encoding-progress", (job_id, progress))
+            }
+            Event::Log(line) => self.app.emit("ffmpeg-log", line),
+            Event::StatusChange { job_id, status } => {
+                self.app.emit("job-status-change", (job_id, status))
+            }
+            Event::QueueFinished => self.app.emit("queue-finished", ()),
+        };
+    }
+}
+
+/// Sends events over an unbounded channel, e.g. to a printer task in the CLI
+#[derive(Clone)]
+pub struct ChannelEventSink {
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+impl ChannelEventSink {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<Event>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { sender }, receiver)
+    }
+}
+
+impl EventSink for ChannelEventSink {
+    fn emit(&self, event: Event) {
+        // The receiver going away only means nobody is listening anymore
+        let _ = self.sender.send(event);
+    }
+}
+
+/// Keeps every event in memory so tests can assert on what was published
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct RecordingEventSink {
+    events: std::sync::Mutex<Vec<Event>>,
+}
+
+#[cfg(test)]
+impl RecordingEventSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+
+    pub fn log_lines(&self) -> Vec<String> {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Log(line) => Some(line),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Status changes published for one job, in order
+    pub fn statuses(&self, job_id: &str) -> Vec<JobStatus> {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::StatusChange { job_id: id, status } if id == job_id => Some(status),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn queue_finished(&self) -> bool {
+        self.events()
+            .iter()
+            .any(|event| matches!(event, Event::QueueFinished))
+    }
+}
+
+#[cfg(test)]
+impl EventSink for RecordingEventSink {
+    fn emit(&self, event: Event) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_sink() {
+        let sink = RecordingEventSink::new();
+        sink.log("frame=1".to_string());
+        sink.emit(Event::StatusChange {
+            job_id: "a".to_string(),
+            status: JobStatus::Pending,
+        });
+        sink.emit(Event::StatusChange {
+            job_id: "b".to_string(),
+            status: JobStatus::Cancelled,
+        });
+        sink.emit(Event::QueueFinished);
+
+        assert_eq!(sink.log_lines(), vec!["frame=1".to_string()]);
+        assert_eq!(sink.statuses("b"), vec![JobStatus::Cancelled]);
+        assert!(sink.queue_finished());
+    }
+
+    #[tokio::test]
+    async fn test_channel_sink() {
+        let (sink, mut receiver) = ChannelEventSink::new();
+        let shared: SharedEventSink = Arc::new(sink);
+        shared.log("done".to_string());
+        drop(shared);
+
+        assert!(matches!(receiver.recv().await, Some(Event::Log(line)) if line == "done"));
+        assert!(receiver.recv().await.is_none());
+    }
+}
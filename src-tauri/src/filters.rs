@@ -0,0 +1,379 @@
+use crate::probe::VideoInfo;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// One step of the user's video filter chain
+///
+/// Filters see the decoded frames after the source's rotation metadata has been
+/// applied, so crop coordinates are in those pixels.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum VideoFilter {
+    /// Cut a `width`x`height` area out of the frame, centred unless `x`/`y` are set
+    Crop {
+        width: u32,
+        height: u32,
+        #[serde(default)]
+        x: Option<u32>,
+        #[serde(default)]
+        y: Option<u32>,
+    },
+    /// Turn the picture clockwise by a multiple of 90 degrees
+    Rotate {
+        degrees: i32,
+    },
+    HorizontalFlip,
+    VerticalFlip,
+    /// Only allowed as the first filter, while the fields are still intact
+    Deinterlace {
+        method: DeinterlaceMethod,
+        /// Output one frame per field, doubling the frame rate
+        #[serde(default)]
+        double_rate: bool,
+    },
+    Denoise {
+        method: DenoiseMethod,
+        strength: f64,
+    },
+    /// Unsharp mask on the luma plane; a negative amount blurs
+    Sharpen {
+        amount: f64,
+        size: u32,
+    },
+    /// Brightness, contrast, saturation and gamma through the `eq` filter
+    Adjust {
+        #[serde(default)]
+        brightness: f64,
+        #[serde(default = "neutral")]
+        contrast: f64,
+        #[serde(default = "neutral")]
+        saturation: f64,
+        #[serde(default = "neutral")]
+        gamma: f64,
+    },
+    /// Convert to a constant frame rate by dropping or duplicating frames
+    FrameRate {
+        fps: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum DeinterlaceMethod {
+    Yadif,
+    /// Slower, with better motion handling than yadif
+    Bwdif,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum DenoiseMethod {
+    /// Fast spatial and temporal denoiser; strength 0-30
+    Hqdn3d,
+    /// Non-local means, much slower and better at keeping detail; strength 1-30
+    Nlmeans,
+}
+
+fn neutral() -> f64 {
+    1.0
+}
+
+fn check_range(name: &str, value: f64, min: f64, max: f64) -> Result<()> {
+    if !(min..=max).contains(&value) {
+        anyhow::bail!(
+            "{} must be between {} and {}, got {}",
+            name,
+            min,
+            max,
+            value
+        );
+    }
+    Ok(())
+}
+
+/// Check the parameters of every filter and their order
+pub fn validate_filters(filters: &[VideoFilter]) -> Result<()> {
+    for (position, filter) in filters.iter().enumerate() {
+        match filter {
+            VideoFilter::Crop { width, height, .. } => {
+                if *width == 0 || *height == 0 {
+                    anyhow::bail!("Crop size must not be zero, got {}x{}", width, height);
+                }
+            }
+            VideoFilter::Rotate { degrees } => {
+                if degrees % 90 != 0 {
+                    anyhow::bail!("Rotation must be a multiple of 90 degrees, got {}", degrees);
+                }
+            }
+            VideoFilter::HorizontalFlip | VideoFilter::VerticalFlip => {}
+            VideoFilter::Deinterlace { .. } => {
+                if position != 0 {
+                    anyhow::bail!("Deinterlacing must be the first filter");
+                }
+            }
+            VideoFilter::Denoise { method, strength } => match method {
+                DenoiseMethod::Hqdn3d => check_range("hqdn3d strength", *strength, 0.0, 30.0)?,
+                DenoiseMethod::Nlmeans => check_range("nlmeans strength", *strength, 1.0, 30.0)?,
+            },
+            VideoFilter::Sharpen { amount, size } => {
+                check_range("Sharpen amount", *amount, -2.0, 5.0)?;
+                if !(3..=23).contains(size) || size % 2 == 0 {
+                    anyhow::bail!(
+                        "Sharpen size must be an odd number from 3 to 23, got {}",
+                        size
+                    );
+                }
+            }
+            VideoFilter::Adjust {
+                brightness,
+                contrast,
+                saturation,
+                gamma,
+            } => {
+                check_range("Brightness", *brightness, -1.0, 1.0)?;
+                check_range("Contrast", *contrast, -1000.0, 1000.0)?;
+                check_range("Saturation", *saturation, 0.0, 3.0)?;
+                check_range("Gamma", *gamma, 0.1, 10.0)?;
+            }
+            VideoFilter::FrameRate { fps } => check_range("Frame rate", *fps, 1.0, 240.0)?,
+        }
+    }
+    Ok(())
+}
+
+/// The source as the steps after the chain see it, with the crops and quarter
+/// turns applied to its size
+///
+/// The result has no rotation left to apply and its pixel aspect ratio turned
+/// along with the picture. Fails if a crop does not fit the frame.
+pub fn filtered_info(filters: &[VideoFilter], info: &VideoInfo) -> Result<VideoInfo> {
+    if filters.is_empty() {
+        return Ok(info.clone());
+    }
+
+    let (mut width, mut height) = info.oriented_size();
+    let mut aspect = match info.sample_aspect_ratio {
+        Some((num, den)) if info.rotation.rem_euclid(180) == 90 => Some((den, num)),
+        aspect => aspect,
+    };
+    let mut fps = info.fps;
+
+    for filter in filters {
+        match filter {
+            VideoFilter::Crop {
+                width: crop_width,
+                height: crop_height,
+                x,
+                y,
+            } => {
+                // Unknown source sizes are left for ffmpeg to check
+                let x = x.unwrap_or(width.saturating_sub(*crop_width) / 2);
+                let y = y.unwrap_or(height.saturating_sub(*crop_height) / 2);
+                let fits = |offset: u32, size: u32, frame: u32| {
+                    offset as u64 + size as u64 <= frame as u64
+                };
+                if width > 0 && !(fits(x, *crop_width, width) && fits(y, *crop_height, height)) {
+                    anyhow::bail!(
+                        "Crop {}x{} at {},{} does not fit the {}x{} frame",
+                        crop_width,
+                        crop_height,
+                        x,
+                        y,
+                        width,
+                        height
+                    );
+                }
+                width = *crop_width;
+                height = *crop_height;
+            }
+            VideoFilter::Rotate { degrees } if degrees.rem_euclid(180) == 90 => {
+                (width, height) = (height, width);
+                aspect = aspect.map(|(num, den)| (den, num));
+            }
+            VideoFilter::Deinterlace {
+                double_rate: true, ..
+            } => fps *= 2.0,
+            VideoFilter::FrameRate { fps: rate } => fps = *rate as f32,
+            _ => {}
+        }
+    }
+
+    Ok(VideoInfo {
+        width,
+        height,
+        sample_aspect_ratio: aspect,
+        rotation: 0,
+        fps,
+        ..info.clone()
+    })
+}
+
+/// The ffmpeg filters for a chain, in order
+pub fn filter_chain(filters: &[VideoFilter]) -> Vec<String> {
+    let mut chain = Vec::new();
+    for filter in filters {
+        match filter {
+            VideoFilter::Crop {
+                width,
+                height,
+                x,
+                y,
+            } => chain.push(match (x, y) {
+                (Some(x), Some(y)) => format!("crop={}:{}:{}:{}", width, height, x, y),
+                (Some(x), None) => format!("crop={}:{}:x={}", width, height, x),
+                (None, Some(y)) => format!("crop={}:{}:y={}", width, height, y),
+                (None, None) => format!("crop={}:{}", width, height),
+            }),
+            VideoFilter::Rotate { degrees } => match degrees.rem_euclid(360) {
+                90 => chain.push("transpose=clock".to_string()),
+                180 => chain.extend(["hflip".to_string(), "vflip".to_string()]),
+                270 => chain.push("transpose=cclock".to_string()),
+                _ => {}
+            },
+            VideoFilter::HorizontalFlip => chain.push("hflip".to_string()),
+            VideoFilter::VerticalFlip => chain.push("vflip".to_string()),
+            VideoFilter::Deinterlace {
+                method,
+                double_rate,
+            } => {
+                let name = match method {
+                    DeinterlaceMethod::Yadif => "yadif",
+                    DeinterlaceMethod::Bwdif => "bwdif",
+                };
+                let mode = if *double_rate {
+                    "send_field"
+                } else {
+                    "send_frame"
+                };
+                chain.push(format!("{}=mode={}", name, mode));
+            }
+            VideoFilter::Denoise { method, strength } => chain.push(match method {
+                DenoiseMethod::Hqdn3d => format!("hqdn3d={}", strength),
+                DenoiseMethod::Nlmeans => format!("nlmeans=s={}", strength),
+            }),
+            VideoFilter::Sharpen { amount, size } => {
+                chain.push(format!("unsharp=lx={}:ly={}:la={}", size, size, amount))
+            }
+            VideoFilter::Adjust {
+                brightness,
+                contrast,
+                saturation,
+                gamma,
+            } => chain.push(format!(
+                "eq=brightness={}:contrast={}:saturation={}:gamma={}",
+                brightness, contrast, saturation, gamma
+            )),
+            VideoFilter::FrameRate { fps } => chain.push(format!("fps={}", fps)),
+        }
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn phone_video() -> VideoInfo {
+        VideoInfo {
+            path: PathBuf::from("phone.mp4"),
+            rotation: 90,
+            ..VideoInfo::test_default()
+        }
+    }
+
+    #[test]
+    fn test_filter_chain() {
+        let filters = vec![
+            VideoFilter::Deinterlace {
+                method: DeinterlaceMethod::Bwdif,
+                double_rate: true,
+            },
+            VideoFilter::Crop {
+                width: 1080,
+                height: 1080,
+                x: None,
+                y: Some(0),
+            },
+            VideoFilter::Rotate { degrees: -90 },
+            VideoFilter::HorizontalFlip,
+            VideoFilter::Denoise {
+                method: DenoiseMethod::Hqdn3d,
+                strength: 4.0,
+            },
+            VideoFilter::Sharpen {
+                amount: 0.5,
+                size: 5,
+            },
+            VideoFilter::Adjust {
+                brightness: 0.05,
+                contrast: 1.1,
+                saturation: 1.0,
+                gamma: 1.0,
+            },
+            VideoFilter::FrameRate { fps: 25.0 },
+        ];
+        assert!(validate_filters(&filters).is_ok());
+        assert_eq!(
+            filter_chain(&filters).join(","),
+            "bwdif=mode=send_field,crop=1080:1080:y=0,transpose=cclock,hflip,hqdn3d=4,\
+             unsharp=lx=5:ly=5:la=0.5,eq=brightness=0.05:contrast=1.1:saturation=1:gamma=1,fps=25"
+        );
+    }
+
+    #[test]
+    fn test_validate_filters() {
+        let late_deinterlace = [
+            VideoFilter::HorizontalFlip,
+            VideoFilter::Deinterlace {
+                method: DeinterlaceMethod::Yadif,
+                double_rate: false,
+            },
+        ];
+        assert!(validate_filters(&late_deinterlace).is_err());
+        assert!(validate_filters(&[VideoFilter::Rotate { degrees: 45 }]).is_err());
+        assert!(validate_filters(&[VideoFilter::Sharpen {
+            amount: 1.0,
+            size: 4
+        }])
+        .is_err());
+        assert!(validate_filters(&[VideoFilter::Denoise {
+            method: DenoiseMethod::Nlmeans,
+            strength: 0.5
+        }])
+        .is_err());
+        assert!(validate_filters(&[VideoFilter::FrameRate { fps: 0.0 }]).is_err());
+    }
+
+    #[test]
+    fn test_filtered_info() {
+        // The 1920x1080 stream is shown as 1080x1920 portrait
+        let info = phone_video();
+        let crop = VideoFilter::Crop {
+            width: 1080,
+            height: 1080,
+            x: None,
+            y: None,
+        };
+        let square = filtered_info(&[crop], &info).unwrap();
+        assert_eq!(square.display_size(), (1080, 1080));
+
+        let turned = filtered_info(&[VideoFilter::Rotate { degrees: 90 }], &info).unwrap();
+        assert_eq!(turned.display_size(), (1920, 1080));
+
+        let too_wide = VideoFilter::Crop {
+            width: 1920,
+            height: 1080,
+            x: None,
+            y: None,
+        };
+        assert!(filtered_info(&[too_wide], &info).is_err());
+
+        let anamorphic = VideoInfo {
+            width: 720,
+            height: 576,
+            rotation: 0,
+            sample_aspect_ratio: Some((16, 11)),
+            ..info
+        };
+        let turned = filtered_info(&[VideoFilter::Rotate { degrees: 270 }], &anamorphic).unwrap();
+        assert_eq!(turned.display_size(), (396, 720));
+    }
+}
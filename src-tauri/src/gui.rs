@@ -0,0 +1,642 @@
+// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+
+use crate::capabilities::{
+    detect_capabilities, detect_filters, tool_version, Capabilities, ToolVersion,
+};
+use crate::container::{plan_remux, validate_codecs, CompatibilityError, RemuxStream};
+use crate::database_path;
+use crate::encoder::{
+    detect_hardware_encoders, is_already_compliant, CompliancePolicy, EncodingSettings,
+};
+use crate::events::{Event, EventSink, SharedEventSink, TauriEventSink};
+use crate::filters::validate_filters;
+use crate::probe::{check_ffmpeg, check_ffprobe, probe_video, VideoInfo};
+use crate::quality::detect_vmaf;
+use crate::queue::{calculate_max_concurrent, Job, JobQueue, JobStatus, QueueStats};
+use crate::runner::{SharedProcessRunner, SystemProcessRunner, Tool, ToolPaths};
+use crate::session::{Session, SessionManager};
+use crate::subtitles::{find_sidecar_subtitles, validate_subtitles};
+use crate::utils::{generate_unique_filename, get_resolution_presets, scan_directory};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{Manager, State};
+use tokio::sync::Mutex;
+
+// Application state
+pub struct AppState {
+    queue: Arc<Mutex<JobQueue>>,
+    session_manager: Arc<Mutex<SessionManager>>,
+    current_session_id: Arc<Mutex<Option<i64>>>,
+    recovered_session: Arc<Mutex<Option<RecoveredSession>>>,
+}
+
+/// Unfinished session restored on startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveredSession {
+    pub session_id: i64,
+    pub name: String,
+    /// Jobs that were running when the app went away
+    pub interrupted_jobs: Vec<String>,
+    /// Interrupted jobs that start over because they were not chunked
+    pub restarted_jobs: usize,
+    pub pending_jobs: usize,
+}
+
+/// Restore the latest session if it has unfinished jobs
+async fn restore_latest_session(state: &AppState) -> anyhow::Result<Option<RecoveredSession>> {
+    let session_manager = state.session_manager.lock().await;
+    let Some(session) = session_manager.get_latest_session()? else {
+        return Ok(None);
+    };
+
+    let mut jobs = session_manager.load_jobs(session.id)?;
+    let interrupted_jobs: Vec<String> = jobs
+        .iter_mut()
+        .filter_map(|job| job.reconcile_interrupted().then(|| job.id.clone()))
+        .collect();
+    let restarted_jobs = jobs
+        .iter()
+        .filter(|job| interrupted_jobs.contains(&job.id) && !job.can_resume())
+        .count();
+    let pending_jobs = jobs
+        .iter()
+        .filter(|job| matches!(job.status, JobStatus::Pending))
+        .count();
+
+    if pending_jobs == 0 {
+        return Ok(None);
+    }
+    if !interrupted_jobs.is_empty() {
+        session_manager.save_jobs(session.id, &jobs)?;
+    }
+
+    let queue = state.queue.lock().await;
+    for job in jobs {
+        queue.add_job(job).await;
+    }
+    *state.current_session_id.lock().await = Some(session.id);
+
+    Ok(Some(RecoveredSession {
+        session_id: session.id,
+        name: session.name,
+        interrupted_jobs,
+        restarted_jobs,
+        pending_jobs,
+    }))
+}
+
+/// Save the queue to the current session, creating one when the queue first gets jobs
+///
+/// The jobs are read while holding the session lock, so saves run one at a time
+/// and the last one always writes the newest state.
+async fn autosave(state: &AppState) {
+    let session_manager = state.session_manager.lock().await;
+    let jobs = state.queue.lock().await.get_jobs().await;
+    let mut current_session_id = state.current_session_id.lock().await;
+
+    let session_id = match *current_session_id {
+        Some(id) => id,
+        None if jobs.is_empty() => return,
+        None => {
+            let name = format!("Session {}", chrono::Local::now().format("%Y-%m-%d %H:%M"));
+            match session_manager.create_session(name) {
+                Ok(session) => *current_session_id.insert(session.id),
+                Err(e) => {
+                    eprintln!("Autosave failed: {}", e);
+                    return;
+                }
+            }
+        }
+    };
+
+    if let Err(e) = session_manager.save_jobs(session_id, &jobs) {
+        eprintln!("Autosave failed: {}", e);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemInfo {
+    pub ffmpeg_available: bool,
+    pub ffprobe_available: bool,
+    pub hardware_encoders: Vec<String>,
+    pub vmaf_available: bool,
+    pub ffmpeg_version: Option<ToolVersion>,
+    pub ffprobe_version: Option<ToolVersion>,
+    pub capabilities: Capabilities,
+    pub max_concurrent_jobs: usize,
+    pub cpu_cores: usize,
+}
+
+// Tauri Commands
+
+#[tauri::command]
+async fn get_system_info(state: State<'_, AppState>) -> Result<SystemInfo, String> {
+    let runner = state.queue.lock().await.runner();
+    Ok(SystemInfo {
+        ffmpeg_available: check_ffmpeg(&runner),
+        ffprobe_available: check_ffprobe(&runner),
+        hardware_encoders: detect_hardware_encoders(&runner),
+        vmaf_available: detect_vmaf(&runner),
+        ffmpeg_version: tool_version(&runner, Tool::Ffmpeg),
+        ffprobe_version: tool_version(&runner, Tool::Ffprobe),
+        capabilities: detect_capabilities(&runner),
+        max_concurrent_jobs: calculate_max_concurrent(),
+        cpu_cores: num_cpus::get(),
+    })
+}
+
+/// Use the given ffmpeg/ffprobe binaries for jobs started from now on
+#[tauri::command]
+async fn set_tool_paths(paths: ToolPaths, state: State<'_, AppState>) -> Result<(), String> {
+    let runner: SharedProcessRunner = Arc::new(SystemProcessRunner::new(paths));
+    state.queue.lock().await.set_runner(runner);
+    Ok(())
+}
+
+#[tauri::command]
+async fn probe_video_file(path: String, state: State<'_, AppState>) -> Result<VideoInfo, String> {
+    let runner = state.queue.lock().await.runner();
+    let path_buf = PathBuf::from(path);
+    probe_video(&runner, path_buf)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_remux_plan(
+    path: String,
+    settings: EncodingSettings,
+    state: State<'_, AppState>,
+) -> Result<Vec<RemuxStream>, String> {
+    let runner = state.queue.lock().await.runner();
+    let video_info = probe_video(&runner, PathBuf::from(path))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(plan_remux(
+        &video_info,
+        &settings,
+        &detect_hardware_encoders(&runner),
+    ))
+}
+
+/// Why no jobs were added, tagged so the UI can offer the suggested settings
+#[derive(Debug, Serialize, thiserror::Error)]
+#[serde(tag = "kind", content = "details", rename_all = "snake_case")]
+enum AddFilesError {
+    #[error(transparent)]
+    Incompatible(#[from] CompatibilityError),
+    #[error("{0}")]
+    InvalidFilters(String),
+    #[error("{0}")]
+    Scan(String),
+}
+
+/// An input `add_files` left out of the queue
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedInput {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Jobs queued by `add_files`, along with the inputs it could not queue
+#[derive(Debug, Clone, Serialize)]
+pub struct AddedFiles {
+    pub jobs: Vec<Job>,
+    pub rejected: Vec<RejectedInput>,
+}
+
+#[tauri::command]
+async fn add_files(
+    paths: Vec<String>,
+    output_dir: String,
+    settings: EncodingSettings,
+    state: State<'_, AppState>,
+) -> Result<AddedFiles, AddFilesError> {
+    // Reject codecs the container cannot hold and bad filter parameters before probing anything
+    validate_codecs(&settings)?;
+    validate_filters(&settings.filters)
+        .map_err(|e| AddFilesError::InvalidFilters(e.to_string()))?;
+
+    let mut jobs = Vec::new();
+    let mut rejected = Vec::new();
+    let queue = state.queue.lock().await;
+    // Probe with the binaries these settings will encode with
+    let runner = queue.runner_for(&settings);
+    // Tone mapping needs filters that not every ffmpeg build has
+    let filters = detect_filters(&runner);
+
+    for path_str in paths {
+        let input_path = PathBuf::from(&path_str);
+
+        // Probe video
+        let video_info = match probe_video(&runner, input_path.clone()).await {
+            Ok(info) => info,
+            Err(e) => {
+                rejected.push(RejectedInput {
+                    path: path_str,
+                    reason: format!("Failed to probe: {}", e),
+                });
+                continue;
+            }
+        };
+
+        // Attach subtitle files found next to the input
+        let mut job_settings = settings.clone();
+        if job_settings.subtitles.use_sidecar_files {
+            job_settings
+                .subtitles
+                .external_files
+                .extend(find_sidecar_subtitles(&input_path));
+        }
+
+        if let Err(e) = validate_subtitles(&job_settings.subtitles, &video_info) {
+            rejected.push(RejectedInput {
+                path: path_str,
+                reason: e.to_string(),
+            });
+            continue;
+        }
+
+        // Detect inputs that already match the requested settings
+        let policy = job_settings.compliance_policy;
+        let already_compliant =
+            policy != CompliancePolicy::Encode && is_already_compliant(&video_info, &job_settings);
+        if already_compliant && policy == CompliancePolicy::Remux {
+            job_settings.remux = true;
+        }
+
+        // Generate output path (copied files keep their original container)
+        let filename = input_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("output");
+        let extension = match input_path.extension().and_then(|s| s.to_str()) {
+            Some(ext) if already_compliant && policy == CompliancePolicy::Copy => ext,
+            _ => settings.output_format.as_str(),
+        };
+        let output_filename = format!("{}.{}", filename, extension);
+        let output_path = PathBuf::from(&output_dir).join(output_filename);
+        let output_path = generate_unique_filename(&output_path);
+
+        // Create job
+        let mut job = Job::new(input_path, output_path, video_info, job_settings);
+        job.check_tone_mapping(&filters);
+        job.already_compliant = already_compliant;
+        // A job rejected by the source limits stays failed
+        if already_compliant
+            && policy == CompliancePolicy::Skip
+            && matches!(job.status, JobStatus::Pending)
+        {
+            job.status = JobStatus::Skipped {
+                reason: "Input already matches the target settings".to_string(),
+            };
+        }
+        queue.add_job(job.clone()).await;
+        jobs.push(job);
+    }
+    drop(queue);
+
+    autosave(&state).await;
+    Ok(AddedFiles { jobs, rejected })
+}
+
+#[tauri::command]
+async fn add_directory(
+    dir_path: String,
+    output_dir: String,
+    settings: EncodingSettings,
+    recursive: bool,
+    state: State<'_, AppState>,
+) -> Result<AddedFiles, AddFilesError> {
+    let dir = PathBuf::from(&dir_path);
+
+    // Scan directory for video files
+    let video_files =
+        scan_directory(&dir, recursive).map_err(|e| AddFilesError::Scan(e.to_string()))?;
+
+    let paths: Vec<String> = video_files
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    add_files(paths, output_dir, settings, state).await
+}
+
+#[tauri::command]
+async fn get_jobs(state: State<'_, AppState>) -> Result<Vec<Job>, String> {
+    let queue = state.queue.lock().await;
+    Ok(queue.get_jobs().await)
+}
+
+#[tauri::command]
+async fn get_job(id: String, state: State<'_, AppState>) -> Result<Option<Job>, String> {
+    let queue = state.queue.lock().await;
+    Ok(queue.get_job(&id).await)
+}
+
+#[tauri::command]
+async fn remove_job(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.queue.lock().await.remove_job(&id).await;
+    autosave(&state).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn clear_jobs(state: State<'_, AppState>) -> Result<(), String> {
+    state.queue.lock().await.clear_jobs().await;
+    autosave(&state).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_max_concurrent_jobs(state: State<'_, AppState>, count: usize) -> Result<(), String> {
+    let queue = state.queue.lock().await;
+    queue.set_max_concurrent(count).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn start_processing(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    should_shutdown: bool,
+) -> Result<(), String> {
+    let queue = state.queue.lock().await;
+    let queue_clone = queue.clone();
+    drop(queue);
+
+    tokio::spawn(async move {
+        use tauri_plugin_notification::NotificationExt;
+
+        let sink = TauriEventSink::new(app.clone());
+        let app_status = app.clone();
+        let events: SharedEventSink = Arc::new(move |event: Event| {
+            let status_changed = matches!(event, Event::StatusChange { .. });
+            sink.emit(event);
+
+            if status_changed {
+                let app = app_status.clone();
+                tauri::async_runtime::spawn(async move {
+                    autosave(&app.state::<AppState>()).await;
+                });
+            }
+        });
+
+        queue_clone.process_all(events).await;
+
+        // Runs after the saves queued by status changes, so the session holds the
+        // final state before the user is told or the machine shuts down
+        autosave(&app.state::<AppState>()).await;
+
+        // Notify user
+        let _ = app
+            .notification()
+            .builder()
+            .title("Processing Complete")
+            .body("All videos in the queue have been converted.")
+            .show();
+
+        if should_shutdown {
+            println!("Shutdown requested. Executing shutdown command...");
+            let _ = std::process::Command::new("shutdown")
+                .args(["-h", "now"])
+                .spawn();
+        }
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn pause_queue(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let changes = state.queue.lock().await.pause().await;
+    let sink = TauriEventSink::new(app);
+    for (job_id, status) in changes {
+        sink.emit(Event::StatusChange { job_id, status });
+    }
+    autosave(&state).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn resume_queue(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let changes = state.queue.lock().await.resume().await;
+    let sink = TauriEventSink::new(app);
+    for (job_id, status) in changes {
+        sink.emit(Event::StatusChange { job_id, status });
+    }
+    autosave(&state).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn pause_job(
+    id: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let status = state
+        .queue
+        .lock()
+        .await
+        .pause_job(&id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(status) = status {
+        TauriEventSink::new(app).emit(Event::StatusChange { job_id: id, status });
+        autosave(&state).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn resume_job(
+    id: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let status = state
+        .queue
+        .lock()
+        .await
+        .resume_job(&id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(status) = status {
+        TauriEventSink::new(app).emit(Event::StatusChange { job_id: id, status });
+        autosave(&state).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn cancel_job(id: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.queue.lock().await.cancel_job(&id).await;
+    autosave(&state).await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_recovered_session(
+    state: State<'_, AppState>,
+) -> Result<Option<RecoveredSession>, String> {
+    Ok(state.recovered_session.lock().await.clone())
+}
+
+#[tauri::command]
+async fn dismiss_recovered_session(state: State<'_, AppState>) -> Result<(), String> {
+    state.recovered_session.lock().await.take();
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_queue_stats(state: State<'_, AppState>) -> Result<QueueStats, String> {
+    let queue = state.queue.lock().await;
+    Ok(queue.get_stats().await)
+}
+
+#[tauri::command]
+async fn create_session(name: String, state: State<'_, AppState>) -> Result<i64, String> {
+    let session_manager = state.session_manager.lock().await;
+    let session = session_manager
+        .create_session(name)
+        .map_err(|e| e.to_string())?;
+
+    let mut current_session = state.current_session_id.lock().await;
+    *current_session = Some(session.id);
+
+    Ok(session.id)
+}
+
+#[tauri::command]
+async fn save_session(state: State<'_, AppState>) -> Result<(), String> {
+    let current_session_id = {
+        let session_id = state.current_session_id.lock().await;
+        *session_id
+    };
+
+    if let Some(session_id) = current_session_id {
+        let session_manager = state.session_manager.lock().await;
+        let jobs = state.queue.lock().await.get_jobs().await;
+        session_manager
+            .save_jobs(session_id, &jobs)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn load_session(session_id: i64, state: State<'_, AppState>) -> Result<Vec<Job>, String> {
+    let session_manager = state.session_manager.lock().await;
+    let mut jobs = session_manager
+        .load_jobs(session_id)
+        .map_err(|e| e.to_string())?;
+
+    let queue = state.queue.lock().await;
+
+    // Jobs interrupted by a crash or restart go back to pending and resume when started
+    for job in &mut jobs {
+        if !queue.is_running(&job.id).await {
+            job.reconcile_interrupted();
+        }
+    }
+
+    queue.clear_jobs().await;
+
+    for job in &jobs {
+        queue.add_job(job.clone()).await;
+    }
+
+    let mut current_session = state.current_session_id.lock().await;
+    *current_session = Some(session_id);
+
+    Ok(jobs)
+}
+
+#[tauri::command]
+async fn get_sessions(state: State<'_, AppState>) -> Result<Vec<Session>, String> {
+    let session_manager = state.session_manager.lock().await;
+    session_manager.get_sessions().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_session(session_id: i64, state: State<'_, AppState>) -> Result<(), String> {
+    let session_manager = state.session_manager.lock().await;
+    session_manager
+        .delete_session(session_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_resolution_presets_cmd() -> Vec<(String, (u32, u32))> {
+    get_resolution_presets()
+        .into_iter()
+        .map(|(name, res)| (name.to_string(), res))
+        .collect()
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    // Initialize application state
+    let max_concurrent = calculate_max_concurrent();
+    let queue = Arc::new(Mutex::new(JobQueue::new(max_concurrent)));
+
+    let session_manager = Arc::new(Mutex::new(
+        SessionManager::new(database_path()).expect("Failed to initialize session manager"),
+    ));
+
+    let app_state = AppState {
+        queue,
+        session_manager,
+        current_session_id: Arc::new(Mutex::new(None)),
+        recovered_session: Arc::new(Mutex::new(None)),
+    };
+
+    // Bring back unfinished work from the last run
+    match tauri::async_runtime::block_on(restore_latest_session(&app_state)) {
+        Ok(recovered) => *app_state.recovered_session.blocking_lock() = recovered,
+        Err(e) => eprintln!("Failed to restore the last session: {}", e),
+    }
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_store::Builder::new().build())
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_process::init())
+        .manage(app_state)
+        .invoke_handler(tauri::generate_handler![
+            get_system_info,
+            set_tool_paths,
+            probe_video_file,
+            get_remux_plan,
+            add_files,
+            add_directory,
+            get_jobs,
+            get_job,
+            remove_job,
+            clear_jobs,
+            start_processing,
+            set_max_concurrent_jobs,
+            pause_queue,
+            resume_queue,
+            pause_job,
+            resume_job,
+            cancel_job,
+            get_queue_stats,
+            get_recovered_session,
+            dismiss_recovered_session,
+            create_session,
+            save_session,
+            load_session,
+            get_sessions,
+            delete_session,
+            get_resolution_presets_cmd,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,305 @@
+use crate::encoder::{encoder_codec_name, resolve_encoder, EncodingSettings};
+use crate::pixel_format::output_pix_fmt;
+use crate::probe::{ColorInfo, HdrFormat, MasteringDisplay, VideoInfo};
+use serde::{Deserialize, Serialize};
+
+/// What happens to HDR sources; SDR sources are encoded the same way in every mode
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum HdrMode {
+    /// Keep HDR when the output codec can carry it, otherwise tone map to SDR
+    #[default]
+    Auto,
+    /// 10-bit output with the source's colour tags and mastering metadata
+    Preserve,
+    /// Convert to SDR BT.709
+    ToneMap,
+    /// Encode as if the source were SDR, which leaves the picture washed out
+    Ignore,
+}
+
+/// How a particular job treats its source's dynamic range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HdrHandling {
+    Sdr,
+    Preserve,
+    ToneMap,
+}
+
+/// Filters that tone map PQ or HLG video to SDR BT.709
+///
+/// `zscale` linearises the light, `tonemap` compresses the highlights and a
+/// second `zscale` converts to BT.709 limited range in the output pixel format.
+const TONE_MAP_FILTERS: [&str; 5] = [
+    "zscale=t=linear:npl=100",
+    "format=gbrpf32le",
+    "zscale=p=bt709",
+    "tonemap=tonemap=hable:desat=0",
+    "zscale=t=bt709:m=bt709:r=tv",
+];
+
+/// Filters the tone mapping chain depends on; `zscale` needs ffmpeg built with zimg
+const TONE_MAP_REQUIRED_FILTERS: [&str; 2] = ["zscale", "tonemap"];
+
+/// Check if a video codec has a standard way to signal HDR
+pub fn codec_supports_hdr(video_codec: &str) -> bool {
+    matches!(
+        encoder_codec_name(resolve_encoder(video_codec)),
+        "hevc" | "vp9" | "av1"
+    )
+}
+
+/// Decide how a job treats its source's dynamic range
+pub fn hdr_handling(settings: &EncodingSettings, info: &VideoInfo) -> HdrHandling {
+    if info.color.hdr_format().is_none() {
+        return HdrHandling::Sdr;
+    }
+    match settings.hdr_mode {
+        HdrMode::Auto if codec_supports_hdr(&settings.video_codec) => HdrHandling::Preserve,
+        HdrMode::Auto | HdrMode::ToneMap => HdrHandling::ToneMap,
+        HdrMode::Preserve => HdrHandling::Preserve,
+        HdrMode::Ignore => HdrHandling::Sdr,
+    }
+}
+
+/// Turn off tone mapping if the installed ffmpeg cannot do it
+///
+/// `filters` are the filters ffmpeg has, see `detect_filters`. The source is
+/// then encoded as SDR, the same as `HdrMode::Ignore`, and the returned note
+/// says so.
+pub fn check_tone_mapping(
+    settings: &mut EncodingSettings,
+    info: &VideoInfo,
+    filters: &[String],
+) -> Option<String> {
+    let available = TONE_MAP_REQUIRED_FILTERS
+        .iter()
+        .all(|name| filters.iter().any(|f| f == name));
+    if available || hdr_handling(settings, info) != HdrHandling::ToneMap {
+        return None;
+    }
+    settings.hdr_mode = HdrMode::Ignore;
+    Some("Encoded without tone mapping: ffmpeg lacks the zscale and tonemap filters".to_string())
+}
+
+/// Pixel format, filters and colour signalling for the video encoder
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorOutput {
+    pub pix_fmt: String,
+    /// Filters to run before scaling
+    pub filters: Vec<String>,
+    /// `-color_primaries`, `-color_trc`, `-colorspace` and `-color_range`
+    pub args: Vec<String>,
+    /// HDR10 options for libx265, which only writes them from its own parameters
+    pub x265_params: Vec<String>,
+}
+
+/// Colour handling arguments for a job encoded with `encoder`
+pub fn color_output(settings: &EncodingSettings, info: &VideoInfo, encoder: &str) -> ColorOutput {
+    let pix_fmt = output_pix_fmt(settings, info, encoder);
+    match hdr_handling(settings, info) {
+        HdrHandling::Sdr => ColorOutput {
+            pix_fmt,
+            filters: Vec::new(),
+            args: Vec::new(),
+            x265_params: Vec::new(),
+        },
+        HdrHandling::ToneMap => {
+            let mut filters: Vec<String> = TONE_MAP_FILTERS.iter().map(|f| f.to_string()).collect();
+            filters.push(format!("format={}", pix_fmt));
+            ColorOutput {
+                pix_fmt,
+                filters,
+                args: tag_args("bt709", "bt709", "bt709", "tv"),
+                x265_params: Vec::new(),
+            }
+        }
+        HdrHandling::Preserve => preserve_output(&info.color, encoder, pix_fmt),
+    }
+}
+
+fn preserve_output(color: &ColorInfo, encoder: &str, pix_fmt: String) -> ColorOutput {
+    let primaries = color.primaries.as_deref().unwrap_or("bt2020");
+    let transfer = color.transfer.as_deref().unwrap_or("smpte2084");
+    let space = color.space.as_deref().unwrap_or("bt2020nc");
+    let range = color.range.as_deref().unwrap_or("tv");
+
+    let mut x265_params = Vec::new();
+    if encoder == "libx265" {
+        x265_params.extend([
+            format!("colorprim={}", primaries),
+            format!("transfer={}", transfer),
+            format!("colormatrix={}", space),
+            format!("range={}", if range == "pc" { "full" } else { "limited" }),
+            "repeat-headers=1".to_string(),
+        ]);
+        if color.hdr_format() == Some(HdrFormat::Hdr10) {
+            x265_params.push("hdr10=1".to_string());
+            x265_params.push("hdr10-opt=1".to_string());
+            if let Some(display) = &color.mastering_display {
+                x265_params.push(format!("master-display={}", master_display(display)));
+            }
+            if let Some(light) = &color.content_light {
+                x265_params.push(format!(
+                    "max-cll={},{}",
+                    light.max_content, light.max_average
+                ));
+            }
+        }
+    }
+
+    ColorOutput {
+        pix_fmt,
+        filters: Vec::new(),
+        args: tag_args(primaries, transfer, space, range),
+        x265_params,
+    }
+}
+
+fn tag_args(primaries: &str, transfer: &str, space: &str, range: &str) -> Vec<String> {
+    vec![
+        "-color_primaries".to_string(),
+        primaries.to_string(),
+        "-color_trc".to_string(),
+        transfer.to_string(),
+        "-colorspace".to_string(),
+        space.to_string(),
+        "-color_range".to_string(),
+        range.to_string(),
+    ]
+}
+
+/// x265's `master-display` string: chromaticities in 0.00002 and luminance in
+/// 0.0001 cd/m² units, green first
+fn master_display(display: &MasteringDisplay) -> String {
+    let point = |(x, y): (f64, f64)| {
+        format!(
+            "({},{})",
+            (x * 50000.0).round() as u32,
+            (y * 50000.0).round() as u32
+        )
+    };
+    format!(
+        "G{}B{}R{}WP{}L({},{})",
+        point(display.green),
+        point(display.blue),
+        point(display.red),
+        point(display.white_point),
+        (display.max_luminance * 10000.0).round() as u64,
+        (display.min_luminance * 10000.0).round() as u64
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::ContentLight;
+    use std::path::PathBuf;
+
+    fn hdr10_info() -> VideoInfo {
+        VideoInfo {
+            path: PathBuf::from("hdr.mkv"),
+            width: 3840,
+            height: 2160,
+            bitrate: 20_000_000,
+            codec: "hevc".to_string(),
+            color: ColorInfo {
+                primaries: Some("bt2020".to_string()),
+                transfer: Some("smpte2084".to_string()),
+                space: Some("bt2020nc".to_string()),
+                range: Some("tv".to_string()),
+                mastering_display: Some(MasteringDisplay {
+                    red: (0.68, 0.32),
+                    green: (0.265, 0.69),
+                    blue: (0.15, 0.06),
+                    white_point: (0.3127, 0.329),
+                    min_luminance: 0.005,
+                    max_luminance: 1000.0,
+                }),
+                content_light: Some(ContentLight {
+                    max_content: 1000,
+                    max_average: 400,
+                }),
+            },
+            pix_fmt: Some("yuv420p10le".to_string()),
+            ..VideoInfo::test_default()
+        }
+    }
+
+    #[test]
+    fn test_hdr_handling() {
+        let info = hdr10_info();
+        let mut settings = EncodingSettings {
+            video_codec: "libx265".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(hdr_handling(&settings, &info), HdrHandling::Preserve);
+
+        settings.video_codec = "h264".to_string();
+        assert_eq!(hdr_handling(&settings, &info), HdrHandling::ToneMap);
+
+        settings.hdr_mode = HdrMode::Ignore;
+        assert_eq!(hdr_handling(&settings, &info), HdrHandling::Sdr);
+
+        let sdr = VideoInfo {
+            color: ColorInfo::default(),
+            ..info
+        };
+        settings.hdr_mode = HdrMode::ToneMap;
+        assert_eq!(hdr_handling(&settings, &sdr), HdrHandling::Sdr);
+    }
+
+    #[test]
+    fn test_check_tone_mapping() {
+        let info = hdr10_info();
+        let mut settings = EncodingSettings {
+            video_codec: "h264".to_string(),
+            ..Default::default()
+        };
+        let filters: Vec<String> = ["scale", "zscale", "tonemap"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(check_tone_mapping(&mut settings, &info, &filters), None);
+        assert_eq!(settings.hdr_mode, HdrMode::Auto);
+
+        assert!(check_tone_mapping(&mut settings, &info, &filters[..1]).is_some());
+        assert_eq!(settings.hdr_mode, HdrMode::Ignore);
+        assert_eq!(hdr_handling(&settings, &info), HdrHandling::Sdr);
+    }
+
+    #[test]
+    fn test_color_output() {
+        let info = hdr10_info();
+        let settings = EncodingSettings {
+            video_codec: "libx265".to_string(),
+            hdr_mode: HdrMode::Preserve,
+            ..Default::default()
+        };
+
+        let x265 = color_output(&settings, &info, "libx265");
+        assert_eq!(x265.pix_fmt, "yuv420p10le");
+        assert!(x265
+            .args
+            .windows(2)
+            .any(|w| w == ["-color_trc", "smpte2084"]));
+        assert!(x265.x265_params.contains(
+            &"master-display=G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,50)"
+                .to_string()
+        ));
+        assert!(x265.x265_params.contains(&"max-cll=1000,400".to_string()));
+
+        let nvenc = color_output(&settings, &info, "hevc_nvenc");
+        assert_eq!(nvenc.pix_fmt, "p010le");
+        assert!(nvenc.x265_params.is_empty());
+
+        let settings = EncodingSettings {
+            hdr_mode: HdrMode::ToneMap,
+            ..settings
+        };
+        let sdr = color_output(&settings, &info, "libx265");
+        assert_eq!(sdr.pix_fmt, "yuv420p");
+        assert!(sdr.filters.iter().any(|f| f.starts_with("tonemap=")));
+        assert_eq!(sdr.filters.last().unwrap(), "format=yuv420p");
+        assert!(sdr.args.windows(2).any(|w| w == ["-colorspace", "bt709"]));
+    }
+}
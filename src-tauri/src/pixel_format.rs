@@ -0,0 +1,273 @@
+use crate::encoder::{encoder_codec_name, EncodingSettings};
+use crate::hdr::{hdr_handling, HdrHandling};
+use crate::probe::VideoInfo;
+use serde::{Deserialize, Serialize};
+
+/// Pixel format of the encoded video
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum PixelFormat {
+    /// Keep the source's chroma subsampling and bit depth as far as the encoder
+    /// and profile allow
+    #[default]
+    MatchSource,
+    /// 4:2:0, which every player decodes; 8-bit unless HDR is kept
+    Compatible,
+    /// An ffmpeg pixel format such as `yuv422p10le`, passed through unchanged
+    Custom(String),
+}
+
+/// Chroma subsampling, from most to least subsampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Chroma {
+    Yuv420,
+    Yuv422,
+    Yuv444,
+}
+
+impl Chroma {
+    fn digits(self) -> &'static str {
+        match self {
+            Chroma::Yuv420 => "420",
+            Chroma::Yuv422 => "422",
+            Chroma::Yuv444 => "444",
+        }
+    }
+}
+
+type Format = (Chroma, u8);
+
+const ALL_CHROMA: [Chroma; 3] = [Chroma::Yuv420, Chroma::Yuv422, Chroma::Yuv444];
+
+/// Chroma subsampling and bit depth of an ffmpeg pixel format
+///
+/// RGB formats count as 4:4:4. Returns `None` for formats that are not
+/// understood, such as paletted or Bayer formats.
+fn parse_pix_fmt(name: &str) -> Option<Format> {
+    let base = name.trim_end_matches("le").trim_end_matches("be");
+
+    // Semi-planar formats: nv12 is 8-bit 4:2:0, p010 is 10-bit 4:2:0, p210 is 10-bit 4:2:2
+    if let Some(rest) = base.strip_prefix("nv") {
+        let chroma = match rest {
+            "12" | "21" => Chroma::Yuv420,
+            "16" | "61" => Chroma::Yuv422,
+            "24" | "42" => Chroma::Yuv444,
+            _ => return None,
+        };
+        return Some((chroma, 8));
+    }
+    if base.len() == 4 && base.starts_with('p') {
+        let chroma = match &base[1..2] {
+            "0" => Chroma::Yuv420,
+            "2" => Chroma::Yuv422,
+            "4" => Chroma::Yuv444,
+            _ => return None,
+        };
+        return Some((chroma, base[2..].parse().ok()?));
+    }
+
+    let chroma = if base.contains("444") || base.starts_with("gbr") {
+        Chroma::Yuv444
+    } else if base.contains("422") {
+        Chroma::Yuv422
+    } else if base.contains("420") || base.starts_with("gray") {
+        Chroma::Yuv420
+    } else {
+        return None;
+    };
+
+    // The depth follows the last `p` of planar names (`yuv420p10`, `gbrp12`) or ends gray
+    // names (`gray10`); packed formats such as `yuyv422` are 8-bit
+    let digits = match base.strip_prefix("gray") {
+        Some(digits) => digits,
+        None => base.rfind('p').map_or("", |index| &base[index + 1..]),
+    };
+    let depth = if digits.is_empty() {
+        8
+    } else {
+        digits.parse().ok()?
+    };
+    Some((chroma, depth))
+}
+
+/// Chroma subsampling and bit depths an encoder accepts
+fn encoder_formats(encoder: &str) -> Vec<Format> {
+    let formats = |chroma: &[Chroma], depths: &[u8]| {
+        chroma
+            .iter()
+            .flat_map(|c| depths.iter().map(move |d| (*c, *d)))
+            .collect()
+    };
+    match encoder {
+        "libx264" => formats(&ALL_CHROMA, &[8, 10]),
+        "libx265" | "libvpx-vp9" | "libaom-av1" => formats(&ALL_CHROMA, &[8, 10, 12]),
+        "libsvtav1" | "av1_nvenc" | "hevc_qsv" | "av1_qsv" => formats(&[Chroma::Yuv420], &[8, 10]),
+        "h264_nvenc" => formats(&[Chroma::Yuv420, Chroma::Yuv444], &[8]),
+        "hevc_nvenc" => formats(&[Chroma::Yuv420, Chroma::Yuv444], &[8, 10]),
+        // VAAPI, libvpx (VP8), mpeg4 and anything unknown get the safe default
+        _ => formats(&[Chroma::Yuv420], &[8]),
+    }
+}
+
+/// Largest chroma resolution and bit depth a codec profile allows
+///
+/// The 8-bit 4:2:0 profiles are the ones picked for compatibility with older
+/// players, so sources are brought down to `yuv420p` for them.
+fn profile_limit(codec: &str, profile: &str) -> Option<Format> {
+    match (codec, profile) {
+        ("h264", "baseline" | "constrained_baseline" | "main" | "high") => {
+            Some((Chroma::Yuv420, 8))
+        }
+        ("h264", "high10") => Some((Chroma::Yuv420, 10)),
+        ("h264", "high422") => Some((Chroma::Yuv422, 10)),
+        ("hevc", "main") => Some((Chroma::Yuv420, 8)),
+        ("hevc", "main10") => Some((Chroma::Yuv420, 10)),
+        ("hevc", "main12") => Some((Chroma::Yuv420, 12)),
+        ("vp9", "0") => Some((Chroma::Yuv420, 8)),
+        ("vp9", "1") => Some((Chroma::Yuv444, 8)),
+        ("vp9", "2") => Some((Chroma::Yuv420, 12)),
+        ("vp9", "3") => Some((Chroma::Yuv444, 12)),
+        _ => None,
+    }
+}
+
+/// ffmpeg name of a pixel format, in the layout the encoder takes as input
+fn format_name(encoder: &str, (chroma, depth): Format) -> String {
+    let hardware = encoder.ends_with("_nvenc") || encoder.ends_with("_qsv");
+    match (chroma, depth) {
+        (Chroma::Yuv420, 8) if encoder.ends_with("_qsv") => "nv12".to_string(),
+        (Chroma::Yuv420, 10) if hardware => "p010le".to_string(),
+        (Chroma::Yuv444, 10) if hardware => "yuv444p16le".to_string(),
+        (chroma, 8) => format!("yuv{}p", chroma.digits()),
+        (chroma, depth) => format!("yuv{}p{}le", chroma.digits(), depth),
+    }
+}
+
+/// Pixel format to encode with, given the job's settings, source and encoder
+pub fn output_pix_fmt(settings: &EncodingSettings, info: &VideoInfo, encoder: &str) -> String {
+    // Kept HDR needs at least 10 bits to avoid banding, tone mapped output is for
+    // players that only handle 8-bit SDR
+    let handling = hdr_handling(settings, info);
+    let min_depth = if handling == HdrHandling::Preserve {
+        10
+    } else {
+        8
+    };
+
+    let (mut chroma, mut depth) = match &settings.pixel_format {
+        PixelFormat::Custom(name) => return name.clone(),
+        PixelFormat::Compatible => (Chroma::Yuv420, min_depth),
+        PixelFormat::MatchSource => {
+            let (chroma, depth) = info
+                .pix_fmt
+                .as_deref()
+                .and_then(parse_pix_fmt)
+                .unwrap_or((Chroma::Yuv420, 8));
+            let depth = if handling == HdrHandling::ToneMap {
+                8
+            } else {
+                depth
+            };
+            (chroma, settings.bit_depth.unwrap_or(depth).max(min_depth))
+        }
+    };
+
+    let codec = encoder_codec_name(encoder);
+    let profile = settings.tuning.profile.as_deref();
+    if let Some((max_chroma, max_depth)) = profile.and_then(|p| profile_limit(codec, p)) {
+        chroma = chroma.min(max_chroma);
+        depth = depth.min(max_depth);
+    }
+
+    // The closest format the encoder supports without exceeding the request
+    let supported = encoder_formats(encoder);
+    let format = supported
+        .iter()
+        .filter(|(c, d)| *c <= chroma && *d <= depth)
+        .max()
+        .copied()
+        .unwrap_or(supported[0]);
+    format_name(encoder, format)
+}
+
+/// Check if the output keeps the source's chroma subsampling and bit depth
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn keeps_source_format(settings: &EncodingSettings, info: &VideoInfo, encoder: &str) -> bool {
+    let Some(source) = info.pix_fmt.as_deref().and_then(parse_pix_fmt) else {
+        return true;
+    };
+    parse_pix_fmt(&output_pix_fmt(settings, info, encoder)) == Some(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_control::VideoTuning;
+
+    fn source(pix_fmt: &str) -> VideoInfo {
+        VideoInfo {
+            codec: "prores".to_string(),
+            pix_fmt: Some(pix_fmt.to_string()),
+            ..VideoInfo::test_default()
+        }
+    }
+
+    #[test]
+    fn test_parse_pix_fmt() {
+        assert_eq!(parse_pix_fmt("yuv420p"), Some((Chroma::Yuv420, 8)));
+        assert_eq!(parse_pix_fmt("yuvj420p"), Some((Chroma::Yuv420, 8)));
+        assert_eq!(parse_pix_fmt("yuv422p10le"), Some((Chroma::Yuv422, 10)));
+        assert_eq!(parse_pix_fmt("yuv444p12be"), Some((Chroma::Yuv444, 12)));
+        assert_eq!(parse_pix_fmt("gbrp10le"), Some((Chroma::Yuv444, 10)));
+        assert_eq!(parse_pix_fmt("nv12"), Some((Chroma::Yuv420, 8)));
+        assert_eq!(parse_pix_fmt("p010le"), Some((Chroma::Yuv420, 10)));
+        assert_eq!(parse_pix_fmt("gray10le"), Some((Chroma::Yuv420, 10)));
+        assert_eq!(parse_pix_fmt("yuyv422"), Some((Chroma::Yuv422, 8)));
+        assert_eq!(parse_pix_fmt("pal8"), None);
+    }
+
+    #[test]
+    fn test_output_pix_fmt() {
+        let settings = EncodingSettings::default();
+        let prores = source("yuv422p10le");
+
+        assert_eq!(output_pix_fmt(&settings, &prores, "libx264"), "yuv422p10le");
+        assert_eq!(
+            output_pix_fmt(&settings, &prores, "libsvtav1"),
+            "yuv420p10le"
+        );
+        assert_eq!(output_pix_fmt(&settings, &prores, "hevc_nvenc"), "p010le");
+        assert_eq!(output_pix_fmt(&settings, &prores, "h264_qsv"), "nv12");
+        assert_eq!(output_pix_fmt(&settings, &prores, "libvpx"), "yuv420p");
+        assert_eq!(
+            output_pix_fmt(&settings, &source("yuv444p12le"), "libx264"),
+            "yuv444p10le"
+        );
+
+        // Compatibility profiles fall back to 8-bit 4:2:0
+        let high = EncodingSettings {
+            tuning: VideoTuning {
+                profile: Some("high".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(output_pix_fmt(&high, &prores, "libx264"), "yuv420p");
+
+        let deeper = EncodingSettings {
+            bit_depth: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            output_pix_fmt(&deeper, &source("yuv420p"), "libx265"),
+            "yuv420p10le"
+        );
+
+        let compatible = EncodingSettings {
+            pixel_format: PixelFormat::Compatible,
+            ..Default::default()
+        };
+        assert_eq!(output_pix_fmt(&compatible, &prores, "libx265"), "yuv420p");
+        assert!(!keeps_source_format(&compatible, &prores, "libx265"));
+        assert!(keeps_source_format(&settings, &prores, "libx265"));
+    }
+}
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 use anyhow::{Context, Result};
+use crate::runner::{wait_with_output, ProcessOutput, SharedProcessRunner, Tool};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoInfo {
@@ -15,6 +15,240 @@ pub struct VideoInfo {
     pub size: u64,
     pub audio_codec: Option<String>,
     pub audio_bitrate: Option<u64>,
+    /// Every stream in the container, in file order
+    #[serde(default)]
+    pub streams: Vec<StreamInfo>,
+    /// Pixel aspect ratio of the video stream; square pixels if not set
+    #[serde(default)]
+    pub sample_aspect_ratio: Option<(u32, u32)>,
+    /// Clockwise rotation in degrees that players (and ffmpeg) apply when decoding
+    #[serde(default)]
+    pub rotation: i32,
+    /// Colour description of the video stream, including HDR metadata
+    #[serde(default)]
+    pub color: ColorInfo,
+    /// Pixel format of the video stream as ffprobe names it, e.g. `yuv420p10le`
+    #[serde(default)]
+    pub pix_fmt: Option<String>,
+    /// Timestamp of the start of the file in seconds; packet times count from it,
+    /// while `-ss` seeks relative to it
+    #[serde(default)]
+    pub start_time: f64,
+}
+
+/// Colour properties as ffprobe names them, e.g. `bt2020` / `smpte2084` / `bt2020nc`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ColorInfo {
+    pub primaries: Option<String>,
+    pub transfer: Option<String>,
+    pub space: Option<String>,
+    /// `tv` (limited) or `pc` (full)
+    pub range: Option<String>,
+    pub mastering_display: Option<MasteringDisplay>,
+    pub content_light: Option<ContentLight>,
+}
+
+/// SMPTE ST 2086 mastering display colour volume
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MasteringDisplay {
+    /// CIE 1931 xy chromaticity coordinates
+    pub red: (f64, f64),
+    pub green: (f64, f64),
+    pub blue: (f64, f64),
+    pub white_point: (f64, f64),
+    /// Luminance in cd/m²
+    pub min_luminance: f64,
+    pub max_luminance: f64,
+}
+
+/// Content light level in cd/m² (MaxCLL and MaxFALL)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ContentLight {
+    pub max_content: u32,
+    pub max_average: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum HdrFormat {
+    /// PQ transfer (SMPTE ST 2084), also the base layer of HDR10+ and most Dolby Vision
+    Hdr10,
+    /// Hybrid log-gamma (ARIB STD-B67)
+    Hlg,
+}
+
+impl ColorInfo {
+    /// The HDR format, or `None` for SDR sources
+    pub fn hdr_format(&self) -> Option<HdrFormat> {
+        match self.transfer.as_deref() {
+            Some("smpte2084") => Some(HdrFormat::Hdr10),
+            Some("arib-std-b67") => Some(HdrFormat::Hlg),
+            _ => None,
+        }
+    }
+}
+
+impl VideoInfo {
+    /// Get all streams of a given type
+    pub fn streams_of(&self, stream_type: StreamType) -> impl Iterator<Item = &StreamInfo> {
+        self.streams
+            .iter()
+            .filter(move |s| s.stream_type == stream_type)
+    }
+
+    /// Frame size after rotation, as ffmpeg hands it to the filters
+    pub fn oriented_size(&self) -> (u32, u32) {
+        if self.rotation.rem_euclid(180) == 90 {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+
+    /// Size the video is shown at: rotated, with non-square pixels stretched to square
+    pub fn display_size(&self) -> (u32, u32) {
+        let (width, height) = self.oriented_size();
+        match self.sample_aspect_ratio {
+            Some((num, den)) if num != den => {
+                // The pixel aspect applies along the stored rows, which rotation turns into columns
+                if self.rotation.rem_euclid(180) == 90 {
+                    (width, (height as u64 * num as u64 / den as u64) as u32)
+                } else {
+                    ((width as u64 * num as u64 / den as u64) as u32, height)
+                }
+            }
+            _ => (width, height),
+        }
+    }
+}
+
+#[cfg(test)]
+impl VideoInfo {
+    /// A one minute 1080p25 H.264 file with AAC audio, for tests to adjust with
+    /// struct update syntax
+    pub fn test_default() -> Self {
+        VideoInfo {
+            path: PathBuf::from("in.mkv"),
+            duration: 60.0,
+            width: 1920,
+            height: 1080,
+            bitrate: 8_000_000,
+            codec: "h264".to_string(),
+            fps: 25.0,
+            size: 60_000_000,
+            audio_codec: Some("aac".to_string()),
+            audio_bitrate: Some(192_000),
+            streams: Vec::new(),
+            sample_aspect_ratio: None,
+            rotation: 0,
+            color: ColorInfo::default(),
+            pix_fmt: None,
+            start_time: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StreamType {
+    Video,
+    Audio,
+    Subtitle,
+    Attachment,
+    Data,
+}
+
+impl StreamType {
+    fn from_codec_type(codec_type: &str) -> Self {
+        match codec_type {
+            "video" => StreamType::Video,
+            "audio" => StreamType::Audio,
+            "subtitle" => StreamType::Subtitle,
+            "attachment" => StreamType::Attachment,
+            _ => StreamType::Data,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    Ass,
+    WebVtt,
+    MovText,
+    Pgs,
+    DvdSub,
+    DvbSub,
+    Other(String),
+}
+
+impl SubtitleFormat {
+    pub fn from_codec(codec: &str) -> Self {
+        match codec {
+            "subrip" | "srt" => SubtitleFormat::Srt,
+            "ass" | "ssa" => SubtitleFormat::Ass,
+            "webvtt" => SubtitleFormat::WebVtt,
+            "mov_text" => SubtitleFormat::MovText,
+            "hdmv_pgs_subtitle" => SubtitleFormat::Pgs,
+            "dvd_subtitle" => SubtitleFormat::DvdSub,
+            "dvb_subtitle" => SubtitleFormat::DvbSub,
+            other => SubtitleFormat::Other(other.to_string()),
+        }
+    }
+
+    /// Text formats can be converted between each other; bitmap formats cannot
+    pub fn is_text(&self) -> bool {
+        matches!(
+            self,
+            SubtitleFormat::Srt | SubtitleFormat::Ass | SubtitleFormat::WebVtt | SubtitleFormat::MovText
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StreamDisposition {
+    pub default: bool,
+    pub forced: bool,
+    pub commentary: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamInfo {
+    /// Absolute stream index in the input file
+    pub index: u32,
+    pub stream_type: StreamType,
+    pub codec: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub bitrate: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub disposition: StreamDisposition,
+    pub subtitle_format: Option<SubtitleFormat>,
+}
+
+#[cfg(test)]
+impl StreamInfo {
+    /// A stream known only by its index, type and codec, for tests
+    pub fn test_stream(index: u32, stream_type: StreamType, codec: &str) -> Self {
+        StreamInfo {
+            index,
+            stream_type,
+            codec: Some(codec.to_string()),
+            language: None,
+            title: None,
+            bitrate: None,
+            width: None,
+            height: None,
+            channels: None,
+            channel_layout: None,
+            sample_rate: None,
+            disposition: StreamDisposition::default(),
+            subtitle_format: (stream_type == StreamType::Subtitle)
+                .then(|| SubtitleFormat::from_codec(codec)),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -26,22 +260,177 @@ struct FFProbeOutput {
 #[derive(Debug, Deserialize)]
 struct Format {
     duration: Option<String>,
+    start_time: Option<String>,
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     size: Option<String>,
     bit_rate: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct Stream {
+    index: u32,
     codec_type: String,
-    codec_name: String,
+    codec_name: Option<String>,
     width: Option<u32>,
     height: Option<u32>,
     r_frame_rate: Option<String>,
+    sample_aspect_ratio: Option<String>,
+    pix_fmt: Option<String>,
+    color_primaries: Option<String>,
+    color_transfer: Option<String>,
+    color_space: Option<String>,
+    color_range: Option<String>,
     bit_rate: Option<String>,
+    channels: Option<u32>,
+    channel_layout: Option<String>,
+    sample_rate: Option<String>,
+    #[serde(default)]
+    disposition: Disposition,
+    #[serde(default)]
+    tags: StreamTags,
+    #[serde(default)]
+    side_data_list: Vec<SideData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SideData {
+    #[serde(default)]
+    side_data_type: String,
+    rotation: Option<f64>,
+    // Mastering display metadata, as `34000/50000` style rationals
+    red_x: Option<String>,
+    red_y: Option<String>,
+    green_x: Option<String>,
+    green_y: Option<String>,
+    blue_x: Option<String>,
+    blue_y: Option<String>,
+    white_point_x: Option<String>,
+    white_point_y: Option<String>,
+    min_luminance: Option<String>,
+    max_luminance: Option<String>,
+    // Content light level metadata
+    max_content: Option<u32>,
+    max_average: Option<u32>,
+}
+
+/// Parse a `num/den` rational
+fn parse_rational(value: &Option<String>) -> Option<f64> {
+    let (num, den) = value.as_ref()?.split_once('/')?;
+    let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+    (den != 0.0).then(|| num / den)
+}
+
+impl SideData {
+    fn mastering_display(&self) -> Option<MasteringDisplay> {
+        if self.side_data_type != "Mastering display metadata" {
+            return None;
+        }
+        let point = |x, y| Some((parse_rational(x)?, parse_rational(y)?));
+        Some(MasteringDisplay {
+            red: point(&self.red_x, &self.red_y)?,
+            green: point(&self.green_x, &self.green_y)?,
+            blue: point(&self.blue_x, &self.blue_y)?,
+            white_point: point(&self.white_point_x, &self.white_point_y)?,
+            min_luminance: parse_rational(&self.min_luminance)?,
+            max_luminance: parse_rational(&self.max_luminance)?,
+        })
+    }
+
+    fn content_light(&self) -> Option<ContentLight> {
+        if self.side_data_type != "Content light level metadata" {
+            return None;
+        }
+        Some(ContentLight {
+            max_content: self.max_content?,
+            max_average: self.max_average?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Disposition {
+    #[serde(default)]
+    default: u8,
+    #[serde(default)]
+    forced: u8,
+    #[serde(default)]
+    comment: u8,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StreamTags {
+    language: Option<String>,
+    title: Option<String>,
+    /// Rotation written by older muxers; newer ffprobe reports a display matrix instead
+    rotate: Option<String>,
+}
+
+impl Stream {
+    /// `16:15` style ratio; ffprobe reports `0:1` or `N/A` when it is unknown
+    fn sample_aspect_ratio(&self) -> Option<(u32, u32)> {
+        let (num, den) = self.sample_aspect_ratio.as_ref()?.split_once(':')?;
+        let (num, den) = (num.parse::<u32>().ok()?, den.parse::<u32>().ok()?);
+        (num > 0 && den > 0).then_some((num, den))
+    }
+
+    /// Clockwise rotation in degrees, normalised to 0, 90, 180 or 270
+    fn rotation(&self) -> i32 {
+        // The display matrix rotation is counter-clockwise
+        let degrees = self.side_data_list.iter()
+            .find_map(|data| data.rotation)
+            .map(|rotation| -rotation)
+            .or_else(|| self.tags.rotate.as_ref().and_then(|r| r.parse::<f64>().ok()))
+            .unwrap_or(0.0);
+        ((degrees / 90.0).round() as i32 * 90).rem_euclid(360)
+    }
+
+    fn color_info(&self) -> ColorInfo {
+        // "unknown" and "reserved" carry no more information than a missing tag
+        let known = |value: &Option<String>| {
+            value.clone().filter(|v| v != "unknown" && !v.starts_with("reserved"))
+        };
+        ColorInfo {
+            primaries: known(&self.color_primaries),
+            transfer: known(&self.color_transfer),
+            space: known(&self.color_space),
+            range: known(&self.color_range),
+            mastering_display: self.side_data_list.iter().find_map(SideData::mastering_display),
+            content_light: self.side_data_list.iter().find_map(SideData::content_light),
+        }
+    }
+
+    fn to_stream_info(&self) -> StreamInfo {
+        let stream_type = StreamType::from_codec_type(&self.codec_type);
+        let subtitle_format = match (stream_type, &self.codec_name) {
+            (StreamType::Subtitle, Some(codec)) => Some(SubtitleFormat::from_codec(codec)),
+            _ => None,
+        };
+
+        StreamInfo {
+            index: self.index,
+            stream_type,
+            codec: self.codec_name.clone(),
+            // "und" is ffprobe's marker for an unknown language
+            language: self.tags.language.clone().filter(|l| l != "und"),
+            title: self.tags.title.clone(),
+            bitrate: self.bit_rate.as_ref().and_then(|b| b.parse::<u64>().ok()),
+            width: self.width,
+            height: self.height,
+            channels: self.channels,
+            channel_layout: self.channel_layout.clone(),
+            sample_rate: self.sample_rate.as_ref().and_then(|r| r.parse::<u32>().ok()),
+            disposition: StreamDisposition {
+                default: self.disposition.default != 0,
+                forced: self.disposition.forced != 0,
+                commentary: self.disposition.comment != 0,
+            },
+            subtitle_format,
+        }
+    }
 }
 
 /// Probe video file using ffprobe and extract metadata
-pub async fn probe_video(path: PathBuf) -> Result<VideoInfo> {
+pub async fn probe_video(runner: &SharedProcessRunner, path: PathBuf) -> Result<VideoInfo> {
     // Check if file exists
     if !path.exists() {
         anyhow::bail!("File does not exist: {:?}", path);
@@ -53,24 +442,70 @@ pub async fn probe_video(path: PathBuf) -> Result<VideoInfo> {
     let size = metadata.len();
 
     // Execute ffprobe command
-    let output = Command::new("ffprobe")
-        .args([
-            "-v", "quiet",
-            "-print_format", "json",
-            "-show_format",
-            "-show_streams",
-            path.to_str().context("Failed to convert path to string")?,
-        ])
-        .output()
-        .context("Failed to execute ffprobe. Make sure ffprobe is installed and in PATH")?;
+    let args = [
+        "-v", "quiet",
+        "-print_format", "json",
+        "-show_format",
+        "-show_streams",
+        path.to_str().context("Failed to convert path to string")?,
+    ];
+    let output = run_ffprobe(runner, &args).await?;
+
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("ffprobe failed: {}", error);
+    }
+
+    parse_probe_output(path, size, &output.stdout)
+}
+
+/// List keyframe timestamps (in seconds) of the first video stream
+pub async fn probe_keyframes(runner: &SharedProcessRunner, path: &Path) -> Result<Vec<f64>> {
+    // Reading packet flags avoids decoding the video
+    let args = [
+        "-v", "error",
+        "-select_streams", "v:0",
+        "-show_entries", "packet=pts_time,flags",
+        "-of", "csv=p=0",
+        path.to_str().context("Failed to convert path to string")?,
+    ];
+    let output = run_ffprobe(runner, &args).await?;
 
     if !output.status.success() {
         let error = String::from_utf8_lossy(&output.stderr);
         anyhow::bail!("ffprobe failed: {}", error);
     }
 
-    // Parse JSON output
-    let probe_data: FFProbeOutput = serde_json::from_slice(&output.stdout)
+    Ok(parse_keyframes(&String::from_utf8_lossy(&output.stdout)))
+}
+
+async fn run_ffprobe(runner: &SharedProcessRunner, args: &[&str]) -> Result<ProcessOutput> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let mut process = runner
+        .spawn(Tool::Ffprobe, &args)
+        .context("Failed to execute ffprobe. Make sure ffprobe is installed and in PATH")?;
+    wait_with_output(process.as_mut()).await
+}
+
+/// Parse `pts_time,flags` lines, keeping packets flagged as keyframes
+fn parse_keyframes(csv: &str) -> Vec<f64> {
+    let mut keyframes: Vec<f64> = csv.lines()
+        .filter_map(|line| {
+            let (time, flags) = line.trim().split_once(',')?;
+            if !flags.starts_with('K') {
+                return None;
+            }
+            time.parse::<f64>().ok()
+        })
+        .collect();
+
+    keyframes.sort_by(|a, b| a.total_cmp(b));
+    keyframes
+}
+
+/// Build `VideoInfo` from ffprobe JSON output
+fn parse_probe_output(path: PathBuf, size: u64, json: &[u8]) -> Result<VideoInfo> {
+    let probe_data: FFProbeOutput = serde_json::from_slice(json)
         .context("Failed to parse ffprobe output")?;
 
     // Extract video stream info
@@ -87,6 +522,10 @@ pub async fn probe_video(path: PathBuf) -> Result<VideoInfo> {
         .and_then(|d| d.parse::<f64>().ok())
         .unwrap_or(0.0);
 
+    let start_time = probe_data.format.start_time
+        .and_then(|t| t.parse::<f64>().ok())
+        .unwrap_or(0.0);
+
     // Parse bitrate
     let bitrate = probe_data.format.bit_rate
         .and_then(|b| b.parse::<u64>().ok())
@@ -109,7 +548,7 @@ pub async fn probe_video(path: PathBuf) -> Result<VideoInfo> {
         .unwrap_or(0.0);
 
     // Extract audio info
-    let audio_codec = audio_stream.map(|s| s.codec_name.clone());
+    let audio_codec = audio_stream.and_then(|s| s.codec_name.clone());
     let audio_bitrate = audio_stream
         .and_then(|s| s.bit_rate.as_ref())
         .and_then(|b| b.parse::<u64>().ok());
@@ -120,28 +559,36 @@ pub async fn probe_video(path: PathBuf) -> Result<VideoInfo> {
         width: video_stream.width.unwrap_or(0),
         height: video_stream.height.unwrap_or(0),
         bitrate,
-        codec: video_stream.codec_name.clone(),
+        codec: video_stream.codec_name.clone().unwrap_or_default(),
         fps,
         size,
         audio_codec,
         audio_bitrate,
+        streams: probe_data.streams.iter().map(Stream::to_stream_info).collect(),
+        sample_aspect_ratio: video_stream.sample_aspect_ratio(),
+        rotation: video_stream.rotation(),
+        color: video_stream.color_info(),
+        pix_fmt: video_stream.pix_fmt.clone(),
+        start_time,
     })
 }
 
 /// Check if ffprobe is available
-pub fn check_ffprobe() -> bool {
-    Command::new("ffprobe")
-        .arg("-version")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn check_ffprobe(runner: &SharedProcessRunner) -> bool {
+    check_tool(runner, Tool::Ffprobe)
 }
 
 /// Check if ffmpeg is available
-pub fn check_ffmpeg() -> bool {
-    Command::new("ffmpeg")
-        .arg("-version")
-        .output()
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn check_ffmpeg(runner: &SharedProcessRunner) -> bool {
+    check_tool(runner, Tool::Ffmpeg)
+}
+
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+fn check_tool(runner: &SharedProcessRunner, tool: Tool) -> bool {
+    runner
+        .output_blocking(tool, &["-version".to_string()])
         .map(|output| output.status.success())
         .unwrap_or(false)
 }
@@ -149,14 +596,132 @@ pub fn check_ffmpeg() -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner::SystemProcessRunner;
 
     #[test]
     fn test_ffprobe_available() {
-        assert!(check_ffprobe(), "ffprobe is not available");
+        assert!(check_ffprobe(&SystemProcessRunner::shared()), "ffprobe is not available");
     }
 
     #[test]
     fn test_ffmpeg_available() {
-        assert!(check_ffmpeg(), "ffmpeg is not available");
+        assert!(check_ffmpeg(&SystemProcessRunner::shared()), "ffmpeg is not available");
+    }
+
+    #[test]
+    fn test_parse_keyframes() {
+        let csv = "0.000000,K__\n0.041708,___\n2.085417,K__\nN/A,K__\n1.001000,K_\n";
+        assert_eq!(parse_keyframes(csv), vec![0.0, 1.001, 2.085417]);
+    }
+
+    #[test]
+    fn test_parse_multi_stream_output() {
+        let json = br#"{
+            "format": { "duration": "120.5", "start_time": "1.400000", "size": "1000", "bit_rate": "4000000" },
+            "streams": [
+                { "index": 0, "codec_type": "video", "codec_name": "h264",
+                  "width": 1920, "height": 1080, "r_frame_rate": "24000/1001",
+                  "sample_aspect_ratio": "1:1",
+                  "disposition": { "default": 1, "forced": 0, "comment": 0 } },
+                { "index": 1, "codec_type": "audio", "codec_name": "aac",
+                  "channels": 6, "channel_layout": "5.1", "sample_rate": "48000",
+                  "bit_rate": "384000",
+                  "disposition": { "default": 1, "forced": 0, "comment": 0 },
+                  "tags": { "language": "eng", "title": "Surround" } },
+                { "index": 2, "codec_type": "audio", "codec_name": "ac3",
+                  "channels": 2, "sample_rate": "48000",
+                  "disposition": { "default": 0, "forced": 0, "comment": 1 },
+                  "tags": { "language": "deu" } },
+                { "index": 3, "codec_type": "subtitle", "codec_name": "subrip",
+                  "disposition": { "default": 0, "forced": 1, "comment": 0 },
+                  "tags": { "language": "fra" } },
+                { "index": 4, "codec_type": "attachment",
+                  "tags": { "language": "und" } }
+            ]
+        }"#;
+
+        let info = parse_probe_output(PathBuf::from("in.mkv"), 1000, json).unwrap();
+        assert_eq!(info.codec, "h264");
+        assert_eq!(info.audio_codec.as_deref(), Some("aac"));
+        assert_eq!(info.streams.len(), 5);
+        assert_eq!(info.streams_of(StreamType::Audio).count(), 2);
+        assert_eq!(info.display_size(), (1920, 1080));
+        assert_eq!(info.start_time, 1.4);
+
+        let commentary = &info.streams[2];
+        assert_eq!(commentary.language.as_deref(), Some("deu"));
+        assert!(commentary.disposition.commentary);
+
+        let subtitle = &info.streams[3];
+        assert_eq!(subtitle.subtitle_format, Some(SubtitleFormat::Srt));
+        assert!(subtitle.disposition.forced);
+
+        let attachment = &info.streams[4];
+        assert_eq!(attachment.stream_type, StreamType::Attachment);
+        assert_eq!(attachment.codec, None);
+        assert_eq!(attachment.language, None);
+    }
+
+    #[test]
+    fn test_parse_rotation_and_aspect() {
+        let json = br#"{
+            "format": { "duration": "10.0" },
+            "streams": [
+                { "index": 0, "codec_type": "video", "codec_name": "h264",
+                  "width": 1920, "height": 1080, "sample_aspect_ratio": "1:1",
+                  "side_data_list": [ { "side_data_type": "Display Matrix", "rotation": -90 } ] }
+            ]
+        }"#;
+        let phone = parse_probe_output(PathBuf::from("phone.mp4"), 0, json).unwrap();
+        assert_eq!(phone.rotation, 90);
+        assert_eq!(phone.display_size(), (1080, 1920));
+
+        let json = br#"{
+            "format": { "duration": "10.0" },
+            "streams": [
+                { "index": 0, "codec_type": "video", "codec_name": "mpeg2video",
+                  "width": 720, "height": 576, "sample_aspect_ratio": "64:45" }
+            ]
+        }"#;
+        let dvd = parse_probe_output(PathBuf::from("dvd.mpg"), 0, json).unwrap();
+        assert_eq!(dvd.sample_aspect_ratio, Some((64, 45)));
+        assert_eq!(dvd.display_size(), (1024, 576));
+    }
+
+    #[test]
+    fn test_parse_hdr_metadata() {
+        let json = br#"{
+            "format": { "duration": "10.0" },
+            "streams": [
+                { "index": 0, "codec_type": "video", "codec_name": "hevc",
+                  "width": 3840, "height": 2160, "pix_fmt": "yuv420p10le",
+                  "color_range": "tv",
+                  "color_space": "bt2020nc", "color_transfer": "smpte2084",
+                  "color_primaries": "bt2020",
+                  "side_data_list": [
+                      { "side_data_type": "Mastering display metadata",
+                        "red_x": "34000/50000", "red_y": "16000/50000",
+                        "green_x": "13250/50000", "green_y": "34500/50000",
+                        "blue_x": "7500/50000", "blue_y": "3000/50000",
+                        "white_point_x": "15635/50000", "white_point_y": "16450/50000",
+                        "min_luminance": "50/10000", "max_luminance": "10000000/10000" },
+                      { "side_data_type": "Content light level metadata",
+                        "max_content": 1000, "max_average": 400 }
+                  ] }
+            ]
+        }"#;
+        let info = parse_probe_output(PathBuf::from("hdr.mkv"), 0, json).unwrap();
+        assert_eq!(info.pix_fmt.as_deref(), Some("yuv420p10le"));
+        assert_eq!(info.color.hdr_format(), Some(HdrFormat::Hdr10));
+        assert_eq!(info.color.space.as_deref(), Some("bt2020nc"));
+        let display = info.color.mastering_display.unwrap();
+        assert_eq!(display.red, (0.68, 0.32));
+        assert_eq!(display.max_luminance, 1000.0);
+        assert_eq!(
+            info.color.content_light,
+            Some(ContentLight { max_content: 1000, max_average: 400 })
+        );
+        // Rotation side data is unaffected by the extra entries
+        assert_eq!(info.rotation, 0);
     }
 }
@@ -0,0 +1,260 @@
+use crate::encoder::{null_device, ProcessControl};
+use crate::events::SharedEventSink;
+use crate::runner::{wait_with_output, SharedProcessRunner, Tool};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// PSNR reported for identical frames, which ffmpeg prints as `inf`
+const IDENTICAL_PSNR: f64 = 100.0;
+
+/// Scores from comparing an encoded output against its input
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct QualityMetrics {
+    /// Mean SSIM over all planes (0-1)
+    pub ssim: Option<f64>,
+    /// Average PSNR in dB
+    pub psnr: Option<f64>,
+    /// Mean VMAF score (0-100), only if ffmpeg was built with libvmaf
+    pub vmaf: Option<f64>,
+}
+
+/// What happens to a job whose output scores below the thresholds
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum QualityFailureAction {
+    /// Complete the job but attach a warning
+    #[default]
+    Flag,
+    /// Mark the job as failed; the output is kept for inspection
+    Fail,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QualityCheck {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Also compute VMAF when the local ffmpeg supports it
+    #[serde(default)]
+    pub use_vmaf: bool,
+    #[serde(default)]
+    pub min_ssim: Option<f64>,
+    #[serde(default)]
+    pub min_psnr: Option<f64>,
+    #[serde(default)]
+    pub min_vmaf: Option<f64>,
+    #[serde(default)]
+    pub on_failure: QualityFailureAction,
+}
+
+impl QualityMetrics {
+    /// Describe every score below its minimum, or `None` if the output passes
+    ///
+    /// Metrics that were not measured are not checked.
+    pub fn below_threshold(&self, check: &QualityCheck) -> Option<String> {
+        let scores = [
+            ("SSIM", self.ssim, check.min_ssim),
+            ("PSNR", self.psnr, check.min_psnr),
+            ("VMAF", self.vmaf, check.min_vmaf),
+        ];
+
+        let failures: Vec<String> = scores
+            .iter()
+            .filter_map(|(name, value, min)| match (value, min) {
+                (Some(value), Some(min)) if value < min => {
+                    Some(format!("{} {:.4} is below {}", name, value, min))
+                }
+                _ => None,
+            })
+            .collect();
+
+        if failures.is_empty() {
+            None
+        } else {
+            Some(format!("Quality check failed: {}", failures.join(", ")))
+        }
+    }
+}
+
+/// Check if the local ffmpeg has the libvmaf filter
+pub fn detect_vmaf(runner: &SharedProcessRunner) -> bool {
+    let args = ["-filters".to_string(), "-hide_banner".to_string()];
+    runner
+        .output_blocking(Tool::Ffmpeg, &args)
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("libvmaf"))
+        .unwrap_or(false)
+}
+
+/// Build the filtergraph comparing the output (input 0) against the source (input 1)
+///
+/// `geometry` holds the scale, crop and pad filters of the encode; the source runs
+/// through them so both sides have the same frame size and framing.
+fn quality_filter(geometry: &[String], vmaf: bool) -> String {
+    let mut metrics = vec!["ssim", "psnr"];
+    if vmaf {
+        metrics.push("libvmaf");
+    }
+    let count = metrics.len();
+
+    let distorted: String = (0..count).map(|i| format!("[d{}]", i)).collect();
+    let references: String = (0..count).map(|i| format!("[r{}]", i)).collect();
+
+    // Both inputs are rotated on decode, which the geometry filters already expect
+    let reference_filters: String = geometry.iter().map(|f| format!("{},", f)).collect();
+    let mut graph = vec![
+        format!(
+            "[0:v:0]settb=AVTB,setpts=PTS-STARTPTS,split={}{}",
+            count, distorted
+        ),
+        format!(
+            "[1:v:0]{}settb=AVTB,setpts=PTS-STARTPTS,split={}{}",
+            reference_filters, count, references
+        ),
+    ];
+    for (i, metric) in metrics.iter().enumerate() {
+        graph.push(format!("[d{}][r{}]{}", i, i, metric));
+    }
+
+    graph.join(";")
+}
+
+/// Pull the summary scores out of ffmpeg's stderr
+pub fn parse_quality_output(stderr: &str) -> QualityMetrics {
+    let mut metrics = QualityMetrics::default();
+
+    for line in stderr.lines() {
+        if line.contains("SSIM ") {
+            metrics.ssim = value_after(line, "All:");
+        } else if line.contains("PSNR ") {
+            metrics.psnr = value_after(line, "average:");
+        } else if line.contains("VMAF score") {
+            metrics.vmaf = value_after(line, "VMAF score:");
+        }
+    }
+
+    metrics
+}
+
+fn value_after(line: &str, key: &str) -> Option<f64> {
+    let rest = line.split(key).nth(1)?.trim_start();
+    let token = rest.split_whitespace().next()?;
+    if token == "inf" {
+        return Some(IDENTICAL_PSNR);
+    }
+    token.parse::<f64>().ok()
+}
+
+/// Compare an encoded output against its source
+pub async fn measure_quality(
+    runner: &SharedProcessRunner,
+    input: &Path,
+    output: &Path,
+    geometry: &[String],
+    check: &QualityCheck,
+    control: &ProcessControl,
+    events: &SharedEventSink,
+) -> Result<QualityMetrics> {
+    let vmaf = check.use_vmaf && detect_vmaf(runner);
+    if check.use_vmaf && !vmaf {
+        events.log("libvmaf is not available in this ffmpeg build, skipping VMAF".to_string());
+    }
+
+    let args = vec![
+        "-hide_banner".to_string(),
+        "-nostats".to_string(),
+        "-i".to_string(),
+        output.to_string_lossy().to_string(),
+        "-i".to_string(),
+        input.to_string_lossy().to_string(),
+        "-lavfi".to_string(),
+        quality_filter(geometry, vmaf),
+        "-f".to_string(),
+        "null".to_string(),
+        null_device().to_string(),
+    ];
+
+    let mut child = runner
+        .spawn(Tool::Ffmpeg, &args)
+        .context("Failed to spawn ffmpeg for quality check")?;
+
+    let pid = child.id();
+    control.attach(pid)?;
+
+    let result = tokio::select! {
+        result = wait_with_output(child.as_mut()) => Some(result),
+        _ = control.cancelled() => None,
+    };
+    control.detach(pid);
+
+    let Some(result) = result else {
+        let _ = child.kill().await;
+        anyhow::bail!("Quality check cancelled");
+    };
+    let result = result.context("Failed to wait for ffmpeg quality check")?;
+
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if !result.status.success() {
+        anyhow::bail!("Quality check failed with status: {}", result.status);
+    }
+
+    Ok(parse_quality_output(&stderr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quality_output() {
+        let stderr = "\
+[Parsed_ssim_4 @ 0x55d1] SSIM Y:0.991234 (20.568) U:0.995 (23.0) V:0.994 (22.2) All:0.992345 (21.159)
+[Parsed_psnr_5 @ 0x55d2] PSNR y:42.10 u:46.33 v:46.01 average:43.21 min:38.50 max:49.90
+[Parsed_libvmaf_6 @ 0x55d3] VMAF score: 95.432100
+";
+        let metrics = parse_quality_output(stderr);
+        assert_eq!(metrics.ssim, Some(0.992345));
+        assert_eq!(metrics.psnr, Some(43.21));
+        assert_eq!(metrics.vmaf, Some(95.4321));
+
+        let identical = parse_quality_output(
+            "[Parsed_psnr_5 @ 0x1] PSNR y:inf u:inf v:inf average:inf min:inf max:inf",
+        );
+        assert_eq!(identical.psnr, Some(IDENTICAL_PSNR));
+        assert_eq!(identical.vmaf, None);
+    }
+
+    #[test]
+    fn test_quality_filter() {
+        let geometry = vec![
+            "scale=1280:720".to_string(),
+            "setsar=1".to_string(),
+            "pad=1280:960:(ow-iw)/2:(oh-ih)/2".to_string(),
+        ];
+        let graph = quality_filter(&geometry, false);
+        assert!(graph.starts_with("[0:v:0]settb=AVTB,setpts=PTS-STARTPTS,split=2[d0][d1];"));
+        assert!(graph.contains(
+            "[1:v:0]scale=1280:720,setsar=1,pad=1280:960:(ow-iw)/2:(oh-ih)/2,settb=AVTB,"
+        ));
+        assert!(graph.ends_with("[d0][r0]ssim;[d1][r1]psnr"));
+    }
+
+    #[test]
+    fn test_below_threshold() {
+        let metrics = QualityMetrics {
+            ssim: Some(0.95),
+            psnr: Some(38.0),
+            vmaf: None,
+        };
+        let mut check = QualityCheck {
+            enabled: true,
+            min_ssim: Some(0.9),
+            min_vmaf: Some(90.0),
+            ..Default::default()
+        };
+        assert_eq!(metrics.below_threshold(&check), None);
+
+        check.min_psnr = Some(40.0);
+        let warning = metrics.below_threshold(&check).unwrap();
+        assert!(warning.contains("PSNR"));
+        assert!(!warning.contains("SSIM"));
+    }
+}
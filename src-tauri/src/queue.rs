@@ -1,7 +1,16 @@
-use crate::encoder::{encode_video, EncodingProgress, EncodingSettings};
+use crate::chunked::{chunking_blocker, completed_segments, encode_chunked, should_chunk};
+use crate::encoder::{
+    apply_source_limits, encode_video, geometry_filters, subtitle_adjustments, CompliancePolicy,
+    EncodingProgress, EncodingSettings, ProcessControl,
+};
+use crate::events::{Event, SharedEventSink};
+use crate::hdr::{check_tone_mapping, hdr_handling, HdrHandling};
 use crate::probe::VideoInfo;
-use anyhow::Result;
+use crate::quality::{measure_quality, QualityFailureAction, QualityMetrics};
+use crate::runner::{SharedProcessRunner, SystemProcessRunner};
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{Mutex, Semaphore};
@@ -12,8 +21,9 @@ pub enum JobStatus {
     Processing { progress: f32 },
     Completed { output_path: PathBuf },
     Failed { error: String },
-    Paused,
+    Paused { progress: f32 },
     Cancelled,
+    Skipped { reason: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +37,21 @@ pub struct Job {
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub started_at: Option<chrono::DateTime<chrono::Utc>>,
     pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Input already satisfies the requested settings
+    #[serde(default)]
+    pub already_compliant: bool,
+    /// Scores from the post-encode quality check
+    #[serde(default)]
+    pub quality: Option<QualityMetrics>,
+    /// Set when the output scored below the configured thresholds
+    #[serde(default)]
+    pub quality_warning: Option<String>,
+    /// `settings` adjusted to the source's resolution and bitrate; what the encode uses
+    #[serde(default)]
+    pub effective_settings: Option<EncodingSettings>,
+    /// What was changed from the requested settings and why
+    #[serde(default)]
+    pub adjustments: Vec<String>,
 }
 
 impl Job {
@@ -36,36 +61,150 @@ impl Job {
         video_info: VideoInfo,
         settings: EncodingSettings,
     ) -> Self {
+        // Jobs the source limits reject are kept so the reason is visible
+        let (status, effective_settings, adjustments) =
+            match apply_source_limits(&settings, &video_info) {
+                Ok((effective, mut adjustments)) => {
+                    if let Some(reason) = chunking_blocker(&effective, &video_info) {
+                        adjustments.push(format!("Encoded in one piece: {}", reason));
+                    }
+                    adjustments.extend(subtitle_adjustments(&effective, &video_info));
+                    (JobStatus::Pending, Some(effective), adjustments)
+                }
+                Err(e) => (
+                    JobStatus::Failed {
+                        error: e.to_string(),
+                    },
+                    None,
+                    Vec::new(),
+                ),
+            };
+
         Self {
             id: uuid::Uuid::new_v4().to_string(),
             input_path,
             output_path,
             video_info,
             settings,
-            status: JobStatus::Pending,
+            status,
             created_at: chrono::Utc::now(),
             started_at: None,
             completed_at: None,
+            already_compliant: false,
+            quality: None,
+            quality_warning: None,
+            effective_settings,
+            adjustments,
+        }
+    }
+
+    /// Settings the encoder runs with
+    ///
+    /// Jobs saved before source limits were recorded use their requested settings.
+    pub fn encode_settings(&self) -> &EncodingSettings {
+        self.effective_settings.as_ref().unwrap_or(&self.settings)
+    }
+
+    /// Encode as SDR if the job would tone map but ffmpeg lacks the filters for it
+    ///
+    /// `filters` are the filters ffmpeg has, see `detect_filters`.
+    pub fn check_tone_mapping(&mut self, filters: &[String]) {
+        let Some(settings) = self.effective_settings.as_mut() else {
+            return;
+        };
+        if let Some(note) = check_tone_mapping(settings, &self.video_info, filters) {
+            self.adjustments.push(note);
         }
     }
+
+    /// Check if an interrupted encode continues from finished segments
+    ///
+    /// Only chunked encodes can; see `should_chunk` for which jobs are chunked.
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fn can_resume(&self) -> bool {
+        completed_segments(&self.output_path).is_some()
+    }
+
+    /// Reset a job left `Processing` or `Paused` by a crash or app restart
+    ///
+    /// Chunked encodes keep their finished segments and resume from them. Every
+    /// other job starts over and its partial output is removed. Returns `true` if
+    /// the job was changed.
+    pub fn reconcile_interrupted(&mut self) -> bool {
+        if !matches!(
+            self.status,
+            JobStatus::Processing { .. } | JobStatus::Paused { .. }
+        ) {
+            return false;
+        }
+
+        match completed_segments(&self.output_path) {
+            Some((done, total)) => eprintln!(
+                "Job {} was interrupted, resuming at segment {} of {}",
+                self.id,
+                done + 1,
+                total
+            ),
+            None => {
+                eprintln!(
+                    "Job {} was interrupted and starts over; only chunked encodes resume",
+                    self.id
+                );
+                if self.output_path.exists() {
+                    let _ = std::fs::remove_file(&self.output_path);
+                }
+            }
+        }
+
+        self.status = JobStatus::Pending;
+        self.started_at = None;
+        true
+    }
 }
 
 pub struct JobQueue {
     jobs: Arc<Mutex<Vec<Job>>>,
     semaphore: Arc<Mutex<Arc<Semaphore>>>,
     paused: Arc<Mutex<bool>>,
+    processes: Arc<Mutex<HashMap<String, ProcessControl>>>,
+    runner: Arc<std::sync::RwLock<SharedProcessRunner>>,
 }
 
 impl JobQueue {
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub fn new(max_concurrent: usize) -> Self {
+        Self::with_runner(max_concurrent, SystemProcessRunner::shared())
+    }
+
+    /// Create a queue that starts ffmpeg through `runner`
+    pub fn with_runner(max_concurrent: usize, runner: SharedProcessRunner) -> Self {
         Self {
             jobs: Arc::new(Mutex::new(Vec::new())),
             semaphore: Arc::new(Mutex::new(Arc::new(Semaphore::new(max_concurrent)))),
             paused: Arc::new(Mutex::new(false)),
+            processes: Arc::new(Mutex::new(HashMap::new())),
+            runner: Arc::new(std::sync::RwLock::new(runner)),
         }
     }
 
+    pub fn runner(&self) -> SharedProcessRunner {
+        self.runner.read().unwrap().clone()
+    }
+
+    /// Replace the runner used by jobs started from now on
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fn set_runner(&self, runner: SharedProcessRunner) {
+        *self.runner.write().unwrap() = runner;
+    }
+
+    /// Runner for a job, honouring binary paths set in its settings
+    pub fn runner_for(&self, settings: &EncodingSettings) -> SharedProcessRunner {
+        let runner = self.runner();
+        runner.with_paths(&settings.tool_paths).unwrap_or(runner)
+    }
+
     /// Set maximum concurrent jobs
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub async fn set_max_concurrent(&self, max_concurrent: usize) {
         let mut semaphore = self.semaphore.lock().await;
         *semaphore = Arc::new(Semaphore::new(max_concurrent));
@@ -89,10 +228,36 @@ impl JobQueue {
         jobs.iter().find(|j| j.id == id).cloned()
     }
 
+    /// Store quality scores for a job
+    pub async fn set_job_quality(
+        &self,
+        id: &str,
+        metrics: QualityMetrics,
+        warning: Option<String>,
+    ) {
+        let mut jobs = self.jobs.lock().await;
+        if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+            job.quality = Some(metrics);
+            job.quality_warning = warning;
+        }
+    }
+
     /// Update job status
     pub async fn update_job_status(&self, id: &str, status: JobStatus) {
         let mut jobs = self.jobs.lock().await;
         if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+            // A cancelled job must not be revived by late progress or result updates
+            if matches!(job.status, JobStatus::Cancelled)
+                && matches!(
+                    status,
+                    JobStatus::Processing { .. }
+                        | JobStatus::Completed { .. }
+                        | JobStatus::Failed { .. }
+                )
+            {
+                return;
+            }
+
             job.status = status.clone();
 
             match status {
@@ -101,7 +266,7 @@ impl JobQueue {
                         job.started_at = Some(chrono::Utc::now());
                     }
                 }
-                JobStatus::Completed { .. } | JobStatus::Failed { .. } => {
+                JobStatus::Completed { .. } | JobStatus::Failed { .. } | JobStatus::Cancelled => {
                     job.completed_at = Some(chrono::Utc::now());
                 }
                 _ => {}
@@ -109,28 +274,119 @@ impl JobQueue {
         }
     }
 
+    /// Update progress of a job that is still processing
+    ///
+    /// Progress updates arrive asynchronously, so they must not override a job
+    /// that has been paused, cancelled or finished in the meantime.
+    pub async fn update_job_progress(&self, id: &str, progress: f32) {
+        let mut jobs = self.jobs.lock().await;
+        if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+            if matches!(job.status, JobStatus::Processing { .. }) {
+                job.status = JobStatus::Processing { progress };
+            }
+        }
+    }
+
     /// Remove a job from the queue
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub async fn remove_job(&self, id: &str) {
         let mut jobs = self.jobs.lock().await;
         jobs.retain(|j| j.id != id);
     }
 
     /// Clear all jobs
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub async fn clear_jobs(&self) {
         let mut jobs = self.jobs.lock().await;
         jobs.clear();
     }
 
-    /// Pause the queue
-    pub async fn pause(&self) {
-        let mut paused = self.paused.lock().await;
-        *paused = true;
+    /// Pause the queue: stop starting new jobs and suspend all running encodes
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub async fn pause(&self) -> Vec<(String, JobStatus)> {
+        {
+            let mut paused = self.paused.lock().await;
+            *paused = true;
+        }
+
+        let ids: Vec<String> = self.processes.lock().await.keys().cloned().collect();
+        let mut changed = Vec::new();
+        for id in ids {
+            match self.pause_job(&id).await {
+                Ok(Some(status)) => changed.push((id, status)),
+                Ok(None) => {}
+                Err(e) => eprintln!("Failed to pause job {}: {}", id, e),
+            }
+        }
+        changed
+    }
+
+    /// Resume the queue and every suspended encode
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub async fn resume(&self) -> Vec<(String, JobStatus)> {
+        {
+            let mut paused = self.paused.lock().await;
+            *paused = false;
+        }
+
+        let ids: Vec<String> = self.processes.lock().await.keys().cloned().collect();
+        let mut changed = Vec::new();
+        for id in ids {
+            match self.resume_job(&id).await {
+                Ok(Some(status)) => changed.push((id, status)),
+                Ok(None) => {}
+                Err(e) => eprintln!("Failed to resume job {}: {}", id, e),
+            }
+        }
+        changed
+    }
+
+    /// Suspend a single running encode, keeping its progress
+    ///
+    /// Returns the new status, or `None` if the job is not currently processing.
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub async fn pause_job(&self, id: &str) -> Result<Option<JobStatus>> {
+        let control = match self.processes.lock().await.get(id) {
+            Some(control) => control.clone(),
+            None => return Ok(None),
+        };
+
+        let progress = match self.get_job(id).await.map(|j| j.status) {
+            Some(JobStatus::Processing { progress }) => progress,
+            _ => return Ok(None),
+        };
+
+        control.suspend()?;
+        let status = JobStatus::Paused { progress };
+        self.update_job_status(id, status.clone()).await;
+        Ok(Some(status))
+    }
+
+    /// Resume a single suspended encode from where it stopped
+    ///
+    /// Returns the new status, or `None` if the job is not paused.
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub async fn resume_job(&self, id: &str) -> Result<Option<JobStatus>> {
+        let control = match self.processes.lock().await.get(id) {
+            Some(control) => control.clone(),
+            None => return Ok(None),
+        };
+
+        let progress = match self.get_job(id).await.map(|j| j.status) {
+            Some(JobStatus::Paused { progress }) => progress,
+            _ => return Ok(None),
+        };
+
+        control.resume()?;
+        let status = JobStatus::Processing { progress };
+        self.update_job_status(id, status.clone()).await;
+        Ok(Some(status))
     }
 
-    /// Resume the queue
-    pub async fn resume(&self) {
-        let mut paused = self.paused.lock().await;
-        *paused = false;
+    /// Check if a job has an encode running in this process
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub async fn is_running(&self, id: &str) -> bool {
+        self.processes.lock().await.contains_key(id)
     }
 
     /// Check if queue is paused
@@ -139,18 +395,8 @@ impl JobQueue {
         *paused
     }
 
-    /// Process a single job
-    pub async fn process_job<F, S>(
-        &self,
-        app: tauri::AppHandle,
-        job_id: String,
-        progress_callback: F,
-        status_callback: S,
-    ) -> Result<()>
-    where
-        F: Fn(String, EncodingProgress) + Send + Clone + 'static,
-        S: Fn(String, JobStatus) + Send + Clone + 'static,
-    {
+    /// Process a single job, publishing its progress and status changes to `events`
+    pub async fn process_job(&self, events: SharedEventSink, job_id: String) -> Result<()> {
         // Acquire semaphore permit
         let _permit = {
             let semaphore = self.semaphore.lock().await.clone();
@@ -178,64 +424,136 @@ impl JobQueue {
             return Ok(());
         }
 
+        // Register process control so the job can be cancelled while encoding
+        let control = ProcessControl::new();
+        self.processes
+            .lock()
+            .await
+            .insert(job_id.clone(), control.clone());
+
+        // The job may have been cancelled before its control was registered
+        if let Some(JobStatus::Cancelled) = self.get_job(&job_id).await.map(|j| j.status) {
+            control.cancel();
+        }
+
         // Update status to processing
         let processing_status = JobStatus::Processing { progress: 0.0 };
         self.update_job_status(&job_id, processing_status.clone())
             .await;
-        status_callback(job_id.clone(), processing_status);
+        events.emit(Event::StatusChange {
+            job_id: job_id.clone(),
+            status: processing_status,
+        });
 
         // Create progress callback
         let job_id_clone = job_id.clone();
         let queue = self.clone();
+        let progress_events = events.clone();
         let callback = move |progress: EncodingProgress| {
             let job_id = job_id_clone.clone();
             let queue = queue.clone();
-            let progress_cb = progress_callback.clone();
+            let percentage = progress.percentage;
 
+            progress_events.emit(Event::Progress {
+                job_id: job_id.clone(),
+                progress,
+            });
             tokio::spawn(async move {
-                queue
-                    .update_job_status(
-                        &job_id,
-                        JobStatus::Processing {
-                            progress: progress.percentage,
-                        },
-                    )
-                    .await;
-                progress_cb(job_id, progress);
+                queue.update_job_progress(&job_id, percentage).await;
             });
         };
 
+        // Compliant inputs may only need to be copied to the output directory
+        let settings = job.encode_settings().clone();
+        let copy_only =
+            job.already_compliant && settings.compliance_policy == CompliancePolicy::Copy;
+
         // Encode video
-        let mut result = encode_video(
-            app.clone(),
-            job.input_path.clone(),
-            job.output_path.clone(),
-            job.settings.clone(),
-            job.video_info.duration,
-            callback.clone(),
-        )
-        .await;
+        let mut result = if copy_only {
+            tokio::fs::copy(&job.input_path, &job.output_path)
+                .await
+                .map(|_| ())
+                .context("Failed to copy input file")
+        } else {
+            self.encode_job(
+                events.clone(),
+                &job,
+                settings.clone(),
+                control.clone(),
+                callback.clone(),
+            )
+            .await
+        };
 
         // Fallback to software encoding if hardware encoding fails
-        if result.is_err() && job.settings.use_hardware {
+        if result.is_err() && settings.use_hardware && !copy_only && !control.is_cancelled() {
             let err = result.as_ref().unwrap_err();
             eprintln!(
                 "Hardware encoding failed ({}). Retrying with software encoder...",
                 err
             );
 
-            let mut software_settings = job.settings.clone();
+            let mut software_settings = settings.clone();
             software_settings.use_hardware = false;
 
-            result = encode_video(
-                app,
-                job.input_path.clone(),
-                job.output_path.clone(),
-                software_settings,
-                job.video_info.duration,
-                callback,
+            result = self
+                .encode_job(
+                    events.clone(),
+                    &job,
+                    software_settings,
+                    control.clone(),
+                    callback,
+                )
+                .await;
+        }
+
+        // Compare the output against the input before reporting the job as completed;
+        // filters and tone mapping change the picture on purpose, so the input is no
+        // reference then
+        let check = &settings.quality_check;
+        let comparable = !copy_only
+            && settings.filters.is_empty()
+            && hdr_handling(&settings, &job.video_info) != HdrHandling::ToneMap;
+        if result.is_ok() && check.enabled && comparable && !control.is_cancelled() {
+            result = match measure_quality(
+                &self.runner_for(&settings),
+                &job.input_path,
+                &job.output_path,
+                &geometry_filters(&settings, &job.video_info),
+                check,
+                &control,
+                &events,
             )
-            .await;
+            .await
+            {
+                Ok(metrics) => {
+                    let warning = metrics.below_threshold(check);
+                    self.set_job_quality(&job_id, metrics, warning.clone())
+                        .await;
+                    match warning {
+                        Some(warning) if check.on_failure == QualityFailureAction::Fail => {
+                            Err(anyhow::anyhow!(warning))
+                        }
+                        _ => Ok(()),
+                    }
+                }
+                Err(e) => {
+                    // A broken comparison should not throw away a finished encode
+                    eprintln!("Quality check failed for job {}: {}", job_id, e);
+                    Ok(())
+                }
+            };
+        }
+
+        self.processes.lock().await.remove(&job_id);
+
+        // Cancelled jobs keep their status; the encoder already removed the partial output
+        if control.is_cancelled() {
+            events.emit(Event::StatusChange {
+                job_id,
+                status: JobStatus::Cancelled,
+            });
+            return Ok(());
         }
 
         // Update final status
@@ -245,30 +563,64 @@ impl JobQueue {
                     output_path: job.output_path,
                 };
                 self.update_job_status(&job_id, status.clone()).await;
-                status_callback(job_id, status);
+                events.emit(Event::StatusChange { job_id, status });
             }
             Err(e) => {
                 let status = JobStatus::Failed {
                     error: e.to_string(),
                 };
                 self.update_job_status(&job_id, status.clone()).await;
-                status_callback(job_id, status);
+                events.emit(Event::StatusChange { job_id, status });
             }
         }
 
         Ok(())
     }
 
-    /// Process all pending jobs
-    pub async fn process_all<F, S>(
+    /// Encode a job's input, in parallel segments when chunking applies
+    async fn encode_job<F>(
         &self,
-        app: tauri::AppHandle,
+        events: SharedEventSink,
+        job: &Job,
+        settings: EncodingSettings,
+        control: ProcessControl,
         progress_callback: F,
-        status_callback: S,
-    ) where
-        F: Fn(String, EncodingProgress) + Send + Clone + 'static,
-        S: Fn(String, JobStatus) + Send + Clone + 'static,
+    ) -> Result<()>
+    where
+        F: Fn(EncodingProgress) + Send + Clone + 'static,
     {
+        let runner = self.runner_for(&settings);
+        if should_chunk(&settings, &job.video_info) {
+            let workers = self.semaphore.lock().await.clone();
+            encode_chunked(
+                events,
+                runner,
+                job.input_path.clone(),
+                job.output_path.clone(),
+                settings,
+                &job.video_info,
+                control,
+                workers,
+                progress_callback,
+            )
+            .await
+        } else {
+            encode_video(
+                events,
+                runner,
+                job.input_path.clone(),
+                job.output_path.clone(),
+                settings,
+                &job.video_info,
+                control,
+                progress_callback,
+            )
+            .await
+        }
+    }
+
+    /// Process all pending jobs, then publish `QueueFinished`
+    pub async fn process_all(&self, events: SharedEventSink) {
         let job_ids: Vec<String> = {
             let jobs = self.jobs.lock().await;
             jobs.iter()
@@ -281,14 +633,10 @@ impl JobQueue {
 
         for job_id in job_ids {
             let queue = self.clone();
-            let callback = progress_callback.clone();
-            let status_cb = status_callback.clone();
-            let app_handle = app.clone();
+            let events = events.clone();
 
             let handle = tokio::spawn(async move {
-                let _ = queue
-                    .process_job(app_handle, job_id, callback, status_cb)
-                    .await;
+                let _ = queue.process_job(events, job_id).await;
             });
 
             handles.push(handle);
@@ -298,14 +646,21 @@ impl JobQueue {
         for handle in handles {
             let _ = handle.await;
         }
+
+        events.emit(Event::QueueFinished);
     }
 
-    /// Cancel a job
+    /// Cancel a job, killing its ffmpeg process if it is currently encoding
     pub async fn cancel_job(&self, id: &str) {
         self.update_job_status(id, JobStatus::Cancelled).await;
+
+        if let Some(control) = self.processes.lock().await.get(id) {
+            control.cancel();
+        }
     }
 
     /// Get queue statistics
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub async fn get_stats(&self) -> QueueStats {
         let jobs = self.jobs.lock().await;
 
@@ -330,6 +685,14 @@ impl JobQueue {
             .iter()
             .filter(|j| matches!(j.status, JobStatus::Cancelled))
             .count();
+        let paused = jobs
+            .iter()
+            .filter(|j| matches!(j.status, JobStatus::Paused { .. }))
+            .count();
+        let skipped = jobs
+            .iter()
+            .filter(|j| matches!(j.status, JobStatus::Skipped { .. }))
+            .count();
 
         QueueStats {
             total,
@@ -338,6 +701,8 @@ impl JobQueue {
             completed,
             failed,
             cancelled,
+            paused,
+            skipped,
         }
     }
 }
@@ -348,11 +713,14 @@ impl Clone for JobQueue {
             jobs: Arc::clone(&self.jobs),
             semaphore: Arc::clone(&self.semaphore),
             paused: Arc::clone(&self.paused),
+            processes: Arc::clone(&self.processes),
+            runner: Arc::clone(&self.runner),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 pub struct QueueStats {
     pub total: usize,
     pub pending: usize,
@@ -360,6 +728,8 @@ pub struct QueueStats {
     pub completed: usize,
     pub failed: usize,
     pub cancelled: usize,
+    pub paused: usize,
+    pub skipped: usize,
 }
 
 /// Calculate optimal concurrent jobs based on CPU cores
@@ -367,3 +737,235 @@ pub fn calculate_max_concurrent() -> usize {
     let cores = num_cpus::get();
     std::cmp::max(1, cores / 4)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunked::ChunkSettings;
+    use crate::events::RecordingEventSink;
+    use crate::runner::{progress_output, FakeProcess, FakeProcessRunner, Tool};
+
+    fn test_job(use_hardware: bool) -> Job {
+        let video_info = VideoInfo {
+            path: PathBuf::from("/tmp/rvc-test-in.mkv"),
+            ..VideoInfo::test_default()
+        };
+        let settings = EncodingSettings {
+            use_hardware,
+            ..Default::default()
+        };
+        let output = std::env::temp_dir().join(format!("rvc-test-{}.mp4", uuid::Uuid::new_v4()));
+        Job::new(video_info.path.clone(), output, video_info, settings)
+    }
+
+    async fn queue_with(
+        runner: FakeProcessRunner,
+        job: &Job,
+    ) -> (JobQueue, Arc<FakeProcessRunner>) {
+        let runner = Arc::new(runner);
+        let queue = JobQueue::with_runner(1, runner.clone());
+        queue.add_job(job.clone()).await;
+        (queue, runner)
+    }
+
+    /// Encoder passed to each ffmpeg encode, in call order
+    fn encoders_used(runner: &FakeProcessRunner) -> Vec<String> {
+        runner
+            .calls()
+            .into_iter()
+            .filter_map(|(_, args)| {
+                let index = args.iter().position(|arg| arg == "-c:v")?;
+                args.get(index + 1).cloned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unsupported_chunking_is_reported() {
+        let job = test_job(false);
+        let settings = EncodingSettings {
+            bitrate: Some(2_000_000),
+            two_pass: true,
+            chunked: Some(ChunkSettings {
+                min_input_duration: 30.0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let job = Job::new(job.input_path, job.output_path, job.video_info, settings);
+        assert_eq!(
+            job.adjustments,
+            vec!["Encoded in one piece: segments are encoded in a single pass"]
+        );
+        assert!(!should_chunk(job.encode_settings(), &job.video_info));
+    }
+
+    #[tokio::test]
+    async fn test_process_job_reports_progress_in_order() {
+        let job = test_job(false);
+        let script = FakeProcess::success(progress_output(&[15.0, 30.0, 60.0]));
+        let (queue, _) = queue_with(
+            FakeProcessRunner::new().on(Tool::Ffmpeg, "rvc-test-in", script),
+            &job,
+        )
+        .await;
+        let sink = Arc::new(RecordingEventSink::new());
+
+        queue
+            .process_job(sink.clone(), job.id.clone())
+            .await
+            .unwrap();
+
+        let events = sink.events();
+        assert!(matches!(
+            events.first(),
+            Some(Event::StatusChange {
+                status: JobStatus::Processing { .. },
+                ..
+            })
+        ));
+        assert!(matches!(
+            events.last(),
+            Some(Event::StatusChange {
+                status: JobStatus::Completed { .. },
+                ..
+            })
+        ));
+
+        let percentages: Vec<f32> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Progress { progress, .. } => Some(progress.percentage),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(percentages, vec![25.0, 50.0, 100.0]);
+
+        let status = queue.get_job(&job.id).await.unwrap().status;
+        assert_eq!(
+            status,
+            JobStatus::Completed {
+                output_path: job.output_path.clone()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_job_falls_back_to_software() {
+        let job = test_job(true);
+        let runner = FakeProcessRunner::new()
+            .on(
+                Tool::Ffmpeg,
+                "-encoders",
+                FakeProcess::success(" V....D h264_nvenc           NVIDIA NVENC H.264 encoder"),
+            )
+            .on(
+                Tool::Ffmpeg,
+                "h264_nvenc",
+                FakeProcess::failure(1, "Cannot load libcuda.so.1"),
+            );
+        let (queue, runner) = queue_with(runner, &job).await;
+        let sink = Arc::new(RecordingEventSink::new());
+
+        queue
+            .process_job(sink.clone(), job.id.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(encoders_used(&runner), vec!["h264_nvenc", "libx264"]);
+        assert!(matches!(
+            sink.statuses(&job.id).last(),
+            Some(JobStatus::Completed { .. })
+        ));
+        assert!(sink.log_lines().iter().any(|line| line.contains("libcuda")));
+    }
+
+    #[tokio::test]
+    async fn test_process_job_reports_ffmpeg_error() {
+        let job = test_job(false);
+        let stderr = "Input #0, matroska,webm, from 'in.mkv':\n\
+                      Error while decoding stream #0:0: Invalid data found when processing input";
+        let (queue, _) = queue_with(
+            FakeProcessRunner::new().on(
+                Tool::Ffmpeg,
+                "rvc-test-in",
+                FakeProcess::failure(1, stderr),
+            ),
+            &job,
+        )
+        .await;
+        let sink = Arc::new(RecordingEventSink::new());
+
+        queue
+            .process_job(sink.clone(), job.id.clone())
+            .await
+            .unwrap();
+
+        let Some(JobStatus::Failed { error }) = queue.get_job(&job.id).await.map(|j| j.status)
+        else {
+            panic!("job should have failed");
+        };
+        assert!(error.contains("exit status: 1"));
+        assert!(error.contains("Invalid data found when processing input"));
+        assert_eq!(
+            sink.statuses(&job.id).last(),
+            Some(&JobStatus::Failed { error })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_job() {
+        let job = test_job(false);
+        let script = FakeProcess::hanging(progress_output(&[6.0]));
+        let (queue, _) = queue_with(
+            FakeProcessRunner::new().on(Tool::Ffmpeg, "rvc-test-in", script),
+            &job,
+        )
+        .await;
+        let sink = Arc::new(RecordingEventSink::new());
+
+        let worker = {
+            let queue = queue.clone();
+            let sink = sink.clone();
+            let id = job.id.clone();
+            tokio::spawn(async move { queue.process_job(sink, id).await })
+        };
+
+        // Wait until the fake ffmpeg has reported progress, then cancel it
+        while !matches!(
+            queue.get_job(&job.id).await.unwrap().status,
+            JobStatus::Processing { progress } if progress >= 10.0
+        ) {
+            tokio::time::sleep(tokio::time::Duration::from_millis(5)).await;
+        }
+        queue.cancel_job(&job.id).await;
+
+        tokio::time::timeout(tokio::time::Duration::from_secs(5), worker)
+            .await
+            .expect("cancelled job should stop")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            queue.get_job(&job.id).await.unwrap().status,
+            JobStatus::Cancelled
+        );
+        assert_eq!(sink.statuses(&job.id).last(), Some(&JobStatus::Cancelled));
+        assert!(!job.output_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_process_all_finishes_queue() {
+        let job = test_job(false);
+        let (queue, _) = queue_with(FakeProcessRunner::new(), &job).await;
+        let sink = Arc::new(RecordingEventSink::new());
+
+        queue.process_all(sink.clone()).await;
+
+        assert!(matches!(sink.events().last(), Some(Event::QueueFinished)));
+        assert!(matches!(
+            sink.statuses(&job.id).last(),
+            Some(JobStatus::Completed { .. })
+        ));
+    }
+}
@@ -0,0 +1,378 @@
+use crate::encoder::EncodingSettings;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Encoder options beyond quality and speed; unset fields keep the encoder defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct VideoTuning {
+    /// Content tuning, e.g. `film` or `animation` for x264, `hq` for NVENC
+    #[serde(default)]
+    pub tune: Option<String>,
+    /// Codec profile, e.g. `high` or `main10`
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Codec level, e.g. `4.1`
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Maximum distance between keyframes in frames
+    #[serde(default)]
+    pub gop_size: Option<u32>,
+    /// Maximum number of consecutive B-frames
+    #[serde(default)]
+    pub b_frames: Option<u32>,
+    /// Peak video bitrate in bits per second (VBV)
+    #[serde(default)]
+    pub max_bitrate: Option<u64>,
+    /// VBV buffer size in bits; twice `max_bitrate` if not set
+    #[serde(default)]
+    pub buffer_size: Option<u64>,
+}
+
+/// Encoders that share the same option names
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EncoderFamily {
+    X264,
+    X265,
+    Vpx,
+    Aom,
+    SvtAv1,
+    Nvenc,
+    Qsv,
+    Vaapi,
+    /// Anything else gets the x264-style options ffmpeg maps generically
+    Other,
+}
+
+impl EncoderFamily {
+    fn of(encoder: &str) -> Self {
+        match encoder {
+            "libx264" => Self::X264,
+            "libx265" => Self::X265,
+            "libvpx" | "libvpx-vp9" => Self::Vpx,
+            "libaom-av1" => Self::Aom,
+            "libsvtav1" => Self::SvtAv1,
+            _ if encoder.ends_with("_nvenc") => Self::Nvenc,
+            _ if encoder.ends_with("_qsv") => Self::Qsv,
+            _ if encoder.ends_with("_vaapi") => Self::Vaapi,
+            _ => Self::Other,
+        }
+    }
+
+    fn has_b_frames(self) -> bool {
+        !matches!(self, Self::Vpx | Self::Aom | Self::SvtAv1)
+    }
+}
+
+/// x264-style preset names, fastest first
+const PRESETS: [&str; 9] = [
+    "ultrafast",
+    "superfast",
+    "veryfast",
+    "faster",
+    "fast",
+    "medium",
+    "slow",
+    "slower",
+    "veryslow",
+];
+
+/// Position of a preset on the speed scale; unknown names count as `medium`
+fn preset_index(preset: &str) -> usize {
+    PRESETS.iter().position(|p| *p == preset).unwrap_or(5)
+}
+
+/// Speed arguments for an encoder, translated from the x264-style preset
+fn speed_args(family: EncoderFamily, preset: &str) -> Vec<String> {
+    let index = preset_index(preset);
+    match family {
+        EncoderFamily::X264 | EncoderFamily::X265 | EncoderFamily::Other => {
+            vec!["-preset".into(), preset.into()]
+        }
+        EncoderFamily::Vpx => {
+            const CPU_USED: [u8; 9] = [8, 7, 6, 5, 4, 3, 2, 1, 0];
+            // Realtime is much faster but only worth it at the fastest settings
+            let deadline = if index < 2 { "realtime" } else { "good" };
+            vec![
+                "-deadline".into(),
+                deadline.into(),
+                "-cpu-used".into(),
+                CPU_USED[index].to_string(),
+                "-row-mt".into(),
+                "1".into(),
+            ]
+        }
+        EncoderFamily::Aom => {
+            const CPU_USED: [u8; 9] = [8, 8, 7, 6, 5, 4, 3, 2, 1];
+            vec![
+                "-cpu-used".into(),
+                CPU_USED[index].to_string(),
+                "-row-mt".into(),
+                "1".into(),
+            ]
+        }
+        EncoderFamily::SvtAv1 => {
+            const PRESET: [u8; 9] = [12, 11, 10, 9, 8, 6, 5, 4, 2];
+            vec!["-preset".into(), PRESET[index].to_string()]
+        }
+        EncoderFamily::Nvenc => {
+            const PRESET: [u8; 9] = [1, 2, 2, 3, 3, 4, 5, 6, 7];
+            vec!["-preset".into(), format!("p{}", PRESET[index])]
+        }
+        EncoderFamily::Qsv => {
+            // QSV has no presets faster than veryfast
+            vec!["-preset".into(), PRESETS[index.max(2)].into()]
+        }
+        // VAAPI has no speed presets
+        EncoderFamily::Vaapi => Vec::new(),
+    }
+}
+
+/// Bitrate or constant-quality arguments for an encoder
+fn quality_args(family: EncoderFamily, settings: &EncodingSettings) -> Vec<String> {
+    if let Some(bitrate) = settings.bitrate {
+        let mut args = Vec::new();
+        if family == EncoderFamily::Nvenc {
+            args.extend(["-rc".into(), "vbr".into()]);
+        }
+        args.extend(["-b:v".into(), format!("{}k", bitrate / 1000)]);
+        return args;
+    }
+
+    let Some(crf) = settings.crf else {
+        return Vec::new();
+    };
+    let crf = crf.to_string();
+    match family {
+        EncoderFamily::X264
+        | EncoderFamily::X265
+        | EncoderFamily::SvtAv1
+        | EncoderFamily::Other => vec!["-crf".into(), crf],
+        // Without `-b:v 0` libvpx and libaom treat the CRF as a quality floor for a bitrate
+        EncoderFamily::Vpx | EncoderFamily::Aom => {
+            vec!["-crf".into(), crf, "-b:v".into(), "0".into()]
+        }
+        EncoderFamily::Nvenc => vec![
+            "-rc".into(),
+            "vbr".into(),
+            "-cq".into(),
+            crf,
+            "-b:v".into(),
+            "0".into(),
+        ],
+        EncoderFamily::Qsv => vec!["-global_quality".into(), crf],
+        EncoderFamily::Vaapi => vec!["-qp".into(), crf],
+    }
+}
+
+/// libvpx only takes the numeric profiles 0 to 3; `profile2` is read as `2`
+fn vpx_profile(profile: &str) -> Option<&str> {
+    let number = profile.strip_prefix("profile").unwrap_or(profile);
+    matches!(number, "0" | "1" | "2" | "3").then_some(number)
+}
+
+/// Escape a value for a `key=value:key=value` option string, so paths such as
+/// `C:\Temp` stay one value
+fn escape_param(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ':' | '\'') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Video encoder arguments for quality, speed, tuning and two-pass statistics
+///
+/// `x265_params` are options other modules need passed to libx265, such as HDR
+/// metadata. `pass` is the pass number and statistics file prefix of a two-pass encode.
+pub fn video_codec_args(
+    settings: &EncodingSettings,
+    encoder: &str,
+    mut x265_params: Vec<String>,
+    pass: Option<(u8, &Path)>,
+) -> Vec<String> {
+    let family = EncoderFamily::of(encoder);
+    let tuning = &settings.tuning;
+    let mut args = quality_args(family, settings);
+
+    if let Some(max_bitrate) = tuning.max_bitrate {
+        let buffer_size = tuning.buffer_size.unwrap_or(max_bitrate * 2);
+        args.push("-maxrate".to_string());
+        args.push(format!("{}k", max_bitrate / 1000));
+        args.push("-bufsize".to_string());
+        args.push(format!("{}k", buffer_size / 1000));
+    }
+
+    args.extend(speed_args(family, &settings.preset));
+
+    // libx265 takes its level and pass options through one parameter string
+    if let Some(tune) = &tuning.tune {
+        if !matches!(family, EncoderFamily::Vpx | EncoderFamily::SvtAv1) {
+            args.push("-tune".to_string());
+            args.push(tune.clone());
+        }
+    }
+    if let Some(profile) = &tuning.profile {
+        let profile = match family {
+            EncoderFamily::Aom | EncoderFamily::SvtAv1 => None,
+            EncoderFamily::Vpx => vpx_profile(profile),
+            _ => Some(profile.as_str()),
+        };
+        if let Some(profile) = profile {
+            args.push("-profile:v".to_string());
+            args.push(profile.to_string());
+        }
+    }
+    if let Some(level) = &tuning.level {
+        match family {
+            EncoderFamily::X265 => x265_params.push(format!("level-idc={}", level)),
+            EncoderFamily::Vpx | EncoderFamily::Aom | EncoderFamily::SvtAv1 => {}
+            _ => {
+                args.push("-level".to_string());
+                args.push(level.clone());
+            }
+        }
+    }
+    if let Some(gop_size) = tuning.gop_size {
+        args.push("-g".to_string());
+        args.push(gop_size.to_string());
+    }
+    if let Some(b_frames) = tuning.b_frames {
+        if family.has_b_frames() {
+            args.push("-bf".to_string());
+            args.push(b_frames.to_string());
+        }
+    }
+
+    if let Some((pass, passlog)) = pass {
+        if family == EncoderFamily::X265 {
+            let stats = format!("{}.log", passlog.display());
+            x265_params.push(format!("pass={}:stats={}", pass, escape_param(&stats)));
+        } else {
+            args.push("-pass".to_string());
+            args.push(pass.to_string());
+            args.push("-passlogfile".to_string());
+            args.push(passlog.to_string_lossy().to_string());
+        }
+    }
+
+    if !x265_params.is_empty() {
+        args.push("-x265-params".to_string());
+        args.push(x265_params.join(":"));
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args_for(encoder: &str, settings: &EncodingSettings) -> Vec<String> {
+        video_codec_args(settings, encoder, Vec::new(), None)
+    }
+
+    fn has(args: &[String], flag: &str, value: &str) -> bool {
+        args.windows(2).any(|w| w[0] == flag && w[1] == value)
+    }
+
+    #[test]
+    fn test_crf_per_encoder() {
+        let settings = EncodingSettings {
+            crf: Some(30),
+            preset: "slow".to_string(),
+            ..Default::default()
+        };
+
+        let x264 = args_for("libx264", &settings);
+        assert!(has(&x264, "-crf", "30") && has(&x264, "-preset", "slow"));
+
+        let vp9 = args_for("libvpx-vp9", &settings);
+        assert!(has(&vp9, "-crf", "30") && has(&vp9, "-b:v", "0"));
+        assert!(has(&vp9, "-cpu-used", "2") && has(&vp9, "-deadline", "good"));
+        assert!(!vp9.contains(&"-preset".to_string()));
+
+        let svt = args_for("libsvtav1", &settings);
+        assert!(has(&svt, "-preset", "5"));
+
+        let nvenc = args_for("hevc_nvenc", &settings);
+        assert!(has(&nvenc, "-cq", "30") && has(&nvenc, "-rc", "vbr"));
+        assert!(has(&nvenc, "-preset", "p5"));
+        assert!(!nvenc.contains(&"-crf".to_string()));
+
+        let vaapi = args_for("h264_vaapi", &settings);
+        assert_eq!(vaapi, vec!["-qp", "30"]);
+    }
+
+    #[test]
+    fn test_tuning_and_vbv() {
+        let settings = EncodingSettings {
+            bitrate: Some(4_000_000),
+            tuning: VideoTuning {
+                tune: Some("film".to_string()),
+                profile: Some("main".to_string()),
+                level: Some("4.1".to_string()),
+                gop_size: Some(48),
+                b_frames: Some(3),
+                max_bitrate: Some(6_000_000),
+                buffer_size: None,
+            },
+            ..Default::default()
+        };
+
+        let x264 = args_for("libx264", &settings);
+        assert!(has(&x264, "-b:v", "4000k"));
+        assert!(has(&x264, "-maxrate", "6000k") && has(&x264, "-bufsize", "12000k"));
+        assert!(has(&x264, "-tune", "film") && has(&x264, "-level", "4.1"));
+        assert!(has(&x264, "-g", "48") && has(&x264, "-bf", "3"));
+
+        let passlog = Path::new("/tmp/rvc-passlog");
+        let x265 = video_codec_args(
+            &settings,
+            "libx265",
+            vec!["hdr10=1".to_string()],
+            Some((2, passlog)),
+        );
+        assert!(has(
+            &x265,
+            "-x265-params",
+            "hdr10=1:level-idc=4.1:pass=2:stats=/tmp/rvc-passlog.log"
+        ));
+        assert!(!x265.contains(&"-level".to_string()));
+
+        let vp9 = args_for("libvpx-vp9", &settings);
+        assert!(!vp9.contains(&"-bf".to_string()) && !vp9.contains(&"-tune".to_string()));
+        assert!(!vp9.contains(&"-profile:v".to_string()));
+    }
+
+    #[test]
+    fn test_vpx_profile() {
+        let settings = EncodingSettings {
+            tuning: VideoTuning {
+                profile: Some("profile2".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(has(&args_for("libvpx-vp9", &settings), "-profile:v", "2"));
+        assert_eq!(vpx_profile("1"), Some("1"));
+        assert_eq!(vpx_profile("high"), None);
+    }
+
+    #[test]
+    fn test_x265_stats_path_is_escaped() {
+        let settings = EncodingSettings {
+            bitrate: Some(4_000_000),
+            ..Default::default()
+        };
+        let passlog = Path::new(r"C:\Temp\rvc-passlog");
+        let x265 = video_codec_args(&settings, "libx265", Vec::new(), Some((1, passlog)));
+        assert!(has(
+            &x265,
+            "-x265-params",
+            r"pass=1:stats=C\:\\Temp\\rvc-passlog.log"
+        ));
+    }
+}
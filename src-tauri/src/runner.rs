@@ -0,0 +1,480 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+pub type BoxReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// External programs the app runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Ffmpeg,
+    Ffprobe,
+}
+
+impl Tool {
+    pub fn program(&self) -> &'static str {
+        match self {
+            Tool::Ffmpeg => "ffmpeg",
+            Tool::Ffprobe => "ffprobe",
+        }
+    }
+}
+
+/// Locations of the ffmpeg and ffprobe binaries; unset tools are looked up on the `PATH`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ToolPaths {
+    #[serde(default)]
+    pub ffmpeg: Option<PathBuf>,
+    #[serde(default)]
+    pub ffprobe: Option<PathBuf>,
+}
+
+impl ToolPaths {
+    fn get(&self, tool: Tool) -> Option<&PathBuf> {
+        match tool {
+            Tool::Ffmpeg => self.ffmpeg.as_ref(),
+            Tool::Ffprobe => self.ffprobe.as_ref(),
+        }
+        .filter(|path| !path.as_os_str().is_empty())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.get(Tool::Ffmpeg).is_none() && self.get(Tool::Ffprobe).is_none()
+    }
+
+    /// Paths set here, falling back to `base` for the others
+    pub fn or(&self, base: &ToolPaths) -> ToolPaths {
+        ToolPaths {
+            ffmpeg: self.get(Tool::Ffmpeg).or(base.get(Tool::Ffmpeg)).cloned(),
+            ffprobe: self.get(Tool::Ffprobe).or(base.get(Tool::Ffprobe)).cloned(),
+        }
+    }
+
+    /// Program to execute for `tool`
+    pub fn program(&self, tool: Tool) -> PathBuf {
+        self.get(tool)
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from(tool.program()))
+    }
+}
+
+/// How a process ended; `code` is `None` if it was killed by a signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus {
+    pub code: Option<i32>,
+}
+
+impl ExitStatus {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl From<std::process::ExitStatus> for ExitStatus {
+    fn from(status: std::process::ExitStatus) -> Self {
+        Self {
+            code: status.code(),
+        }
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "exit status: {}", code),
+            None => write!(f, "terminated by signal"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// A spawned process with piped stdout and stderr
+pub trait RunningProcess: Send {
+    fn id(&self) -> Option<u32>;
+    fn take_stdout(&mut self) -> Option<BoxReader>;
+    fn take_stderr(&mut self) -> Option<BoxReader>;
+    fn wait(&mut self) -> BoxFuture<'_, Result<ExitStatus>>;
+    fn kill(&mut self) -> BoxFuture<'_, Result<()>>;
+}
+
+/// Starts the ffmpeg and ffprobe processes used by the encoder, prober and quality checks
+pub trait ProcessRunner: Send + Sync {
+    /// Spawn a process; it is killed if the handle is dropped while still running
+    fn spawn(&self, tool: Tool, args: &[String]) -> Result<Box<dyn RunningProcess>>;
+
+    /// Run a short-lived command to completion, blocking the current thread
+    fn output_blocking(&self, tool: Tool, args: &[String]) -> Result<ProcessOutput>;
+
+    /// Runner that uses `paths` for the tools they set, or `None` to keep this one
+    ///
+    /// Runners that do not start real binaries ignore path overrides.
+    fn with_paths(&self, _paths: &ToolPaths) -> Option<SharedProcessRunner> {
+        None
+    }
+}
+
+pub type SharedProcessRunner = Arc<dyn ProcessRunner>;
+
+/// Read all output of a process and wait for it to exit
+pub async fn wait_with_output(process: &mut dyn RunningProcess) -> Result<ProcessOutput> {
+    let stdout = process.take_stdout();
+    let stderr = process.take_stderr();
+
+    let (stdout, stderr) = tokio::join!(read_all(stdout), read_all(stderr));
+    let status = process.wait().await?;
+
+    Ok(ProcessOutput {
+        status,
+        stdout: stdout?,
+        stderr: stderr?,
+    })
+}
+
+async fn read_all(reader: Option<BoxReader>) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    if let Some(mut reader) = reader {
+        reader
+            .read_to_end(&mut buffer)
+            .await
+            .context("Failed to read process output")?;
+    }
+    Ok(buffer)
+}
+
+/// Runs the installed ffmpeg and ffprobe binaries
+#[derive(Debug, Clone, Default)]
+pub struct SystemProcessRunner {
+    paths: ToolPaths,
+}
+
+impl SystemProcessRunner {
+    pub fn new(paths: ToolPaths) -> Self {
+        Self { paths }
+    }
+
+    /// Runner using the binaries found on the `PATH`
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub fn shared() -> SharedProcessRunner {
+        Arc::new(Self::default())
+    }
+}
+
+struct SystemProcess {
+    child: tokio::process::Child,
+}
+
+impl RunningProcess for SystemProcess {
+    fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    fn take_stdout(&mut self) -> Option<BoxReader> {
+        self.child
+            .stdout
+            .take()
+            .map(|stdout| Box::new(stdout) as BoxReader)
+    }
+
+    fn take_stderr(&mut self) -> Option<BoxReader> {
+        self.child
+            .stderr
+            .take()
+            .map(|stderr| Box::new(stderr) as BoxReader)
+    }
+
+    fn wait(&mut self) -> BoxFuture<'_, Result<ExitStatus>> {
+        Box::pin(async move {
+            let status = self
+                .child
+                .wait()
+                .await
+                .context("Failed to wait for process")?;
+            Ok(status.into())
+        })
+    }
+
+    fn kill(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            self.child.kill().await.context("Failed to kill process")?;
+            Ok(())
+        })
+    }
+}
+
+impl ProcessRunner for SystemProcessRunner {
+    fn spawn(&self, tool: Tool, args: &[String]) -> Result<Box<dyn RunningProcess>> {
+        let child = tokio::process::Command::new(self.paths.program(tool))
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to spawn {:?}", self.paths.program(tool)))?;
+        Ok(Box::new(SystemProcess { child }))
+    }
+
+    fn output_blocking(&self, tool: Tool, args: &[String]) -> Result<ProcessOutput> {
+        let output = std::process::Command::new(self.paths.program(tool))
+            .args(args)
+            .output()
+            .with_context(|| format!("Failed to run {:?}", self.paths.program(tool)))?;
+        Ok(ProcessOutput {
+            status: output.status.into(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+
+    fn with_paths(&self, paths: &ToolPaths) -> Option<SharedProcessRunner> {
+        if paths.is_empty() {
+            return None;
+        }
+        Some(Arc::new(Self::new(paths.or(&self.paths))))
+    }
+}
+
+#[cfg(test)]
+pub use fake::{progress_output, FakeProcess, FakeProcessRunner};
+
+#[cfg(test)]
+mod fake {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Scripted result of one fake process
+    #[derive(Debug, Clone)]
+    pub struct FakeProcess {
+        pub stdout: String,
+        pub stderr: String,
+        pub exit_code: i32,
+        /// Keep running until killed
+        pub hang: bool,
+    }
+
+    impl FakeProcess {
+        pub fn success(stdout: impl Into<String>) -> Self {
+            Self {
+                stdout: stdout.into(),
+                stderr: String::new(),
+                exit_code: 0,
+                hang: false,
+            }
+        }
+
+        pub fn failure(exit_code: i32, stderr: impl Into<String>) -> Self {
+            Self {
+                stderr: stderr.into(),
+                exit_code,
+                ..Self::success("")
+            }
+        }
+
+        pub fn hanging(stdout: impl Into<String>) -> Self {
+            Self {
+                hang: true,
+                ..Self::success(stdout)
+            }
+        }
+    }
+
+    /// ffmpeg `-progress pipe:1` output reaching each of the given times in seconds
+    pub fn progress_output(times: &[f64]) -> String {
+        times
+            .iter()
+            .map(|time| {
+                format!(
+                    "fps=30.0\nbitrate=1500.0kbits/s\nout_time_ms={}\nspeed=2.0x\nprogress=continue\n",
+                    (time * 1_000_000.0) as u64
+                )
+            })
+            .collect()
+    }
+
+    struct FakeRule {
+        tool: Tool,
+        pattern: String,
+        process: FakeProcess,
+    }
+
+    /// Replays scripted output instead of running real programs
+    ///
+    /// The first rule whose pattern occurs in one of the arguments decides the
+    /// result; calls without a matching rule succeed with no output.
+    #[derive(Default)]
+    pub struct FakeProcessRunner {
+        rules: Vec<FakeRule>,
+        calls: Mutex<Vec<(Tool, Vec<String>)>>,
+    }
+
+    impl FakeProcessRunner {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn on(mut self, tool: Tool, pattern: &str, process: FakeProcess) -> Self {
+            self.rules.push(FakeRule {
+                tool,
+                pattern: pattern.to_string(),
+                process,
+            });
+            self
+        }
+
+        /// Every invocation so far, in order
+        pub fn calls(&self) -> Vec<(Tool, Vec<String>)> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn respond(&self, tool: Tool, args: &[String]) -> FakeProcess {
+            self.calls.lock().unwrap().push((tool, args.to_vec()));
+            self.rules
+                .iter()
+                .find(|rule| {
+                    rule.tool == tool && args.iter().any(|arg| arg.contains(&rule.pattern))
+                })
+                .map(|rule| rule.process.clone())
+                .unwrap_or_else(|| FakeProcess::success(""))
+        }
+    }
+
+    impl ProcessRunner for FakeProcessRunner {
+        fn spawn(&self, tool: Tool, args: &[String]) -> Result<Box<dyn RunningProcess>> {
+            Ok(Box::new(FakeRunningProcess {
+                process: self.respond(tool, args),
+                stdout_taken: false,
+                stderr_taken: false,
+                killed: false,
+            }))
+        }
+
+        fn output_blocking(&self, tool: Tool, args: &[String]) -> Result<ProcessOutput> {
+            let process = self.respond(tool, args);
+            Ok(ProcessOutput {
+                status: ExitStatus {
+                    code: Some(process.exit_code),
+                },
+                stdout: process.stdout.into_bytes(),
+                stderr: process.stderr.into_bytes(),
+            })
+        }
+    }
+
+    struct FakeRunningProcess {
+        process: FakeProcess,
+        stdout_taken: bool,
+        stderr_taken: bool,
+        killed: bool,
+    }
+
+    impl RunningProcess for FakeRunningProcess {
+        fn id(&self) -> Option<u32> {
+            None
+        }
+
+        fn take_stdout(&mut self) -> Option<BoxReader> {
+            if std::mem::replace(&mut self.stdout_taken, true) {
+                return None;
+            }
+            let stdout = self.process.stdout.clone().into_bytes();
+            Some(Box::new(std::io::Cursor::new(stdout)))
+        }
+
+        fn take_stderr(&mut self) -> Option<BoxReader> {
+            if std::mem::replace(&mut self.stderr_taken, true) {
+                return None;
+            }
+            let stderr = self.process.stderr.clone().into_bytes();
+            Some(Box::new(std::io::Cursor::new(stderr)))
+        }
+
+        fn wait(&mut self) -> BoxFuture<'_, Result<ExitStatus>> {
+            Box::pin(async move {
+                if self.killed {
+                    return Ok(ExitStatus { code: None });
+                }
+                if self.process.hang {
+                    std::future::pending::<()>().await;
+                }
+                Ok(ExitStatus {
+                    code: Some(self.process.exit_code),
+                })
+            })
+        }
+
+        fn kill(&mut self) -> BoxFuture<'_, Result<()>> {
+            self.killed = true;
+            Box::pin(async { Ok(()) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_paths_fallback() {
+        let app = ToolPaths {
+            ffmpeg: Some(PathBuf::from("/opt/ffmpeg/bin/ffmpeg")),
+            ffprobe: Some(PathBuf::from("/opt/ffmpeg/bin/ffprobe")),
+        };
+        let job = ToolPaths {
+            ffmpeg: Some(PathBuf::from("/usr/local/bin/ffmpeg")),
+            ffprobe: Some(PathBuf::new()),
+        };
+
+        let merged = job.or(&app);
+        assert_eq!(
+            merged.program(Tool::Ffmpeg),
+            PathBuf::from("/usr/local/bin/ffmpeg")
+        );
+        assert_eq!(
+            merged.program(Tool::Ffprobe),
+            PathBuf::from("/opt/ffmpeg/bin/ffprobe")
+        );
+        assert_eq!(
+            ToolPaths::default().program(Tool::Ffprobe),
+            PathBuf::from("ffprobe")
+        );
+        assert!(SystemProcessRunner::default()
+            .with_paths(&ToolPaths::default())
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fake_runner_replays_script() {
+        let runner = FakeProcessRunner::new()
+            .on(
+                Tool::Ffmpeg,
+                "bad.mkv",
+                FakeProcess::failure(1, "Invalid data"),
+            )
+            .on(Tool::Ffprobe, "", FakeProcess::success("{}"));
+
+        let mut process = runner
+            .spawn(Tool::Ffmpeg, &["-i".to_string(), "bad.mkv".to_string()])
+            .unwrap();
+        let output = wait_with_output(process.as_mut()).await.unwrap();
+        assert!(!output.status.success());
+        assert_eq!(output.stderr, b"Invalid data");
+
+        let output = runner
+            .output_blocking(Tool::Ffprobe, &["good.mkv".to_string()])
+            .unwrap();
+        assert_eq!(output.stdout, b"{}");
+        assert_eq!(runner.calls().len(), 2);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::probe::VideoInfo;
+use serde::{Deserialize, Serialize};
+
+/// How the source picture is brought to `EncodingSettings.resolution`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum ScaleMode {
+    /// Keep the aspect ratio and fit inside the box; the box is turned to match
+    /// portrait sources, so 1920x1080 limits a phone video to 1080x1920
+    #[default]
+    Fit,
+    /// Keep the aspect ratio, cover the box exactly and crop the overflow
+    Fill,
+    /// Keep the aspect ratio and letterbox or pillarbox to exactly the box
+    Pad,
+    /// Limit only the height; the width follows the aspect ratio
+    MaxHeight,
+    /// Scale to exactly the box, distorting the picture if the aspect differs
+    Stretch,
+}
+
+/// Round to the nearest even number, as 4:2:0 chroma needs
+fn even(value: f64) -> u32 {
+    ((value / 2.0).round() as u32 * 2).max(2)
+}
+
+/// Round up to an even number so a cropped picture still covers the box
+fn even_ceil(value: f64) -> u32 {
+    ((value / 2.0).ceil() as u32 * 2).max(2)
+}
+
+fn is_portrait((width, height): (u32, u32)) -> bool {
+    height > width
+}
+
+/// The target box, turned to the source's orientation for `Fit`
+fn target_box(display: (u32, u32), target: (u32, u32), mode: ScaleMode) -> (u32, u32) {
+    let square = target.0 == target.1 || display.0 == display.1;
+    if mode == ScaleMode::Fit && !square && is_portrait(display) != is_portrait(target) {
+        (target.1, target.0)
+    } else {
+        target
+    }
+}
+
+/// Factor the picture is enlarged by; above 1 means upscaling
+pub fn scale_factor(display: (u32, u32), target: (u32, u32), mode: ScaleMode) -> f64 {
+    let (box_width, box_height) = target_box(display, target, mode);
+    let horizontal = box_width as f64 / display.0.max(1) as f64;
+    let vertical = box_height as f64 / display.1.max(1) as f64;
+    match mode {
+        ScaleMode::Fit | ScaleMode::Pad => horizontal.min(vertical),
+        ScaleMode::Fill | ScaleMode::Stretch => horizontal.max(vertical),
+        ScaleMode::MaxHeight => vertical,
+    }
+}
+
+/// Size the picture is scaled to before any crop or pad
+fn scaled_size(display: (u32, u32), target: (u32, u32), mode: ScaleMode) -> (u32, u32) {
+    let factor = scale_factor(display, target, mode);
+    let (width, height) = (display.0 as f64 * factor, display.1 as f64 * factor);
+    match mode {
+        ScaleMode::Fill => (even_ceil(width), even_ceil(height)),
+        ScaleMode::Stretch => target,
+        _ => (even(width), even(height)),
+    }
+}
+
+/// Size of the output frame for a source shown at `display`
+pub fn output_size(display: (u32, u32), target: (u32, u32), mode: ScaleMode) -> (u32, u32) {
+    match mode {
+        ScaleMode::Fill | ScaleMode::Pad => (even(target.0 as f64), even(target.1 as f64)),
+        _ => scaled_size(display, target, mode),
+    }
+}
+
+/// Check if a source already fits the target without being scaled
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn within_target(display: (u32, u32), target: (u32, u32), mode: ScaleMode) -> bool {
+    match mode {
+        ScaleMode::Fit | ScaleMode::MaxHeight => scale_factor(display, target, mode) >= 1.0,
+        _ => display == output_size(display, target, mode),
+    }
+}
+
+/// Filters that bring the decoded (already rotated) frames to the target size
+pub fn scale_filters(info: &VideoInfo, target: (u32, u32), mode: ScaleMode) -> Vec<String> {
+    let display = info.display_size();
+    if mode == ScaleMode::Stretch {
+        return vec![format!("scale={}:{}", target.0, target.1)];
+    }
+    if display.0 == 0 || display.1 == 0 {
+        return fallback_filters(target, mode);
+    }
+
+    // Scaling to the display size makes the pixels square
+    let (width, height) = scaled_size(display, target, mode);
+    let (out_width, out_height) = output_size(display, target, mode);
+    let mut filters = vec![
+        format!("scale={}:{}", width, height),
+        "setsar=1".to_string(),
+    ];
+    match mode {
+        ScaleMode::Fill => filters.push(format!("crop={}:{}", out_width, out_height)),
+        ScaleMode::Pad => filters.push(format!(
+            "pad={}:{}:(ow-iw)/2:(oh-ih)/2",
+            out_width, out_height
+        )),
+        _ => {}
+    }
+    filters
+}
+
+/// Let ffmpeg work out the size when the probe did not report one
+fn fallback_filters((width, height): (u32, u32), mode: ScaleMode) -> Vec<String> {
+    let keep_aspect = |fit: &str| {
+        format!(
+            "scale={}:{}:force_original_aspect_ratio={}:force_divisible_by=2",
+            width, height, fit
+        )
+    };
+    match mode {
+        ScaleMode::Fit => vec![keep_aspect("decrease")],
+        ScaleMode::Fill => vec![
+            keep_aspect("increase"),
+            format!("crop={}:{}", width, height),
+        ],
+        ScaleMode::Pad => vec![
+            keep_aspect("decrease"),
+            format!("pad={}:{}:(ow-iw)/2:(oh-ih)/2", width, height),
+        ],
+        ScaleMode::MaxHeight => vec![format!("scale=-2:{}", height)],
+        ScaleMode::Stretch => vec![format!("scale={}:{}", width, height)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HD: (u32, u32) = (1280, 720);
+
+    #[test]
+    fn test_output_sizes() {
+        // 4:3 source into a 16:9 box
+        assert_eq!(output_size((1440, 1080), HD, ScaleMode::Fit), (960, 720));
+        assert_eq!(output_size((1440, 1080), HD, ScaleMode::Pad), HD);
+        assert_eq!(scaled_size((1440, 1080), HD, ScaleMode::Fill), (1280, 960));
+        assert_eq!(
+            output_size((1440, 1080), HD, ScaleMode::MaxHeight),
+            (960, 720)
+        );
+        assert_eq!(output_size((1440, 1080), HD, ScaleMode::Stretch), HD);
+
+        // Portrait phone video keeps its orientation when fitted
+        assert_eq!(output_size((1080, 1920), HD, ScaleMode::Fit), (720, 1280));
+        assert_eq!(
+            output_size((1080, 1920), HD, ScaleMode::MaxHeight),
+            (406, 720)
+        );
+        assert_eq!(scaled_size((1080, 1920), HD, ScaleMode::Pad), (406, 720));
+    }
+
+    #[test]
+    fn test_within_target() {
+        assert!(within_target((1280, 536), HD, ScaleMode::Fit));
+        assert!(within_target((720, 1280), HD, ScaleMode::Fit));
+        assert!(!within_target((1920, 1080), HD, ScaleMode::Fit));
+        assert!(!within_target((1280, 536), HD, ScaleMode::Pad));
+        assert!(within_target(HD, HD, ScaleMode::Pad));
+        assert!(scale_factor((640, 360), HD, ScaleMode::Fit) > 1.0);
+    }
+}
@@ -57,10 +57,23 @@ impl SessionManager {
             [],
         )?;
 
+        // Columns added after the initial schema
+        add_column_if_missing(
+            &conn,
+            "jobs",
+            "already_compliant",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        add_column_if_missing(&conn, "jobs", "quality", "TEXT")?;
+        add_column_if_missing(&conn, "jobs", "quality_warning", "TEXT")?;
+        add_column_if_missing(&conn, "jobs", "effective_settings", "TEXT")?;
+        add_column_if_missing(&conn, "jobs", "adjustments", "TEXT NOT NULL DEFAULT '[]'")?;
+
         Ok(())
     }
 
     /// Create a new session
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub fn create_session(&self, name: String) -> Result<Session> {
         let conn = self.get_connection()?;
         let now = chrono::Utc::now();
@@ -82,6 +95,7 @@ impl SessionManager {
     }
 
     /// Get all sessions
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub fn get_sessions(&self) -> Result<Vec<Session>> {
         let conn = self.get_connection()?;
         let mut stmt = conn.prepare(
@@ -109,6 +123,7 @@ impl SessionManager {
     }
 
     /// Get a session by ID
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub fn get_session(&self, id: i64) -> Result<Option<Session>> {
         let conn = self.get_connection()?;
         let mut stmt =
@@ -135,6 +150,7 @@ impl SessionManager {
     }
 
     /// Update session
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub fn update_session(&self, id: i64, name: String) -> Result<()> {
         let conn = self.get_connection()?;
         let now = chrono::Utc::now().to_rfc3339();
@@ -148,6 +164,7 @@ impl SessionManager {
     }
 
     /// Delete session
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     pub fn delete_session(&self, id: i64) -> Result<()> {
         let conn = self.get_connection()?;
         conn.execute("DELETE FROM sessions WHERE id = ?1", params![id])?;
@@ -156,10 +173,12 @@ impl SessionManager {
 
     /// Save jobs to session
     pub fn save_jobs(&self, session_id: i64, jobs: &[Job]) -> Result<()> {
-        let conn = self.get_connection()?;
+        let mut conn = self.get_connection()?;
+        // One transaction, so a failed save leaves the previous jobs in place
+        let tx = conn.transaction()?;
 
         // Delete existing jobs for this session
-        conn.execute(
+        tx.execute(
             "DELETE FROM jobs WHERE session_id = ?1",
             params![session_id],
         )?;
@@ -169,10 +188,21 @@ impl SessionManager {
             let video_info_json = serde_json::to_string(&job.video_info)?;
             let settings_json = serde_json::to_string(&job.settings)?;
             let status_json = serde_json::to_string(&job.status)?;
-
-            conn.execute(
-                "INSERT INTO jobs (id, session_id, input_path, output_path, video_info, settings, status, created_at, started_at, completed_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            let quality_json = job
+                .quality
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?;
+            let effective_settings_json = job
+                .effective_settings
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?;
+            let adjustments_json = serde_json::to_string(&job.adjustments)?;
+
+            tx.execute(
+                "INSERT INTO jobs (id, session_id, input_path, output_path, video_info, settings, status, created_at, started_at, completed_at, already_compliant, quality, quality_warning, effective_settings, adjustments)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
                 params![
                     job.id,
                     session_id,
@@ -184,17 +214,23 @@ impl SessionManager {
                     job.created_at.to_rfc3339(),
                     job.started_at.map(|dt| dt.to_rfc3339()),
                     job.completed_at.map(|dt| dt.to_rfc3339()),
+                    job.already_compliant,
+                    quality_json,
+                    job.quality_warning,
+                    effective_settings_json,
+                    adjustments_json,
                 ],
             )?;
         }
 
         // Update session timestamp
         let now = chrono::Utc::now().to_rfc3339();
-        conn.execute(
+        tx.execute(
             "UPDATE sessions SET updated_at = ?1 WHERE id = ?2",
             params![now, session_id],
         )?;
 
+        tx.commit()?;
         Ok(())
     }
 
@@ -202,7 +238,7 @@ impl SessionManager {
     pub fn load_jobs(&self, session_id: i64) -> Result<Vec<Job>> {
         let conn = self.get_connection()?;
         let mut stmt = conn.prepare(
-            "SELECT id, input_path, output_path, video_info, settings, status, created_at, started_at, completed_at
+            "SELECT id, input_path, output_path, video_info, settings, status, created_at, started_at, completed_at, already_compliant, quality, quality_warning, effective_settings, adjustments
              FROM jobs WHERE session_id = ?1"
         )?;
 
@@ -211,6 +247,9 @@ impl SessionManager {
                 let video_info_json: String = row.get(3)?;
                 let settings_json: String = row.get(4)?;
                 let status_json: String = row.get(5)?;
+                let quality_json: Option<String> = row.get(10)?;
+                let effective_settings_json: Option<String> = row.get(12)?;
+                let adjustments_json: String = row.get(13)?;
 
                 Ok(Job {
                     id: row.get(0)?,
@@ -229,6 +268,12 @@ impl SessionManager {
                     completed_at: row
                         .get::<_, Option<String>>(8)?
                         .and_then(|s| s.parse::<chrono::DateTime<chrono::Utc>>().ok()),
+                    already_compliant: row.get(9)?,
+                    quality: quality_json.and_then(|q| serde_json::from_str(&q).ok()),
+                    quality_warning: row.get(11)?,
+                    effective_settings: effective_settings_json
+                        .and_then(|s| serde_json::from_str(&s).ok()),
+                    adjustments: serde_json::from_str(&adjustments_json).unwrap_or_default(),
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -263,3 +308,27 @@ impl SessionManager {
         }
     }
 }
+
+/// Add a column to an existing table if an older database does not have it yet
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
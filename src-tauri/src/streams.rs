@@ -0,0 +1,334 @@
+use crate::probe::{StreamInfo, StreamType, VideoInfo};
+use serde::{Deserialize, Serialize};
+
+/// Which audio and subtitle streams to keep in the output
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum StreamSelection {
+    /// Keep every video, audio and subtitle stream and attachments such as fonts
+    #[default]
+    KeepAll,
+    /// Keep audio and subtitle streams whose language tag is in the list
+    Languages(Vec<String>),
+    /// Keep exactly the streams with these input indices
+    Indices(Vec<u32>),
+}
+
+/// Codec used for a single output stream
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum StreamCodec {
+    Copy,
+    Encode(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StreamCodecOverride {
+    /// Input stream index
+    pub index: u32,
+    pub codec: StreamCodec,
+}
+
+/// Stream mapping rules for a job
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamMapping {
+    #[serde(default)]
+    pub selection: StreamSelection,
+    #[serde(default)]
+    pub codec_overrides: Vec<StreamCodecOverride>,
+    /// Input index of the audio stream flagged as default
+    #[serde(default)]
+    pub default_audio: Option<u32>,
+    /// Input index of the subtitle stream flagged as default
+    #[serde(default)]
+    pub default_subtitle: Option<u32>,
+    /// Input indices of subtitle streams flagged as forced
+    #[serde(default)]
+    pub forced_subtitles: Vec<u32>,
+    /// Input indices in the desired output order; unlisted streams follow in file order
+    #[serde(default)]
+    pub order: Vec<u32>,
+}
+
+/// A stream that will be written to the output
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MappedStream {
+    pub input_index: u32,
+    pub stream_type: StreamType,
+    /// Position among output streams of the same type (the `N` in `-c:a:N`)
+    pub output_index: usize,
+    /// Per-stream codec, if it differs from the job-wide codec
+    pub codec: Option<StreamCodec>,
+    /// Disposition flags to set, if any are controlled by the mapping
+    pub disposition: Option<String>,
+}
+
+/// Resolve mapping rules against the probed streams
+pub fn plan_streams(info: &VideoInfo, mapping: &StreamMapping) -> Vec<MappedStream> {
+    let mut selected: Vec<&StreamInfo> = info
+        .streams
+        .iter()
+        .filter(|s| is_selected(s, &mapping.selection))
+        .collect();
+
+    // Never drop all audio because of a language filter that matches nothing
+    if let StreamSelection::Languages(_) = mapping.selection {
+        if !selected.iter().any(|s| s.stream_type == StreamType::Audio) {
+            if let Some(audio) = info.streams_of(StreamType::Audio).next() {
+                selected.push(audio);
+            }
+        }
+    }
+
+    selected.sort_by_key(|s| {
+        let position = mapping
+            .order
+            .iter()
+            .position(|&i| i == s.index)
+            .unwrap_or(usize::MAX);
+        (position, s.index)
+    });
+
+    let mut counters = [0usize; 5];
+    selected
+        .into_iter()
+        .map(|s| {
+            let counter = &mut counters[type_slot(s.stream_type)];
+            let output_index = *counter;
+            *counter += 1;
+
+            MappedStream {
+                input_index: s.index,
+                stream_type: s.stream_type,
+                output_index,
+                codec: mapping
+                    .codec_overrides
+                    .iter()
+                    .find(|o| o.index == s.index)
+                    .map(|o| o.codec.clone()),
+                disposition: disposition_for(s, mapping),
+            }
+        })
+        .collect()
+}
+
+/// Recompute per-type output indices, e.g. after streams were dropped from a plan
+pub fn renumber_streams(plan: &mut [MappedStream]) {
+    let mut counters = [0usize; 5];
+    for stream in plan.iter_mut() {
+        let counter = &mut counters[type_slot(stream.stream_type)];
+        stream.output_index = *counter;
+        *counter += 1;
+    }
+}
+
+/// Check if a container can store attachment and data streams, such as embedded fonts
+pub fn holds_attachments(container: &str) -> bool {
+    container == "mkv"
+}
+
+/// Copy attachment and data streams into containers that hold them and drop them otherwise
+///
+/// Neither can be encoded, so ffmpeg needs an explicit `copy` for them.
+pub fn resolve_attachments(plan: &mut Vec<MappedStream>, container: &str) {
+    let keep = holds_attachments(container);
+    plan.retain_mut(|stream| {
+        if !matches!(
+            stream.stream_type,
+            StreamType::Attachment | StreamType::Data
+        ) {
+            return true;
+        }
+        stream.codec = Some(StreamCodec::Copy);
+        keep
+    });
+    renumber_streams(plan);
+}
+
+/// Translate a stream plan into `-map`, per-stream codec and disposition arguments
+pub fn stream_mapping_args(plan: &[MappedStream]) -> Vec<String> {
+    let mut args = Vec::new();
+
+    for stream in plan {
+        args.push("-map".to_string());
+        args.push(format!("0:{}", stream.input_index));
+    }
+
+    for stream in plan {
+        let specifier = format!(
+            "{}:{}",
+            type_specifier(stream.stream_type),
+            stream.output_index
+        );
+
+        if let Some(codec) = &stream.codec {
+            args.push(format!("-c:{}", specifier));
+            args.push(match codec {
+                StreamCodec::Copy => "copy".to_string(),
+                StreamCodec::Encode(name) => name.clone(),
+            });
+        }
+
+        if let Some(disposition) = &stream.disposition {
+            args.push(format!("-disposition:{}", specifier));
+            args.push(disposition.clone());
+        }
+    }
+
+    args
+}
+
+fn is_selected(stream: &StreamInfo, selection: &StreamSelection) -> bool {
+    match selection {
+        StreamSelection::KeepAll => matches!(
+            stream.stream_type,
+            StreamType::Video | StreamType::Audio | StreamType::Subtitle | StreamType::Attachment
+        ),
+        StreamSelection::Languages(languages) => match stream.stream_type {
+            StreamType::Video => true,
+            StreamType::Audio | StreamType::Subtitle => stream.language.as_ref().is_some_and(|l| {
+                languages
+                    .iter()
+                    .any(|wanted| wanted.eq_ignore_ascii_case(l))
+            }),
+            _ => false,
+        },
+        StreamSelection::Indices(indices) => indices.contains(&stream.index),
+    }
+}
+
+fn disposition_for(stream: &StreamInfo, mapping: &StreamMapping) -> Option<String> {
+    let default_index = match stream.stream_type {
+        StreamType::Audio => mapping.default_audio,
+        StreamType::Subtitle => mapping.default_subtitle,
+        _ => None,
+    };
+    let controls_forced =
+        stream.stream_type == StreamType::Subtitle && !mapping.forced_subtitles.is_empty();
+
+    // Leave the source disposition alone unless the mapping controls this stream type
+    if default_index.is_none() && !controls_forced {
+        return None;
+    }
+
+    let is_default = match default_index {
+        Some(index) => index == stream.index,
+        None => stream.disposition.default,
+    };
+    let is_forced = if controls_forced {
+        mapping.forced_subtitles.contains(&stream.index)
+    } else {
+        stream.disposition.forced
+    };
+
+    let mut flags = Vec::new();
+    if is_default {
+        flags.push("default");
+    }
+    if is_forced {
+        flags.push("forced");
+    }
+
+    Some(if flags.is_empty() {
+        "0".to_string()
+    } else {
+        flags.join("+")
+    })
+}
+
+fn type_slot(stream_type: StreamType) -> usize {
+    match stream_type {
+        StreamType::Video => 0,
+        StreamType::Audio => 1,
+        StreamType::Subtitle => 2,
+        StreamType::Attachment => 3,
+        StreamType::Data => 4,
+    }
+}
+
+fn type_specifier(stream_type: StreamType) -> &'static str {
+    match stream_type {
+        StreamType::Video => "v",
+        StreamType::Audio => "a",
+        StreamType::Subtitle => "s",
+        StreamType::Attachment => "t",
+        StreamType::Data => "d",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multi_language_info() -> VideoInfo {
+        let stream = |index, stream_type, codec, language: Option<&str>| StreamInfo {
+            language: language.map(str::to_string),
+            ..StreamInfo::test_stream(index, stream_type, codec)
+        };
+        VideoInfo {
+            streams: vec![
+                stream(0, StreamType::Video, "h264", None),
+                stream(1, StreamType::Audio, "aac", Some("eng")),
+                stream(2, StreamType::Audio, "aac", Some("jpn")),
+                stream(3, StreamType::Subtitle, "subrip", Some("eng")),
+                stream(4, StreamType::Subtitle, "subrip", Some("jpn")),
+                stream(5, StreamType::Attachment, "ttf", None),
+            ],
+            ..VideoInfo::test_default()
+        }
+    }
+
+    #[test]
+    fn test_keep_all_keeps_attachments() {
+        let plan = plan_streams(&multi_language_info(), &StreamMapping::default());
+        let indices: Vec<u32> = plan.iter().map(|s| s.input_index).collect();
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(plan[2].output_index, 1);
+    }
+
+    #[test]
+    fn test_resolve_attachments() {
+        let plan = plan_streams(&multi_language_info(), &StreamMapping::default());
+
+        let mut mkv = plan.clone();
+        resolve_attachments(&mut mkv, "mkv");
+        assert!(stream_mapping_args(&mkv)
+            .join(" ")
+            .contains("-map 0:5 -c:t:0 copy"));
+
+        let mut mp4 = plan;
+        resolve_attachments(&mut mp4, "mp4");
+        assert!(mp4.iter().all(|s| s.stream_type != StreamType::Attachment));
+        assert_eq!(mp4.len(), 5);
+    }
+
+    #[test]
+    fn test_language_selection_and_order() {
+        let mapping = StreamMapping {
+            selection: StreamSelection::Languages(vec!["jpn".to_string()]),
+            order: vec![0, 4, 2],
+            ..Default::default()
+        };
+        let plan = plan_streams(&multi_language_info(), &mapping);
+        let indices: Vec<u32> = plan.iter().map(|s| s.input_index).collect();
+        assert_eq!(indices, vec![0, 4, 2]);
+    }
+
+    #[test]
+    fn test_codec_override_and_disposition_args() {
+        let mapping = StreamMapping {
+            codec_overrides: vec![StreamCodecOverride {
+                index: 2,
+                codec: StreamCodec::Copy,
+            }],
+            default_audio: Some(2),
+            ..Default::default()
+        };
+        let plan = plan_streams(&multi_language_info(), &mapping);
+        let args = stream_mapping_args(&plan).join(" ");
+
+        assert!(args.contains("-map 0:0 -map 0:1 -map 0:2"));
+        assert!(args.contains("-c:a:1 copy"));
+        assert!(args.contains("-disposition:a:0 0"));
+        assert!(args.contains("-disposition:a:1 default"));
+        assert!(!args.contains("-disposition:s"));
+    }
+}
@@ -0,0 +1,386 @@
+use crate::probe::{StreamType, SubtitleFormat, VideoInfo};
+use crate::streams::{renumber_streams, MappedStream, StreamCodec};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// How subtitles are carried into the output
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum SubtitleMode {
+    /// Leave subtitles to ffmpeg's default stream selection; external files
+    /// switch to mapping every stream as in `Keep`
+    #[default]
+    Auto,
+    /// Drop all subtitles
+    Remove,
+    /// Keep embedded and external subtitles, converting them when the container requires it
+    Keep,
+    /// Render subtitles into the picture
+    BurnIn(BurnInSource),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum BurnInSource {
+    /// Input stream index of an embedded text subtitle
+    Stream(u32),
+    /// External `.srt`/`.ass`/`.vtt` file
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExternalSubtitle {
+    pub path: PathBuf,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubtitleSettings {
+    #[serde(default)]
+    pub mode: SubtitleMode,
+    /// Pick up subtitle files next to each input when adding jobs
+    #[serde(default)]
+    pub use_sidecar_files: bool,
+    /// External subtitle files muxed into the output in `Auto` and `Keep` mode
+    #[serde(default)]
+    pub external_files: Vec<ExternalSubtitle>,
+}
+
+impl SubtitleSettings {
+    /// Check if every subtitle stream is mapped explicitly, embedded and external
+    pub fn maps_all_subtitles(&self) -> bool {
+        match self.mode {
+            SubtitleMode::Keep => true,
+            SubtitleMode::Auto => !self.external_files.is_empty(),
+            SubtitleMode::Remove | SubtitleMode::BurnIn(_) => false,
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "vtt"];
+
+/// Subtitle format of an external file, based on its extension
+pub fn subtitle_format_from_path(path: &Path) -> Option<SubtitleFormat> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "srt" => Some(SubtitleFormat::Srt),
+        "ass" | "ssa" => Some(SubtitleFormat::Ass),
+        "vtt" => Some(SubtitleFormat::WebVtt),
+        _ => None,
+    }
+}
+
+/// Find subtitle files next to a video (`movie.srt`, `movie.en.srt`, ...)
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn find_sidecar_subtitles(video: &Path) -> Vec<ExternalSubtitle> {
+    let (Some(dir), Some(stem)) = (video.parent(), video.file_stem().and_then(|s| s.to_str()))
+    else {
+        return Vec::new();
+    };
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut subtitles: Vec<ExternalSubtitle> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let ext = path.extension()?.to_str()?.to_lowercase();
+            if !SUBTITLE_EXTENSIONS.contains(&ext.as_str()) {
+                return None;
+            }
+
+            // Whatever sits between the video stem and the extension is the language tag
+            let sub_stem = path.file_stem()?.to_str()?;
+            let language = if sub_stem == stem {
+                None
+            } else {
+                let tag = sub_stem.strip_prefix(stem)?.strip_prefix('.')?;
+                Some(tag.to_string())
+            };
+
+            Some(ExternalSubtitle { path, language })
+        })
+        .collect();
+
+    subtitles.sort_by(|a, b| a.path.cmp(&b.path));
+    subtitles
+}
+
+/// Encoder needed to store a subtitle format in a container, or `None` if it cannot be stored
+pub fn subtitle_codec_for(output_format: &str, format: &SubtitleFormat) -> Option<&'static str> {
+    match output_format {
+        "mkv" => match format {
+            // Matroska has no mov_text codec id
+            SubtitleFormat::MovText => Some("srt"),
+            _ => Some("copy"),
+        },
+        "mp4" | "m4v" | "mov" => match format {
+            SubtitleFormat::MovText => Some("copy"),
+            f if f.is_text() => Some("mov_text"),
+            _ => None,
+        },
+        "webm" => match format {
+            SubtitleFormat::WebVtt => Some("copy"),
+            f if f.is_text() => Some("webvtt"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Drop subtitle streams the container cannot hold and pick a codec for the rest
+///
+/// Returns a note for every dropped stream.
+pub fn resolve_subtitle_codecs(
+    plan: &mut Vec<MappedStream>,
+    info: &VideoInfo,
+    output_format: &str,
+) -> Vec<String> {
+    let mut dropped = Vec::new();
+    plan.retain_mut(|stream| {
+        if stream.stream_type != StreamType::Subtitle {
+            return true;
+        }
+
+        let format = info
+            .streams
+            .iter()
+            .find(|s| s.index == stream.input_index)
+            .and_then(|s| s.subtitle_format.clone());
+
+        match format
+            .as_ref()
+            .and_then(|f| subtitle_codec_for(output_format, f))
+        {
+            Some(codec) => {
+                if stream.codec.is_none() {
+                    stream.codec = Some(match codec {
+                        "copy" => StreamCodec::Copy,
+                        name => StreamCodec::Encode(name.to_string()),
+                    });
+                }
+                true
+            }
+            None => {
+                dropped.push(format!(
+                    "Subtitle stream {} left out: not supported in {}",
+                    stream.input_index, output_format
+                ));
+                false
+            }
+        }
+    });
+
+    renumber_streams(plan);
+    dropped
+}
+
+/// Encoder for an external subtitle file, or `None` if the container cannot hold it
+fn external_subtitle_codec(file: &ExternalSubtitle, output_format: &str) -> Option<&'static str> {
+    subtitle_format_from_path(&file.path)
+        .and_then(|format| subtitle_codec_for(output_format, &format))
+}
+
+/// Notes for the external subtitle files `external_subtitle_args` leaves out
+pub fn unsupported_subtitle_files(files: &[ExternalSubtitle], output_format: &str) -> Vec<String> {
+    files
+        .iter()
+        .filter(|file| external_subtitle_codec(file, output_format).is_none())
+        .map(|file| {
+            format!(
+                "Subtitle file {} left out: not supported in {}",
+                file.path.display(),
+                output_format
+            )
+        })
+        .collect()
+}
+
+/// Extra `-i` inputs and output arguments for external subtitle files
+///
+/// `first_output_index` is the number of subtitle streams already mapped from the main input.
+pub fn external_subtitle_args(
+    files: &[ExternalSubtitle],
+    first_output_index: usize,
+    output_format: &str,
+) -> (Vec<String>, Vec<String>) {
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    let mut output_index = first_output_index;
+
+    for file in files {
+        // Files the container cannot hold are reported by `unsupported_subtitle_files`
+        let Some(codec) = external_subtitle_codec(file, output_format) else {
+            continue;
+        };
+
+        // Input 0 is the video, external files follow in order
+        let input_index = inputs.len() / 2 + 1;
+        inputs.push("-i".to_string());
+        inputs.push(file.path.to_string_lossy().to_string());
+
+        outputs.push("-map".to_string());
+        outputs.push(format!("{}:0", input_index));
+        outputs.push(format!("-c:s:{}", output_index));
+        outputs.push(codec.to_string());
+        if let Some(language) = &file.language {
+            outputs.push(format!("-metadata:s:s:{}", output_index));
+            outputs.push(format!("language={}", language));
+        }
+
+        output_index += 1;
+    }
+
+    (inputs, outputs)
+}
+
+/// Check that subtitle settings can be applied to an input
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub fn validate_subtitles(settings: &SubtitleSettings, info: &VideoInfo) -> Result<()> {
+    match &settings.mode {
+        SubtitleMode::BurnIn(BurnInSource::Stream(index)) => {
+            let stream = info
+                .streams
+                .iter()
+                .find(|s| s.index == *index && s.stream_type == StreamType::Subtitle)
+                .ok_or_else(|| anyhow::anyhow!("Subtitle stream {} not found", index))?;
+
+            if !stream.subtitle_format.as_ref().is_some_and(|f| f.is_text()) {
+                anyhow::bail!(
+                    "Subtitle stream {} is a bitmap format and cannot be burned in",
+                    index
+                );
+            }
+        }
+        SubtitleMode::BurnIn(BurnInSource::File(path)) if !path.exists() => {
+            anyhow::bail!("Subtitle file does not exist: {:?}", path);
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Build the `subtitles` filter used to burn subtitles into the picture
+pub fn burn_in_filter(input: &Path, source: &BurnInSource, info: &VideoInfo) -> String {
+    match source {
+        BurnInSource::Stream(index) => {
+            // The filter addresses subtitle streams by their position among subtitles
+            let position = info
+                .streams_of(StreamType::Subtitle)
+                .position(|s| s.index == *index)
+                .unwrap_or(0);
+            format!("subtitles={}:si={}", escape_filter_path(input), position)
+        }
+        BurnInSource::File(path) => format!("subtitles={}", escape_filter_path(path)),
+    }
+}
+
+/// Escape a path for use as a filter option inside a filtergraph
+fn escape_filter_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+
+    // First level: filter option value
+    let mut value = String::new();
+    for c in path.chars() {
+        if matches!(c, '\\' | '\'' | ':') {
+            value.push('\\');
+        }
+        value.push(c);
+    }
+
+    // Second level: filtergraph description
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '\\' | '\'' | '[' | ']' | ',' | ';') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subtitle_codec_for_container() {
+        assert_eq!(
+            subtitle_codec_for("mkv", &SubtitleFormat::Pgs),
+            Some("copy")
+        );
+        assert_eq!(
+            subtitle_codec_for("mp4", &SubtitleFormat::Srt),
+            Some("mov_text")
+        );
+        assert_eq!(
+            subtitle_codec_for("mkv", &SubtitleFormat::MovText),
+            Some("srt")
+        );
+        assert_eq!(subtitle_codec_for("mp4", &SubtitleFormat::Pgs), None);
+        assert_eq!(
+            subtitle_codec_for("webm", &SubtitleFormat::Ass),
+            Some("webvtt")
+        );
+        assert_eq!(subtitle_codec_for("avi", &SubtitleFormat::Srt), None);
+    }
+
+    #[test]
+    fn test_external_subtitle_args() {
+        let files = vec![
+            ExternalSubtitle {
+                path: PathBuf::from("/videos/movie.en.srt"),
+                language: Some("en".to_string()),
+            },
+            ExternalSubtitle {
+                path: PathBuf::from("/videos/movie.sup"),
+                language: None,
+            },
+        ];
+
+        let (inputs, outputs) = external_subtitle_args(&files, 1, "mp4");
+        assert_eq!(inputs, vec!["-i", "/videos/movie.en.srt"]);
+        assert_eq!(
+            unsupported_subtitle_files(&files, "mp4"),
+            vec!["Subtitle file /videos/movie.sup left out: not supported in mp4"]
+        );
+        assert_eq!(
+            outputs,
+            vec![
+                "-map",
+                "1:0",
+                "-c:s:1",
+                "mov_text",
+                "-metadata:s:s:1",
+                "language=en"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sidecar_files_in_auto_mode() {
+        let mut settings = SubtitleSettings::default();
+        assert!(!settings.maps_all_subtitles());
+
+        settings.external_files.push(ExternalSubtitle {
+            path: PathBuf::from("/videos/movie.srt"),
+            language: None,
+        });
+        assert!(settings.maps_all_subtitles());
+
+        settings.mode = SubtitleMode::Remove;
+        assert!(!settings.maps_all_subtitles());
+    }
+
+    #[test]
+    fn test_escape_filter_path() {
+        assert_eq!(
+            escape_filter_path(Path::new("/media/It's: a, test.srt")),
+            r"/media/It\\\'s\\: a\, test.srt"
+        );
+    }
+}
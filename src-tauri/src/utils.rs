@@ -29,6 +29,7 @@ pub fn generate_unique_filename(path: &Path) -> PathBuf {
 }
 
 /// Scan directory for video files
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 pub fn scan_directory(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
     let video_extensions = vec!["mp4", "mkv", "avi", "mov", "wmv", "flv", "webm", "m4v", "mpg", "mpeg"];
     let mut video_files = Vec::new();
@@ -42,6 +43,7 @@ pub fn scan_directory(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
     Ok(video_files)
 }
 
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 fn scan_directory_recursive(
     dir: &Path,
     extensions: &[&str],
@@ -115,6 +117,7 @@ pub fn parse_resolution(res_str: &str) -> Option<(u32, u32)> {
 }
 
 /// Get common resolution presets
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 pub fn get_resolution_presets() -> Vec<(&'static str, (u32, u32))> {
     vec![
         ("4K (3840x2160)", (3840, 2160)),
@@ -126,6 +129,7 @@ pub fn get_resolution_presets() -> Vec<(&'static str, (u32, u32))> {
 }
 
 /// Calculate bitrate from file size and duration
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 pub fn calculate_bitrate(size_bytes: u64, duration_seconds: f64) -> u64 {
     if duration_seconds <= 0.0 {
         return 0;
@@ -134,6 +138,7 @@ pub fn calculate_bitrate(size_bytes: u64, duration_seconds: f64) -> u64 {
 }
 
 /// Sanitize filename (remove invalid characters)
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
 pub fn sanitize_filename(filename: &str) -> String {
     filename
         .chars()
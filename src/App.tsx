@@ -22,6 +22,56 @@ export interface VideoInfo {
   size: number;
   audio_codec?: string;
   audio_bitrate?: number;
+  streams: StreamInfo[];
+  sample_aspect_ratio?: [number, number];
+  rotation: number;
+  color?: ColorInfo;
+  pix_fmt?: string | null;
+  start_time?: number;
+}
+
+export interface ColorInfo {
+  primaries?: string | null;
+  transfer?: string | null;
+  space?: string | null;
+  range?: string | null;
+  mastering_display?: {
+    red: [number, number];
+    green: [number, number];
+    blue: [number, number];
+    white_point: [number, number];
+    min_luminance: number;
+    max_luminance: number;
+  } | null;
+  content_light?: { max_content: number; max_average: number } | null;
+}
+
+export type StreamType = "Video" | "Audio" | "Subtitle" | "Attachment" | "Data";
+
+export type SubtitleFormat =
+  | "Srt"
+  | "Ass"
+  | "WebVtt"
+  | "MovText"
+  | "Pgs"
+  | "DvdSub"
+  | "DvbSub"
+  | { Other: string };
+
+export interface StreamInfo {
+  index: number;
+  stream_type: StreamType;
+  codec?: string;
+  language?: string;
+  title?: string;
+  bitrate?: number;
+  width?: number;
+  height?: number;
+  channels?: number;
+  channel_layout?: string;
+  sample_rate?: number;
+  disposition: { default: boolean; forced: boolean; commentary: boolean };
+  subtitle_format?: SubtitleFormat;
 }
 
 export interface EncodingSettings {
@@ -29,12 +79,137 @@ export interface EncodingSettings {
   video_codec: string;
   audio_codec: string;
   resolution?: [number, number];
+  scale_mode?: ScaleMode;
+  source_limits?: SourceLimitPolicy;
   bitrate?: number;
   crf?: number;
   preset: string;
   use_hardware: boolean;
   remove_metadata: boolean;
   custom_metadata?: [string, string][];
+  two_pass?: boolean;
+  audio_bitrate?: number;
+  target_size_bytes?: number;
+  target_size_attempts?: number;
+  stream_mapping?: StreamMapping;
+  subtitles?: SubtitleSettings;
+  remux?: boolean;
+  compliance_policy?: CompliancePolicy;
+  quality_check?: QualityCheck;
+  target_quality?: TargetQuality;
+  chunked?: ChunkSettings;
+  tool_paths?: ToolPaths;
+  tuning?: VideoTuning;
+  hdr_mode?: HdrMode;
+  pixel_format?: PixelFormat;
+  bit_depth?: number | null;
+  filters?: VideoFilter[];
+}
+
+export interface VideoTuning {
+  tune?: string | null;
+  profile?: string | null;
+  level?: string | null;
+  gop_size?: number | null;
+  b_frames?: number | null;
+  max_bitrate?: number | null;
+  buffer_size?: number | null;
+}
+
+export interface ToolPaths {
+  ffmpeg?: string | null;
+  ffprobe?: string | null;
+}
+
+export interface ChunkSettings {
+  segment_duration?: number;
+  min_input_duration?: number;
+}
+
+export interface TargetQuality {
+  metric?: "Ssim" | "Vmaf";
+  score: number;
+  min_crf?: number;
+  max_crf?: number;
+  sample_count?: number;
+  sample_duration?: number;
+}
+
+export interface QualityCheck {
+  enabled?: boolean;
+  use_vmaf?: boolean;
+  min_ssim?: number;
+  min_psnr?: number;
+  min_vmaf?: number;
+  on_failure?: "Flag" | "Fail";
+}
+
+export interface QualityMetrics {
+  ssim?: number;
+  psnr?: number;
+  vmaf?: number;
+}
+
+export type CompliancePolicy = "Encode" | "Skip" | "Remux" | "Copy";
+
+export type ScaleMode = "Fit" | "Fill" | "Pad" | "MaxHeight" | "Stretch";
+
+export type SourceLimitPolicy = "Clamp" | "KeepOriginal" | "Reject";
+
+export type HdrMode = "Auto" | "Preserve" | "ToneMap" | "Ignore";
+
+export type PixelFormat = "MatchSource" | "Compatible" | { Custom: string };
+
+export type VideoFilter =
+  | { Crop: { width: number; height: number; x?: number | null; y?: number | null } }
+  | { Rotate: { degrees: number } }
+  | "HorizontalFlip"
+  | "VerticalFlip"
+  | { Deinterlace: { method: "Yadif" | "Bwdif"; double_rate?: boolean } }
+  | { Denoise: { method: "Hqdn3d" | "Nlmeans"; strength: number } }
+  | { Sharpen: { amount: number; size: number } }
+  | { Adjust: { brightness: number; contrast: number; saturation: number; gamma: number } }
+  | { FrameRate: { fps: number } };
+
+export type StreamAction =
+  | "Copy"
+  | { Transcode: { encoder: string } }
+  | { Drop: { reason: string } };
+
+export interface RemuxStream {
+  index: number;
+  stream_type: StreamType;
+  codec?: string;
+  action: StreamAction;
+  disposition?: string;
+}
+
+export type SubtitleMode =
+  | "Auto"
+  | "Remove"
+  | "Keep"
+  | { BurnIn: { Stream: number } | { File: string } };
+
+export interface SubtitleSettings {
+  mode?: SubtitleMode;
+  use_sidecar_files?: boolean;
+  external_files?: { path: string; language?: string }[];
+}
+
+export type StreamSelection =
+  | "KeepAll"
+  | { Languages: string[] }
+  | { Indices: number[] };
+
+export type StreamCodec = "Copy" | { Encode: string };
+
+export interface StreamMapping {
+  selection?: StreamSelection;
+  codec_overrides?: { index: number; codec: StreamCodec }[];
+  default_audio?: number;
+  default_subtitle?: number;
+  forced_subtitles?: number[];
+  order?: number[];
 }
 
 export interface Job {
@@ -47,6 +222,11 @@ export interface Job {
   created_at: string;
   started_at?: string;
   completed_at?: string;
+  already_compliant?: boolean;
+  quality?: QualityMetrics;
+  quality_warning?: string;
+  effective_settings?: EncodingSettings;
+  adjustments?: string[];
 }
 
 export type JobStatus =
@@ -54,17 +234,70 @@ export type JobStatus =
   | { Processing: { progress: number } }
   | { Completed: { output_path: string } }
   | { Failed: { error: string } }
-  | "Paused"
+  | { Paused: { progress: number } }
+  | { Skipped: { reason: string } }
   | "Cancelled";
 
+export interface ToolVersion {
+  version: string;
+  major?: number;
+  minor?: number;
+  configuration: string[];
+  enabled_libs: string[];
+}
+
+export type MediaKind = "Video" | "Audio" | "Subtitle";
+
+export interface CodecEntry {
+  name: string;
+  kind: MediaKind;
+  description: string;
+}
+
+export interface Capabilities {
+  encoders: CodecEntry[];
+  decoders: CodecEntry[];
+  muxers: string[];
+  filters: string[];
+}
+
+export interface CompatibilityError {
+  problems: string[];
+  suggested_video_codec: string;
+  suggested_audio_codec: string;
+  compatible_formats: string[];
+}
+
+export type AddFilesError =
+  | { kind: "incompatible"; details: CompatibilityError }
+  | { kind: "invalid_filters"; details: string }
+  | { kind: "scan"; details: string };
+
+export interface AddedFiles {
+  jobs: Job[];
+  rejected: { path: string; reason: string }[];
+}
+
 export interface SystemInfo {
   ffmpeg_available: boolean;
   ffprobe_available: boolean;
   hardware_encoders: string[];
+  vmaf_available: boolean;
+  ffmpeg_version?: ToolVersion;
+  ffprobe_version?: ToolVersion;
+  capabilities: Capabilities;
   max_concurrent_jobs: number;
   cpu_cores: number;
 }
 
+export interface RecoveredSession {
+  session_id: number;
+  name: string;
+  interrupted_jobs: string[];
+  restarted_jobs: number;
+  pending_jobs: number;
+}
+
 export interface QueueStats {
   total: number;
   pending: number;
@@ -72,6 +305,8 @@ export interface QueueStats {
   completed: number;
   failed: number;
   cancelled: number;
+  paused: number;
+  skipped: number;
 }
 
 const extractPath = (val: any): string | null => {
@@ -112,22 +347,32 @@ function App() {
   const [shouldShutdown, setShouldShutdown] = usePersistentState<boolean>("shouldShutdown", false);
   const [concurrentJobs, setConcurrentJobs] = usePersistentState<number>("concurrentJobs", 1);
   const [activeTab, setActiveTab] = usePersistentState<"queue" | "logs">("activeTab", "queue");
+  const [toolPaths, setToolPaths] = usePersistentState<ToolPaths>("toolPaths", {});
 
   // Non-persistent states
   const [isProcessing, setIsProcessing] = useState(false);
   const [isAboutOpen, setIsAboutOpen] = useState(false);
   const [logs, setLogs] = useState<string[]>([]);
+  const [recoveredSession, setRecoveredSession] = useState<RecoveredSession | null>(null);
 
+  // Point the backend at the configured ffmpeg build and load what it supports
   useEffect(() => {
-    // Load system info
-    invoke<SystemInfo>("get_system_info").then((info) => {
-      setSystemInfo(info);
-      setConcurrentJobs(info.max_concurrent_jobs);
-    });
+    invoke("set_tool_paths", { paths: toolPaths })
+      .then(() => invoke<SystemInfo>("get_system_info"))
+      .then((info) => {
+        setSystemInfo(info);
+        setConcurrentJobs(info.max_concurrent_jobs);
+      })
+      .catch((error) => console.error("Failed to load system info:", error));
+  }, [toolPaths]);
 
+  useEffect(() => {
     // Load jobs
     loadJobs();
 
+    // Offer to continue a session left unfinished by the last run
+    invoke<RecoveredSession | null>("get_recovered_session").then(setRecoveredSession);
+
     // Listen for progress updates
     const unlistenProgress = listen<[string, any]>("encoding-progress", (event) => {
       const [jobId, progress] = event.payload;
@@ -256,19 +501,54 @@ function App() {
           console.log("Output dir:", output);
           console.log("Settings:", settings);
 
-          const newJobs = await invoke<Job[]>("add_files", {
+          const added = await invoke<AddedFiles>("add_files", {
             paths,
             outputDir: output,
             settings,
           });
 
-          console.log("New jobs created:", newJobs);
-          setJobs((prev) => [...prev, ...newJobs]);
+          console.log("New jobs created:", added.jobs);
+          setJobs((prev) => [...prev, ...added.jobs]);
+          reportRejectedInputs(added.rejected);
         }
       }
     } catch (error) {
       console.error("Failed to add files:", error);
-      alert(`Error adding files: ${error}`);
+      reportAddFilesError("files", error);
+    }
+  };
+
+  const reportRejectedInputs = (rejected: AddedFiles["rejected"]) => {
+    if (rejected.length > 0) {
+      const lines = rejected.map(({ path, reason }) => `${path}: ${reason}`);
+      alert(`Some inputs were not added:\n\n${lines.join("\n")}`);
+    }
+  };
+
+  // Offer the nearest valid codecs when the container cannot hold the selected ones
+  const reportAddFilesError = (what: string, error: unknown) => {
+    const addError = error as AddFilesError;
+    if (addError?.kind !== "incompatible") {
+      const details =
+        addError?.kind === "scan" || addError?.kind === "invalid_filters" ? addError.details : error;
+      alert(`Error adding ${what}: ${details}`);
+      return;
+    }
+
+    const { problems, suggested_video_codec, suggested_audio_codec, compatible_formats } =
+      addError.details;
+    const message =
+      `${problems.join("\n")}\n\n` +
+      `Switch to ${suggested_video_codec} and ${suggested_audio_codec}?` +
+      (compatible_formats.length > 0
+        ? ` Or keep the codecs and output ${compatible_formats.join(", ")} instead.`
+        : "");
+    if (confirm(message)) {
+      setSettings({
+        ...settings,
+        video_codec: suggested_video_codec,
+        audio_codec: suggested_audio_codec,
+      });
     }
   };
 
@@ -290,20 +570,21 @@ function App() {
           console.log("Adding directory:", dirPath);
           console.log("Output dir:", output);
 
-          const newJobs = await invoke<Job[]>("add_directory", {
+          const added = await invoke<AddedFiles>("add_directory", {
             dirPath,
             outputDir: output,
             settings,
             recursive: true,
           });
 
-          console.log("New jobs created:", newJobs);
-          setJobs((prev) => [...prev, ...newJobs]);
+          console.log("New jobs created:", added.jobs);
+          setJobs((prev) => [...prev, ...added.jobs]);
+          reportRejectedInputs(added.rejected);
         }
       }
     } catch (error) {
       console.error("Failed to add directory:", error);
-      alert(`Error adding directory: ${error}`);
+      reportAddFilesError("directory", error);
     }
   };
 
@@ -343,6 +624,20 @@ function App() {
   };
 
 
+  const dismissRecoveredSession = async () => {
+    setRecoveredSession(null);
+    try {
+      await invoke("dismiss_recovered_session");
+    } catch (error) {
+      console.error("Failed to dismiss recovered session:", error);
+    }
+  };
+
+  const handleContinueRecovered = async () => {
+    await dismissRecoveredSession();
+    await handleStartProcessing();
+  };
+
   const handleClearCompleted = async () => {
     const completedIds = jobs
       .filter((job) => typeof job.status !== "string" && "Completed" in job.status)
@@ -393,6 +688,32 @@ function App() {
 
       <div className="flex-1 container mx-auto px-4 py-6 flex gap-6 overflow-hidden">
         <div className="flex-1 flex flex-col gap-4 overflow-hidden">
+          {recoveredSession && (
+            <div className="flex items-center justify-between gap-4 rounded-lg border border-yellow-600/40 bg-yellow-900/20 px-4 py-3 text-sm">
+              <span
+                className="text-yellow-200"
+                title="Only chunked encodes continue from their finished segments; other interrupted jobs start over"
+              >
+                Restored "{recoveredSession.name}" with {recoveredSession.pending_jobs} unfinished job
+                {recoveredSession.pending_jobs === 1 ? "" : "s"}
+                {recoveredSession.interrupted_jobs.length > 0 &&
+                  ` (${recoveredSession.interrupted_jobs.length} interrupted` +
+                    (recoveredSession.restarted_jobs > 0
+                      ? `, ${recoveredSession.restarted_jobs} starting over)`
+                      : ")")}
+                .
+              </span>
+              <div className="flex gap-2">
+                <button onClick={handleContinueRecovered} className="btn btn-primary !py-1 !px-3 text-xs">
+                  Continue
+                </button>
+                <button onClick={dismissRecoveredSession} className="btn btn-secondary !py-1 !px-3 text-xs">
+                  Dismiss
+                </button>
+              </div>
+            </div>
+          )}
+
           <StatsPanel stats={stats} />
 
           {/* Tab Navigation */}
@@ -464,6 +785,8 @@ function App() {
             onShouldShutdownChange={setShouldShutdown}
             concurrentJobs={concurrentJobs}
             onConcurrentJobsChange={handleConcurrentJobsChange}
+            toolPaths={toolPaths}
+            onToolPathsChange={setToolPaths}
           />
         </div>
       </div>
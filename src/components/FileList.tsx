@@ -1,5 +1,5 @@
 import { useState } from "react";
-import { Job, JobStatus } from "../App";
+import { Job, JobStatus, VideoInfo } from "../App";
 
 interface FileListProps {
     jobs: Job[];
@@ -115,6 +115,18 @@ export default function FileList({
                                                         <span>{job.video_info.width}x{job.video_info.height}</span>
                                                         <span>{formatDuration(job.video_info.duration)}</span>
                                                         <span>{formatFileSize(job.video_info.size)}</span>
+                                                        {isHdr(job.video_info) && <span className="text-purple-400">HDR</span>}
+                                                        {job.quality?.ssim != null && (
+                                                            <span className={job.quality_warning ? "text-yellow-500" : ""} title={job.quality_warning}>
+                                                                SSIM {job.quality.ssim.toFixed(3)}
+                                                                {job.quality.vmaf != null && ` · VMAF ${job.quality.vmaf.toFixed(1)}`}
+                                                            </span>
+                                                        )}
+                                                        {job.adjustments && job.adjustments.length > 0 && (
+                                                            <span className="text-yellow-500" title={job.adjustments.join("\n")}>
+                                                                Adjusted to source
+                                                            </span>
+                                                        )}
                                                     </div>
                                                 </div>
                                                 <div className="flex items-center gap-2">
@@ -182,6 +194,12 @@ export default function FileList({
 }
 
 // ... formatting helpers need code back
+/** PQ (HDR10) or HLG transfer */
+function isHdr(info: VideoInfo): boolean {
+    const transfer = info.color?.transfer;
+    return transfer === "smpte2084" || transfer === "arib-std-b67";
+}
+
 function formatFileSize(bytes: number): string {
     const units = ["B", "KB", "MB", "GB", "TB"];
     let size = bytes;
@@ -210,13 +228,14 @@ function getStatusInfo(status: JobStatus): {
 } {
     if (typeof status === "string") {
         if (status === "Pending") return { label: "Pending", className: "status-pending" };
-        if (status === "Paused") return { label: "Paused", className: "status-paused" };
         if (status === "Cancelled") return { label: "Cancelled", className: "status-badge bg-gray-600" };
         return { label: status, className: "status-badge" };
     }
     if ("Processing" in status) return { label: "Processing", className: "status-processing", progress: status.Processing.progress };
+    if ("Paused" in status) return { label: "Paused", className: "status-paused", progress: status.Paused.progress };
     if ("Completed" in status) return { label: "Completed", className: "status-completed" };
     if ("Failed" in status) return { label: "Failed", className: "status-failed" };
+    if ("Skipped" in status) return { label: "Skipped", className: "status-badge bg-gray-600" };
     return { label: "Unknown", className: "status-badge" };
 }
 
@@ -0,0 +1,274 @@
+import { VideoFilter } from "../App";
+
+interface FilterChainProps {
+    filters: VideoFilter[];
+    onChange: (filters: VideoFilter[]) => void;
+}
+
+type FilterKind =
+    | "Crop"
+    | "Rotate"
+    | "HorizontalFlip"
+    | "VerticalFlip"
+    | "Deinterlace"
+    | "Denoise"
+    | "Sharpen"
+    | "Adjust"
+    | "FrameRate";
+
+const FILTER_LABELS: Record<FilterKind, string> = {
+    Crop: "Crop",
+    Rotate: "Rotate",
+    HorizontalFlip: "Flip horizontally",
+    VerticalFlip: "Flip vertically",
+    Deinterlace: "Deinterlace",
+    Denoise: "Denoise",
+    Sharpen: "Sharpen",
+    Adjust: "Brightness / contrast",
+    FrameRate: "Frame rate",
+};
+
+/** A new filter with neutral or commonly used parameters */
+function defaultFilter(kind: FilterKind): VideoFilter {
+    switch (kind) {
+        case "Crop":
+            return { Crop: { width: 1920, height: 1080 } };
+        case "Rotate":
+            return { Rotate: { degrees: 90 } };
+        case "HorizontalFlip":
+        case "VerticalFlip":
+            return kind;
+        case "Deinterlace":
+            return { Deinterlace: { method: "Bwdif", double_rate: false } };
+        case "Denoise":
+            return { Denoise: { method: "Hqdn3d", strength: 4 } };
+        case "Sharpen":
+            return { Sharpen: { amount: 1, size: 5 } };
+        case "Adjust":
+            return { Adjust: { brightness: 0, contrast: 1, saturation: 1, gamma: 1 } };
+        case "FrameRate":
+            return { FrameRate: { fps: 30 } };
+    }
+}
+
+function filterKind(filter: VideoFilter): FilterKind {
+    return typeof filter === "string" ? filter : (Object.keys(filter)[0] as FilterKind);
+}
+
+function NumberField({
+    label,
+    value,
+    step,
+    onChange,
+}: {
+    label: string;
+    value: number | null | undefined;
+    step?: number;
+    onChange: (value: number | null) => void;
+}) {
+    return (
+        <label className="flex items-center gap-1 text-xs text-gray-400">
+            {label}
+            <input
+                type="number"
+                step={step ?? 1}
+                value={value ?? ""}
+                onChange={(e) => onChange(e.target.value === "" ? null : Number(e.target.value))}
+                className="input text-sm w-20"
+            />
+        </label>
+    );
+}
+
+/** Parameter inputs for one filter */
+function FilterParams({ filter, onChange }: { filter: VideoFilter; onChange: (filter: VideoFilter) => void }) {
+    if (typeof filter === "string") {
+        return null;
+    }
+    if ("Crop" in filter) {
+        const crop = filter.Crop;
+        const set = (key: keyof typeof crop, value: number | null) =>
+            onChange({ Crop: { ...crop, [key]: value ?? (key === "x" || key === "y" ? null : 0) } });
+        return (
+            <div className="flex flex-wrap gap-2">
+                <NumberField label="W" value={crop.width} onChange={(v) => set("width", v)} />
+                <NumberField label="H" value={crop.height} onChange={(v) => set("height", v)} />
+                <NumberField label="X" value={crop.x} onChange={(v) => set("x", v)} />
+                <NumberField label="Y" value={crop.y} onChange={(v) => set("y", v)} />
+            </div>
+        );
+    }
+    if ("Rotate" in filter) {
+        return (
+            <select
+                value={filter.Rotate.degrees}
+                onChange={(e) => onChange({ Rotate: { degrees: Number(e.target.value) } })}
+                className="select text-sm"
+            >
+                <option value={90}>90° clockwise</option>
+                <option value={180}>180°</option>
+                <option value={270}>90° counter-clockwise</option>
+            </select>
+        );
+    }
+    if ("Deinterlace" in filter) {
+        const deinterlace = filter.Deinterlace;
+        return (
+            <div className="flex items-center gap-2">
+                <select
+                    value={deinterlace.method}
+                    onChange={(e) =>
+                        onChange({ Deinterlace: { ...deinterlace, method: e.target.value as "Yadif" | "Bwdif" } })
+                    }
+                    className="select text-sm"
+                >
+                    <option value="Bwdif">bwdif</option>
+                    <option value="Yadif">yadif</option>
+                </select>
+                <label className="flex items-center gap-1 text-xs text-gray-400">
+                    <input
+                        type="checkbox"
+                        checked={deinterlace.double_rate ?? false}
+                        onChange={(e) => onChange({ Deinterlace: { ...deinterlace, double_rate: e.target.checked } })}
+                    />
+                    Double frame rate
+                </label>
+            </div>
+        );
+    }
+    if ("Denoise" in filter) {
+        const denoise = filter.Denoise;
+        return (
+            <div className="flex items-center gap-2">
+                <select
+                    value={denoise.method}
+                    onChange={(e) =>
+                        onChange({ Denoise: { ...denoise, method: e.target.value as "Hqdn3d" | "Nlmeans" } })
+                    }
+                    className="select text-sm"
+                >
+                    <option value="Hqdn3d">hqdn3d (fast)</option>
+                    <option value="Nlmeans">nlmeans (slow)</option>
+                </select>
+                <NumberField
+                    label="Strength"
+                    value={denoise.strength}
+                    step={0.5}
+                    onChange={(v) => onChange({ Denoise: { ...denoise, strength: v ?? 0 } })}
+                />
+            </div>
+        );
+    }
+    if ("Sharpen" in filter) {
+        const sharpen = filter.Sharpen;
+        return (
+            <div className="flex gap-2">
+                <NumberField
+                    label="Amount"
+                    value={sharpen.amount}
+                    step={0.1}
+                    onChange={(v) => onChange({ Sharpen: { ...sharpen, amount: v ?? 0 } })}
+                />
+                <NumberField
+                    label="Size"
+                    value={sharpen.size}
+                    step={2}
+                    onChange={(v) => onChange({ Sharpen: { ...sharpen, size: v ?? 5 } })}
+                />
+            </div>
+        );
+    }
+    if ("Adjust" in filter) {
+        const adjust = filter.Adjust;
+        const set = (key: keyof typeof adjust, value: number | null) =>
+            onChange({ Adjust: { ...adjust, [key]: value ?? (key === "brightness" ? 0 : 1) } });
+        return (
+            <div className="flex flex-wrap gap-2">
+                <NumberField label="Brightness" value={adjust.brightness} step={0.05} onChange={(v) => set("brightness", v)} />
+                <NumberField label="Contrast" value={adjust.contrast} step={0.05} onChange={(v) => set("contrast", v)} />
+                <NumberField label="Saturation" value={adjust.saturation} step={0.05} onChange={(v) => set("saturation", v)} />
+                <NumberField label="Gamma" value={adjust.gamma} step={0.05} onChange={(v) => set("gamma", v)} />
+            </div>
+        );
+    }
+    return (
+        <NumberField
+            label="FPS"
+            value={filter.FrameRate.fps}
+            step={0.001}
+            onChange={(v) => onChange({ FrameRate: { fps: v ?? 30 } })}
+        />
+    );
+}
+
+/** Ordered list of video filters; they run before scaling, top to bottom */
+export default function FilterChain({ filters, onChange }: FilterChainProps) {
+    const replace = (index: number, filter: VideoFilter) =>
+        onChange(filters.map((f, i) => (i === index ? filter : f)));
+
+    const move = (index: number, offset: number) => {
+        const next = [...filters];
+        const [filter] = next.splice(index, 1);
+        next.splice(index + offset, 0, filter);
+        onChange(next);
+    };
+
+    const add = (kind: FilterKind) => {
+        // Deinterlacing has to see the untouched fields, so it always goes first
+        onChange(kind === "Deinterlace" ? [defaultFilter(kind), ...filters] : [...filters, defaultFilter(kind)]);
+    };
+
+    return (
+        <div className="space-y-2">
+            {filters.map((filter, index) => (
+                <div key={index} className="bg-gray-700/50 rounded p-2 space-y-1">
+                    <div className="flex items-center justify-between">
+                        <span className="text-sm text-gray-300">{FILTER_LABELS[filterKind(filter)]}</span>
+                        <div className="flex gap-1 text-xs">
+                            <button
+                                onClick={() => move(index, -1)}
+                                disabled={index === 0}
+                                className="px-1 text-gray-400 hover:text-white disabled:opacity-30"
+                                title="Move up"
+                            >
+                                ↑
+                            </button>
+                            <button
+                                onClick={() => move(index, 1)}
+                                disabled={index === filters.length - 1}
+                                className="px-1 text-gray-400 hover:text-white disabled:opacity-30"
+                                title="Move down"
+                            >
+                                ↓
+                            </button>
+                            <button
+                                onClick={() => onChange(filters.filter((_, i) => i !== index))}
+                                className="px-1 text-gray-400 hover:text-red-400"
+                                title="Remove"
+                            >
+                                ✕
+                            </button>
+                        </div>
+                    </div>
+                    <FilterParams filter={filter} onChange={(f) => replace(index, f)} />
+                </div>
+            ))}
+            <select
+                value=""
+                onChange={(e) => e.target.value && add(e.target.value as FilterKind)}
+                className="select text-sm"
+            >
+                <option value="">Add filter…</option>
+                {(Object.keys(FILTER_LABELS) as FilterKind[]).map((kind) => (
+                    <option
+                        key={kind}
+                        value={kind}
+                        disabled={kind === "Deinterlace" && filters.some((f) => filterKind(f) === "Deinterlace")}
+                    >
+                        {FILTER_LABELS[kind]}
+                    </option>
+                ))}
+            </select>
+        </div>
+    );
+}
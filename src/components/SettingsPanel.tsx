@@ -1,5 +1,6 @@
 import { useState, useEffect } from "react";
-import { EncodingSettings, SystemInfo } from "../App";
+import { EncodingSettings, PixelFormat, SystemInfo, ToolPaths, VideoTuning } from "../App";
+import FilterChain from "./FilterChain";
 
 interface SettingsPanelProps {
     settings: EncodingSettings;
@@ -11,11 +12,62 @@ interface SettingsPanelProps {
     onShouldShutdownChange: (val: boolean) => void;
     concurrentJobs: number;
     onConcurrentJobsChange: (val: number) => void;
+    toolPaths: ToolPaths;
+    onToolPathsChange: (paths: ToolPaths) => void;
 }
 
 const OUTPUT_FORMATS = ["mp4", "mkv", "avi", "webm", "mov"];
 const VIDEO_CODECS = ["libx264", "libx265", "vp9", "av1"];
 const AUDIO_CODECS = ["aac", "mp3", "opus", "vorbis", "copy"];
+
+// ffmpeg encoders that can produce each codec offered above
+const CODEC_ENCODERS: Record<string, string[]> = {
+    libx264: ["libx264"],
+    libx265: ["libx265"],
+    vp9: ["libvpx-vp9"],
+    av1: ["libsvtav1", "libaom-av1", "librav1e"],
+    aac: ["aac", "libfdk_aac"],
+    mp3: ["libmp3lame"],
+    opus: ["libopus", "opus"],
+    vorbis: ["libvorbis", "vorbis"],
+};
+
+/** Keep the codecs the installed ffmpeg can encode, plus the current selection */
+function supportedCodecs(codecs: string[], systemInfo: SystemInfo | null, selected: string) {
+    // Suggested fixes may pick an encoder that is not one of the listed codecs
+    const options = codecs.includes(selected) ? codecs : [...codecs, selected];
+    const encoders = systemInfo?.capabilities.encoders;
+    if (!encoders || encoders.length === 0) {
+        return options;
+    }
+    return options.filter(
+        (codec) =>
+            codec === selected ||
+            !CODEC_ENCODERS[codec] ||
+            CODEC_ENCODERS[codec].some((name) => encoders.some((e) => e.name === name))
+    );
+}
+
+/** Tone mapping needs ffmpeg built with zimg; assume it is there until capabilities are known */
+function supportsToneMapping(systemInfo: SystemInfo | null) {
+    const filters = systemInfo?.capabilities.filters;
+    return !filters || filters.length === 0 || (filters.includes("zscale") && filters.includes("tonemap"));
+}
+
+const CUSTOM_PIXEL_FORMATS = ["yuv420p10le", "yuv422p", "yuv422p10le", "yuv444p", "yuv444p10le"];
+
+/** Select value for a pixel format: the variant name, or the ffmpeg name of a custom format */
+function pixelFormatValue(format: PixelFormat | undefined): string {
+    if (!format) {
+        return "MatchSource";
+    }
+    return typeof format === "string" ? format : format.Custom;
+}
+
+function parsePixelFormat(value: string): PixelFormat {
+    return value === "MatchSource" || value === "Compatible" ? value : { Custom: value };
+}
+
 const PRESETS = ["ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow"];
 const RESOLUTIONS = [
     { label: "Original", value: null },
@@ -24,6 +76,13 @@ const RESOLUTIONS = [
     { label: "720p (1280x720)", value: [1280, 720] as [number, number] },
     { label: "480p (854x480)", value: [854, 480] as [number, number] },
 ];
+const SCALE_MODES = [
+    { label: "Fit within (keep aspect)", value: "Fit" },
+    { label: "Fill and crop", value: "Fill" },
+    { label: "Pad to exact size", value: "Pad" },
+    { label: "Limit height only", value: "MaxHeight" },
+    { label: "Stretch", value: "Stretch" },
+];
 
 export default function SettingsPanel({
     settings,
@@ -35,8 +94,11 @@ export default function SettingsPanel({
     onShouldShutdownChange,
     concurrentJobs,
     onConcurrentJobsChange,
+    toolPaths,
+    onToolPathsChange,
 }: SettingsPanelProps) {
     const [useCRF, setUseCRF] = useState(settings.crf !== undefined);
+    const canToneMap = supportsToneMapping(systemInfo);
 
     useEffect(() => {
         if (useCRF && settings.bitrate) {
@@ -50,6 +112,14 @@ export default function SettingsPanel({
         onSettingsChange({ ...settings, [key]: value });
     };
 
+    // Empty fields fall back to the encoder defaults
+    const handleTuningChange = (key: keyof VideoTuning, value: string | number | null) => {
+        handleChange("tuning", { ...settings.tuning, [key]: value === "" ? null : value });
+    };
+
+    const parseOptional = (value: string, scale = 1) =>
+        value === "" || isNaN(parseInt(value)) ? null : parseInt(value) * scale;
+
     return (
         <div className="card sticky top-24 max-h-[calc(100vh-8rem)] overflow-y-auto">
             <h2 className="text-lg font-semibold mb-4 flex items-center gap-2">
@@ -122,7 +192,7 @@ export default function SettingsPanel({
                         onChange={(e) => handleChange("video_codec", e.target.value)}
                         className="select"
                     >
-                        {VIDEO_CODECS.map((codec) => (
+                        {supportedCodecs(VIDEO_CODECS, systemInfo, settings.video_codec).map((codec) => (
                             <option key={codec} value={codec}>
                                 {codec.toUpperCase()}
                             </option>
@@ -140,7 +210,7 @@ export default function SettingsPanel({
                         onChange={(e) => handleChange("audio_codec", e.target.value)}
                         className="select"
                     >
-                        {AUDIO_CODECS.map((codec) => (
+                        {supportedCodecs(AUDIO_CODECS, systemInfo, settings.audio_codec).map((codec) => (
                             <option key={codec} value={codec}>
                                 {codec.toUpperCase()}
                             </option>
@@ -185,6 +255,84 @@ export default function SettingsPanel({
                             </option>
                         ))}
                     </select>
+                    {settings.resolution && (
+                        <select
+                            value={settings.scale_mode ?? "Fit"}
+                            onChange={(e) => handleChange("scale_mode", e.target.value)}
+                            className="select mt-2"
+                        >
+                            {SCALE_MODES.map((mode) => (
+                                <option key={mode.value} value={mode.value}>
+                                    {mode.label}
+                                </option>
+                            ))}
+                        </select>
+                    )}
+                    <label className="block text-xs text-gray-400 mt-2 mb-1">
+                        When the target exceeds the source
+                    </label>
+                    <select
+                        value={settings.source_limits ?? "Clamp"}
+                        onChange={(e) => handleChange("source_limits", e.target.value)}
+                        className="select"
+                    >
+                        <option value="Clamp">Clamp to the source</option>
+                        <option value="KeepOriginal">Keep original resolution</option>
+                        <option value="Reject">Reject the file</option>
+                    </select>
+                </div>
+
+                {/* HDR */}
+                <div>
+                    <label className="block text-sm font-medium text-gray-300 mb-2">
+                        HDR Sources
+                    </label>
+                    <select
+                        value={settings.hdr_mode ?? "Auto"}
+                        onChange={(e) => handleChange("hdr_mode", e.target.value)}
+                        className="select"
+                    >
+                        <option value="Auto">Keep HDR if the codec supports it</option>
+                        <option value="Preserve">Keep HDR (10-bit)</option>
+                        <option value="ToneMap" disabled={!canToneMap}>
+                            Tone map to SDR{canToneMap ? "" : " (needs zscale)"}
+                        </option>
+                        <option value="Ignore">Treat as SDR</option>
+                    </select>
+                </div>
+
+                {/* Pixel Format */}
+                <div>
+                    <label className="block text-sm font-medium text-gray-300 mb-2">
+                        Pixel Format
+                    </label>
+                    <select
+                        value={pixelFormatValue(settings.pixel_format)}
+                        onChange={(e) => handleChange("pixel_format", parsePixelFormat(e.target.value))}
+                        className="select"
+                    >
+                        <option value="MatchSource">Match source</option>
+                        <option value="Compatible">4:2:0 (most compatible)</option>
+                        {CUSTOM_PIXEL_FORMATS.map((format) => (
+                            <option key={format} value={format}>
+                                {format}
+                            </option>
+                        ))}
+                    </select>
+                    {pixelFormatValue(settings.pixel_format) === "MatchSource" && (
+                        <select
+                            value={settings.bit_depth ?? ""}
+                            onChange={(e) =>
+                                handleChange("bit_depth", e.target.value ? parseInt(e.target.value) : null)
+                            }
+                            className="select mt-2"
+                        >
+                            <option value="">Source bit depth</option>
+                            <option value="8">8-bit</option>
+                            <option value="10">10-bit</option>
+                            <option value="12">12-bit</option>
+                        </select>
+                    )}
                 </div>
 
                 {/* Quality Control */}
@@ -250,6 +398,15 @@ export default function SettingsPanel({
                                     className="input"
                                 />
                                 <p className="text-xs text-gray-500 mt-1">Bitrate in kbps</p>
+                                <label className="flex items-center gap-2 cursor-pointer mt-2">
+                                    <input
+                                        type="checkbox"
+                                        checked={settings.two_pass ?? false}
+                                        onChange={(e) => handleChange("two_pass", e.target.checked)}
+                                        className="w-4 h-4 text-primary-500 rounded"
+                                    />
+                                    <span className="text-sm text-gray-300">Two-pass encoding</span>
+                                </label>
                             </div>
                         )}
                     </div>
@@ -276,6 +433,91 @@ export default function SettingsPanel({
                     </p>
                 </div>
 
+                {/* Video Filters */}
+                <details>
+                    <summary className="text-sm font-medium text-gray-300 cursor-pointer">
+                        Video Filters{settings.filters?.length ? ` (${settings.filters.length})` : ""}
+                    </summary>
+                    <div className="mt-2">
+                        <FilterChain
+                            filters={settings.filters ?? []}
+                            onChange={(filters) => handleChange("filters", filters)}
+                        />
+                        <p className="text-xs text-gray-500 mt-1">
+                            Applied top to bottom before scaling
+                        </p>
+                    </div>
+                </details>
+
+                {/* Advanced Encoder Options */}
+                <details>
+                    <summary className="text-sm font-medium text-gray-300 cursor-pointer">
+                        Advanced Encoder Options
+                    </summary>
+                    <div className="grid grid-cols-2 gap-2 mt-2">
+                        <input
+                            type="text"
+                            value={settings.tuning?.tune ?? ""}
+                            onChange={(e) => handleTuningChange("tune", e.target.value)}
+                            placeholder="Tune (e.g. film)"
+                            className="input text-sm"
+                        />
+                        <input
+                            type="text"
+                            value={settings.tuning?.profile ?? ""}
+                            onChange={(e) => handleTuningChange("profile", e.target.value)}
+                            placeholder="Profile (e.g. high)"
+                            className="input text-sm"
+                        />
+                        <input
+                            type="text"
+                            value={settings.tuning?.level ?? ""}
+                            onChange={(e) => handleTuningChange("level", e.target.value)}
+                            placeholder="Level (e.g. 4.1)"
+                            className="input text-sm"
+                        />
+                        <input
+                            type="number"
+                            value={settings.tuning?.gop_size ?? ""}
+                            onChange={(e) =>
+                                handleTuningChange("gop_size", parseOptional(e.target.value))
+                            }
+                            placeholder="GOP size (frames)"
+                            className="input text-sm"
+                        />
+                        <input
+                            type="number"
+                            value={settings.tuning?.b_frames ?? ""}
+                            onChange={(e) =>
+                                handleTuningChange("b_frames", parseOptional(e.target.value))
+                            }
+                            placeholder="B-frames"
+                            className="input text-sm"
+                        />
+                        <input
+                            type="number"
+                            value={settings.tuning?.max_bitrate ? settings.tuning.max_bitrate / 1000 : ""}
+                            onChange={(e) =>
+                                handleTuningChange("max_bitrate", parseOptional(e.target.value, 1000))
+                            }
+                            placeholder="Max rate (kbps)"
+                            className="input text-sm"
+                        />
+                        <input
+                            type="number"
+                            value={settings.tuning?.buffer_size ? settings.tuning.buffer_size / 1000 : ""}
+                            onChange={(e) =>
+                                handleTuningChange("buffer_size", parseOptional(e.target.value, 1000))
+                            }
+                            placeholder="Buffer (kbps)"
+                            className="input text-sm"
+                        />
+                    </div>
+                    <p className="text-xs text-gray-500 mt-1">
+                        Options the selected encoder does not support are skipped
+                    </p>
+                </details>
+
                 {/* Hardware & Performance */}
                 <div className="border-t border-gray-700 pt-4 mt-4">
                     <h3 className="text-sm font-semibold text-gray-400 mb-3 uppercase tracking-wider flex items-center gap-2">
@@ -329,6 +571,41 @@ export default function SettingsPanel({
                                 <span>{systemInfo?.cpu_cores || 8} Cores</span>
                             </div>
                         </div>
+
+                        {/* FFmpeg Binaries */}
+                        <div>
+                            <label className="block text-sm font-medium text-gray-300 mb-2">
+                                FFmpeg Binaries
+                            </label>
+                            <div className="space-y-2">
+                                <input
+                                    type="text"
+                                    value={toolPaths.ffmpeg ?? ""}
+                                    onChange={(e) =>
+                                        onToolPathsChange({ ...toolPaths, ffmpeg: e.target.value || null })
+                                    }
+                                    placeholder="ffmpeg (from PATH)"
+                                    className="input text-sm"
+                                />
+                                <input
+                                    type="text"
+                                    value={toolPaths.ffprobe ?? ""}
+                                    onChange={(e) =>
+                                        onToolPathsChange({ ...toolPaths, ffprobe: e.target.value || null })
+                                    }
+                                    placeholder="ffprobe (from PATH)"
+                                    className="input text-sm"
+                                />
+                            </div>
+                            <p className="text-[10px] text-gray-500 mt-1">
+                                {systemInfo?.ffmpeg_version
+                                    ? `FFmpeg ${systemInfo.ffmpeg_version.version}` +
+                                      (systemInfo.ffmpeg_version.enabled_libs.length > 0
+                                          ? ` with ${systemInfo.ffmpeg_version.enabled_libs.join(", ")}`
+                                          : "")
+                                    : "FFmpeg was not found"}
+                            </p>
+                        </div>
                     </div>
                 </div>
 